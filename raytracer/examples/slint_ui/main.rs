@@ -1,44 +1,85 @@
 extern crate scenes;
 
-use scenes::Scenes;
-use slint::{SharedString, ModelRc, VecModel};
+use raytracer::{CancellationToken, RenderedImage};
+use slint::{Image, ModelRc, Rgba8Pixel, SharedPixelBuffer, SharedString, VecModel};
+use std::cell::RefCell;
+use std::rc::Rc;
 
 slint::include_modules!();
 
-fn main() -> Result<(), slint::PlatformError>{
+fn main() -> Result<(), slint::PlatformError> {
     let main_window = MainWindow::new()?;
     let scenes = scenes::get_scenes()
-                                        .iter()
-                                        .map(|scene| {SharedString::from(*scene)})
-                                        .collect::<Vec<SharedString>>();
+        .iter()
+        .map(|scene| SharedString::from(*scene))
+        .collect::<Vec<SharedString>>();
     let model = ModelRc::new(VecModel::from(scenes));
-    let mut selected = SharedString::new();
-
+    let selected = Rc::new(RefCell::new(SharedString::new()));
 
     main_window.set_items(model.clone());
 
+    let selection = selected.clone();
     main_window.on_selection_changed(move |value| {
-        selected = value;
+        *selection.borrow_mut() = value;
     });
-    let window_handle = main_window.as_weak();
-    let progress_function = |i:f32| {
-        if let Some(main_window) = window_handle.upgrade() {
-            main_window.set_progress(i);
-            main_window.set_is_enabled(i == 100.0);
 
-            // if i == 100 {
-            //     main_window.set_image()
-            // }
-        }
-    };
-    main_window.on_render_clicked(move ||{
+    let cancel_token = Rc::new(RefCell::new(CancellationToken::new()));
+    let token = cancel_token.clone();
+    main_window.on_cancel_clicked(move || token.borrow().cancel());
+
+    let window_handle = main_window.as_weak();
+    main_window.on_render_clicked(move || {
         if let Some(main_window) = window_handle.upgrade() {
             main_window.set_is_enabled(false);
-            // scenes::render_scene_buffer(scenes::name_to_scene(selected.to_string().as_str()),
-            //         progress_function);
-            //
         }
+
+        let scene = scenes::name_to_scene(selected.borrow().as_str());
+        let cancel = CancellationToken::new();
+        *cancel_token.borrow_mut() = cancel.clone();
+        let window_handle = window_handle.clone();
+        // Render on a separate thread to keep the UI responsive, and hand the results back to the
+        // event loop.
+        std::thread::spawn(move || {
+            let progress_handle = window_handle.clone();
+            let progress_function = move |i: f32| {
+                let _ = progress_handle
+                    .upgrade_in_event_loop(move |main_window| main_window.set_progress(i / 100.0));
+            };
+
+            // Show the image after every pass, so that it refines over time.
+            let pass_handle = window_handle.clone();
+            let on_pass = move |rendered: &RenderedImage| {
+                let buffer = to_pixel_buffer(rendered);
+                pass_handle
+                    .upgrade_in_event_loop(move |main_window| {
+                        main_window.set_my_image(Image::from_rgba8(buffer))
+                    })
+                    .is_ok()
+            };
+
+            let rendered =
+                scenes::render_scene_progressive(scene, &cancel, on_pass, progress_function);
+            let buffer = match rendered {
+                Ok(outcome) => Some(to_pixel_buffer(&outcome.into_image())),
+                Err(err) => {
+                    eprintln!("Couldn't render the scene: {err}");
+                    None
+                }
+            };
+
+            let _ = window_handle.upgrade_in_event_loop(move |main_window| {
+                if let Some(buffer) = buffer {
+                    main_window.set_my_image(Image::from_rgba8(buffer));
+                }
+                main_window.set_is_enabled(true);
+            });
+        });
     });
 
     main_window.run()
-}
\ No newline at end of file
+}
+
+fn to_pixel_buffer(rendered: &RenderedImage) -> SharedPixelBuffer<Rgba8Pixel> {
+    let image = rendered.image();
+    SharedPixelBuffer::<Rgba8Pixel>::clone_from_slice(image.as_raw(), image.width(), image.height())
+}
@@ -1,7 +1,5 @@
 extern crate scenes;
 
-use scenes::Scenes;
-
 fn main() {
     print!("Hello World!");
 }
\ No newline at end of file
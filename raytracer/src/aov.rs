@@ -0,0 +1,131 @@
+use crate::framebuffer::FrameBuffer;
+use crate::objects::HitRecord;
+use crate::tonemapping::DisplayTransform;
+use crate::utils::clamp;
+use crate::{Color, Ray};
+use image::RgbaImage;
+use serde::Deserialize;
+
+/// # Arbitrary Output Variables
+/// Besides the final color (often called the *beauty* pass), a renderer can write out other
+/// properties of what the camera sees in each pixel. These extra buffers are called Arbitrary
+/// Output Variables (AOVs). They are taken from the first surface hit by the camera ray of each
+/// sample, are cheap to compute, and are noise free for the most part, which makes them useful as
+/// guides for denoising and for compositing the image afterwards.
+///
+/// * `Albedo` - the base color of the material, without any lighting.
+/// * `Normal` - the shading normal at the hit point.
+/// * `Depth` - the distance from the camera to the hit point.
+/// * `Position` - the hit point in world coordinates.
+/// * `ObjectId` - the index of the object in the world, starting from 1. Rather than averaging the
+///   IDs seen by the samples of a pixel, the pixel gets the ID seen by its first sample.
+///
+/// Other than the object IDs, the samples are weighted with the same reconstruction filter as the
+/// beauty pass, so that the buffers line up with the image.
+///
+/// Pixels where the camera ray doesn't hit anything get the background color for the albedo, and
+/// zero for everything else.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Aov {
+    Albedo,
+    Normal,
+    Depth,
+    Position,
+    ObjectId,
+}
+
+impl Aov {
+    /// Returns the name of the variable, to be used for e.g. as a file name suffix.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Aov::Albedo => "albedo",
+            Aov::Normal => "normal",
+            Aov::Depth => "depth",
+            Aov::Position => "position",
+            Aov::ObjectId => "object_id",
+        }
+    }
+
+    /// Returns the value of the variable for the camera ray `r`, given what it hit.
+    pub(crate) fn value(&self, r: &Ray, hit: Option<&HitRecord>, background: &Color) -> Color {
+        match (self, hit) {
+            (Aov::Albedo, Some(rec)) => rec.mat.albedo(rec),
+            (Aov::Albedo, None) => *background,
+            (Aov::Normal, Some(rec)) => rec.normal,
+            (Aov::Depth, Some(rec)) => {
+                let depth = rec.t * r.direction().length();
+                Color::new(depth, depth, depth)
+            }
+            (Aov::Position, Some(rec)) => rec.p,
+            (Aov::ObjectId, Some(rec)) => {
+                let id = rec.object_id as f64;
+                Color::new(id, id, id)
+            }
+            (_, None) => Color::default(),
+        }
+    }
+
+    /// Converts the buffer of this variable to an 8-bit image for viewing. The albedo is encoded
+    /// like a color, normals are mapped from [-1,1] to [0,1], depth and position are normalized by
+    /// their range over the image, and object IDs are given distinct colors.
+    pub(crate) fn visualize(&self, buffer: &FrameBuffer) -> RgbaImage {
+        let pixels = buffer.pixels();
+        let (min, max) = pixels.iter().fold(
+            (
+                Color::new(f64::MAX, f64::MAX, f64::MAX),
+                Color::new(f64::MIN, f64::MIN, f64::MIN),
+            ),
+            |(min, max), p| {
+                (
+                    Color::new(min.x().min(p.x()), min.y().min(p.y()), min.z().min(p.z())),
+                    Color::new(max.x().max(p.x()), max.y().max(p.y()), max.z().max(p.z())),
+                )
+            },
+        );
+        let normalize = |c: f64, axis: usize| {
+            let range = max[axis] - min[axis];
+            if range > 0.0 {
+                (c - min[axis]) / range
+            } else {
+                0.0
+            }
+        };
+        let to_byte = |c: f64| (256.0 * clamp(c, 0.0, 0.999)) as u8;
+
+        RgbaImage::from_fn(buffer.width(), buffer.height(), |x, y| {
+            let p = pixels[(y * buffer.width() + x) as usize];
+            let [r, g, b] = match self {
+                Aov::Albedo => DisplayTransform::default().apply(&p),
+                Aov::Normal => [
+                    to_byte(0.5 * (p.x() + 1.0)),
+                    to_byte(0.5 * (p.y() + 1.0)),
+                    to_byte(0.5 * (p.z() + 1.0)),
+                ],
+                // Closer is brighter, and misses stay black.
+                Aov::Depth if p.x() > 0.0 => {
+                    let d = to_byte(1.0 - normalize(p.x(), 0));
+                    [d, d, d]
+                }
+                Aov::Depth => [0, 0, 0],
+                Aov::Position => [
+                    to_byte(normalize(p.x(), 0)),
+                    to_byte(normalize(p.y(), 1)),
+                    to_byte(normalize(p.z(), 2)),
+                ],
+                Aov::ObjectId => id_color(p.x().round() as u32),
+            };
+            image::Rgba([r, g, b, 255])
+        })
+    }
+}
+
+/// Returns a color for the object ID, such that neighbouring IDs get very different colors.
+fn id_color(id: u32) -> [u8; 3] {
+    if id == 0 {
+        return [0, 0, 0];
+    }
+
+    let hash = id.wrapping_mul(0x9E37_79B9).rotate_left(13) ^ id.wrapping_mul(0x85EB_CA6B);
+    [(hash >> 16) as u8, (hash >> 8) as u8, hash as u8]
+}
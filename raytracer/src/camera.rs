@@ -1,5 +1,6 @@
 use crate::ray::Ray;
-use crate::utils::{degrees_to_radians, random};
+use crate::samplers::sample_1d;
+use crate::utils::degrees_to_radians;
 use crate::vec3::{Point, Vec3};
 use embed_doc_image::embed_doc_image;
 
@@ -117,7 +118,7 @@ impl Camera {
     /// Returns a camera object with the given settings
     /// # Arguments
     /// * `vfov`: Vertical Field of View in Degrees
-    /// todo: Encapsulate fov into a degrees type, to make the code more readable.
+    ///   todo: Encapsulate fov into a degrees type, to make the code more readable.
     /// * `aspect_ratio`: Aspect Ratio determines the width/length of the viewport.
     ///
     #[allow(clippy::too_many_arguments)]
@@ -168,7 +169,7 @@ impl Camera {
             &(self.lower_left_corner + s * self.horizontal + t * self.vertical
                 - self.origin
                 - offset),
-            random(self.time0, self.time1),
+            self.time0 + sample_1d() * (self.time1 - self.time0),
         )
     }
 }
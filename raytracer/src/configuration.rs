@@ -1,6 +1,14 @@
+use crate::aov::Aov;
+use crate::denoiser::Denoiser;
+use crate::filter::{Filter, FilterType};
+use crate::integrators::IntegratorType;
+use crate::samplers::SamplerType;
+use crate::tonemapping::ToneMapping;
 use config::ConfigError;
 use serde::Deserialize;
-use serde_aux::field_attributes::deserialize_number_from_string;
+use serde_aux::field_attributes::{
+    deserialize_number_from_string, deserialize_option_number_from_string,
+};
 
 #[derive(Deserialize, Clone)]
 struct ImageSettingsImpl {
@@ -12,17 +20,118 @@ struct ImageSettingsImpl {
     samples_per_pixel: u32,
     #[serde(deserialize_with = "deserialize_number_from_string")]
     max_depth: u32,
+    #[serde(
+        default = "default_russian_roulette_depth",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    russian_roulette_depth: u32,
+    #[serde(default)]
+    integrator: IntegratorType,
+    #[serde(default, deserialize_with = "deserialize_option_number_from_string")]
+    ambient_occlusion_distance: Option<f64>,
+    #[serde(
+        default = "default_caustic_photons",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    caustic_photons: u32,
+    #[serde(
+        default = "default_caustic_photon_lookup",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    caustic_photon_lookup: usize,
+    #[serde(default, deserialize_with = "deserialize_option_number_from_string")]
+    caustic_photon_radius: Option<f64>,
+    #[serde(default)]
+    spectral: bool,
     format: ImageFormat,
     path: String,
+    #[serde(
+        default = "default_tile_size",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    tile_size: u32,
+    #[serde(default)]
+    tile_order: TileOrder,
+    #[serde(default, deserialize_with = "deserialize_number_from_string")]
+    seed: u64,
+    #[serde(default)]
+    tone_mapping: ToneMapping,
+    #[serde(default, deserialize_with = "deserialize_number_from_string")]
+    exposure: f64,
+    #[serde(default)]
+    sampler: SamplerType,
+    #[serde(default)]
+    filter: FilterType,
+    #[serde(default, deserialize_with = "deserialize_option_number_from_string")]
+    filter_radius: Option<f64>,
+    #[serde(default)]
+    aovs: Vec<Aov>,
+    #[serde(default)]
+    denoise: bool,
+    #[serde(
+        default = "default_denoise_iterations",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    denoise_iterations: u32,
+    #[serde(default)]
+    adaptive_sampling: bool,
+    #[serde(
+        default = "default_min_samples_per_pixel",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    min_samples_per_pixel: u32,
+    #[serde(
+        default = "default_max_samples_per_pixel",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    max_samples_per_pixel: u32,
+    #[serde(
+        default = "default_noise_threshold",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    noise_threshold: f64,
 }
 
-#[derive(Deserialize, Clone)]
+#[derive(Deserialize, Clone, Copy)]
 #[serde(rename_all = "lowercase")]
 pub enum ImageFormat {
     Jpg,
     Png,
     Ppm,
     Tiff,
+    /// OpenEXR, written as 32-bit float linear radiance.
+    Exr,
+    /// Radiance HDR, written as 32-bit float linear radiance.
+    Hdr,
+}
+
+/// The order in which the tiles of the image are handed to the render threads.
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TileOrder {
+    /// Row by row, from the top left corner of the image.
+    Scanline,
+    /// Spiralling outwards from the center tile of the image.
+    #[default]
+    Spiral,
+    /// By the distance of the tile from the center of the image.
+    CenterOut,
+}
+
+/// # Adaptive Sampling
+/// Rather than taking the same number of samples for every pixel, keep sampling only the pixels
+/// which are still noisy. Every pixel first gets `min_samples` samples. After that, the pixels
+/// whose estimated error is above the `noise_threshold` get their number of samples doubled, over
+/// and over, until they fall below the threshold or reach `max_samples`. Flat areas like the
+/// background stop early, and the time saved goes to the noisy parts like caustics and soft
+/// shadows.
+///
+/// The error of a pixel is the standard error of its mean luminance, relative to the luminance.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AdaptiveSampling {
+    pub min_samples: u32,
+    pub max_samples: u32,
+    pub noise_threshold: f64,
 }
 
 #[derive(Clone)]
@@ -31,9 +140,46 @@ pub struct ImageSettings {
     pub width: u32,
     pub height: u32,
     pub samples_per_pixel: u32,
+    /// The most bounces a path can take.
     pub max_depth: u32,
+    /// The number of bounces after which a path may be ended at random, depending on how much light
+    /// it still carries. Setting it to `max_depth` or above traces all paths to the end.
+    pub russian_roulette_depth: u32,
+    /// How the color seen along the camera rays is worked out.
+    pub integrator: IntegratorType,
+    /// How far the ambient occlusion integrator looks for occluders. It defaults to a tenth of the
+    /// size of the scene.
+    pub ambient_occlusion_distance: Option<f64>,
+    /// The number of photons the photon mapping integrator sends out from the lights.
+    pub caustic_photons: u32,
+    /// The number of photons the light of the caustics is estimated from at each point.
+    pub caustic_photon_lookup: usize,
+    /// How far from a point the photons are looked for. It defaults to a hundredth of the size of
+    /// the scene.
+    pub caustic_photon_radius: Option<f64>,
+    /// Whether each path is traced at a single wavelength, so that glass can disperse the light.
+    pub spectral: bool,
     pub format: ImageFormat,
     pub path: String,
+    pub tile_size: u32,
+    pub tile_order: TileOrder,
+    /// The seed for all the random numbers used while building the scene and rendering it. The
+    /// same scene rendered with the same seed always gives the same image.
+    pub seed: u64,
+    pub tone_mapping: ToneMapping,
+    /// Exposure compensation in stops, applied before tone mapping.
+    pub exposure: f64,
+    /// How the pixel, lens, time and scattering dimensions of the samples are picked.
+    pub sampler: SamplerType,
+    /// The reconstruction filter the samples are weighted with.
+    pub filter: Filter,
+    /// The arbitrary output variables to render along with the image.
+    pub aovs: Vec<Aov>,
+    /// The denoiser applied to the radiance before tone mapping, if any. It is guided by the albedo,
+    /// normal and depth passes, which are rendered for it even if they are not in `aovs`.
+    pub denoiser: Option<Denoiser>,
+    /// Takes a varying number of samples per pixel, instead of `samples_per_pixel`, if set.
+    pub adaptive_sampling: Option<AdaptiveSampling>,
 }
 
 impl ImageSettings {
@@ -44,12 +190,74 @@ impl ImageSettings {
             height: settings.height,
             samples_per_pixel: settings.samples_per_pixel,
             max_depth: settings.max_depth,
+            russian_roulette_depth: settings.russian_roulette_depth,
+            integrator: settings.integrator,
+            ambient_occlusion_distance: settings.ambient_occlusion_distance,
+            caustic_photons: settings.caustic_photons,
+            caustic_photon_lookup: settings.caustic_photon_lookup,
+            caustic_photon_radius: settings.caustic_photon_radius,
+            spectral: settings.spectral,
             format: settings.format,
             path: settings.path,
+            tile_size: settings.tile_size.max(1),
+            tile_order: settings.tile_order,
+            seed: settings.seed,
+            tone_mapping: settings.tone_mapping,
+            exposure: settings.exposure,
+            sampler: settings.sampler,
+            filter: Filter::new(
+                settings.filter,
+                settings
+                    .filter_radius
+                    .unwrap_or(settings.filter.default_radius()),
+            ),
+            aovs: settings.aovs,
+            denoiser: settings
+                .denoise
+                .then(|| Denoiser::new(settings.denoise_iterations)),
+            adaptive_sampling: settings.adaptive_sampling.then(|| AdaptiveSampling {
+                min_samples: settings.min_samples_per_pixel.max(1),
+                max_samples: settings
+                    .max_samples_per_pixel
+                    .max(settings.min_samples_per_pixel.max(1)),
+                noise_threshold: settings.noise_threshold,
+            }),
         }
     }
 }
 
+fn default_russian_roulette_depth() -> u32 {
+    3
+}
+
+fn default_caustic_photons() -> u32 {
+    100_000
+}
+
+fn default_caustic_photon_lookup() -> usize {
+    50
+}
+
+fn default_tile_size() -> u32 {
+    16
+}
+
+fn default_denoise_iterations() -> u32 {
+    5
+}
+
+fn default_min_samples_per_pixel() -> u32 {
+    16
+}
+
+fn default_max_samples_per_pixel() -> u32 {
+    1024
+}
+
+fn default_noise_threshold() -> f64 {
+    0.02
+}
+
 pub fn load_configuration() -> Result<ImageSettings, ConfigError> {
     let base_path = std::env::current_dir().expect("Failed to determine the current directory");
     eprintln!("{}", base_path.to_str().unwrap());
@@ -62,3 +270,14 @@ pub fn load_configuration() -> Result<ImageSettings, ConfigError> {
         .try_deserialize::<ImageSettingsImpl>()
         .map(ImageSettings::new)
 }
+
+/// Reads the settings from a YAML string rather than the configuration file.
+#[cfg(test)]
+pub(crate) fn parse_configuration(yaml: &str) -> Result<ImageSettings, ConfigError> {
+    let source = config::File::from_str(yaml, config::FileFormat::Yaml);
+    let settings = config::Config::builder().add_source(source).build()?;
+
+    settings
+        .try_deserialize::<ImageSettingsImpl>()
+        .map(ImageSettings::new)
+}
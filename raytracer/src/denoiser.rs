@@ -0,0 +1,165 @@
+use crate::framebuffer::FrameBuffer;
+use crate::Color;
+use rayon::prelude::*;
+
+/// Weights of the 5-tap B3 spline kernel, which is applied along both axes.
+const KERNEL: [f64; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+
+/// How quickly the weights of the neighbours fall off as their colors, normals, depths and albedos
+/// get further away from those of the filtered pixel. Larger values blur more across the edges.
+const SIGMA_COLOR: f64 = 2.0;
+const SIGMA_NORMAL: f64 = 0.3;
+const SIGMA_DEPTH: f64 = 0.05;
+const SIGMA_ALBEDO: f64 = 0.1;
+
+/// # Denoiser
+/// An edge-avoiding à-trous wavelet filter (Dammertz et al. 2010, "Edge-Avoiding À-Trous Wavelet
+/// Transform for fast Global Illumination Filtering"). Each iteration blurs the image with a 5x5
+/// B3 spline kernel whose taps are spread `2^i` pixels apart, so a few iterations cover a large
+/// footprint at a low cost. To keep the edges sharp, the weight of every tap is scaled down by how
+/// much the neighbour differs from the pixel in color, normal, depth and albedo. The normal, depth
+/// and albedo buffers are (almost) free of noise, so they find the edges reliably even when the
+/// color is very noisy.
+///
+/// The filter works on the lighting arriving at the surfaces rather than the final color: the
+/// radiance is divided by the albedo before filtering and multiplied back afterwards, so that the
+/// detail of the textures isn't blurred away with the noise.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Denoiser {
+    pub iterations: u32,
+}
+
+impl Denoiser {
+    pub fn new(iterations: u32) -> Denoiser {
+        Denoiser { iterations }
+    }
+
+    /// Returns a denoised copy of the `beauty` buffer, using the albedo, normal and depth buffers
+    /// of the same image as guides. The weights and the number of samples of each pixel are kept as
+    /// they are.
+    pub fn denoise(
+        &self,
+        beauty: &FrameBuffer,
+        albedo: &FrameBuffer,
+        normal: &FrameBuffer,
+        depth: &FrameBuffer,
+    ) -> Result<FrameBuffer, String> {
+        let (width, height) = (beauty.width(), beauty.height());
+        if [albedo, normal, depth]
+            .iter()
+            .any(|guide| guide.width() != width || guide.height() != height)
+        {
+            return Err(String::from(
+                "The guide buffers should have the same size as the image",
+            ));
+        }
+
+        let albedo = albedo.pixels();
+        let normal = normal.pixels();
+        let depth = depth.pixels();
+
+        let mut irradiance: Vec<Color> = beauty
+            .pixels()
+            .iter()
+            .zip(albedo.iter())
+            .map(|(color, albedo)| demodulate(color, albedo))
+            .collect();
+
+        for i in 0..self.iterations {
+            let step = 1_i64 << i;
+            // The noise left after each iteration is lower, so be stricter about color differences.
+            let sigma_color = SIGMA_COLOR / (1 << i) as f64;
+
+            irradiance = (0..(width * height) as usize)
+                .into_par_iter()
+                .map(|p| {
+                    let (px, py) = ((p as u32 % width) as i64, (p as u32 / width) as i64);
+                    let color_p = compress(&irradiance[p]);
+                    let mut sum = Color::default();
+                    let mut total_weight = 0.0;
+
+                    for (ky, hy) in KERNEL.iter().enumerate() {
+                        for (kx, hx) in KERNEL.iter().enumerate() {
+                            let qx = px + (kx as i64 - 2) * step;
+                            let qy = py + (ky as i64 - 2) * step;
+                            if qx < 0 || qy < 0 || qx >= width as i64 || qy >= height as i64 {
+                                continue;
+                            }
+                            let q = (qy * width as i64 + qx) as usize;
+
+                            let color_distance = (compress(&irradiance[q]) - color_p)
+                                .length_squared()
+                                / (sigma_color * sigma_color);
+                            let normal_distance = (normal[q] - normal[p]).length_squared()
+                                / (SIGMA_NORMAL * SIGMA_NORMAL);
+                            let albedo_distance = (albedo[q] - albedo[p]).length_squared()
+                                / (SIGMA_ALBEDO * SIGMA_ALBEDO);
+                            // Depths are compared relative to the distance from the camera, as the
+                            // depth of a surface changes faster between pixels the further it is.
+                            let depth_distance = (depth[q].x() - depth[p].x()).abs()
+                                / (SIGMA_DEPTH * depth[p].x().max(depth[q].x()).max(1e-3));
+
+                            let weight = hx
+                                * hy
+                                * (-color_distance
+                                    - normal_distance
+                                    - albedo_distance
+                                    - depth_distance * depth_distance)
+                                    .exp();
+
+                            sum += weight * irradiance[q];
+                            total_weight += weight;
+                        }
+                    }
+
+                    // The center tap always has a weight of (3/8)^2, so the total is never zero.
+                    sum / total_weight
+                })
+                .collect();
+        }
+
+        let mut denoised = beauty.clone();
+        denoised.sum = irradiance
+            .iter()
+            .zip(albedo.iter())
+            .zip(beauty.weights.iter())
+            .map(|((irradiance, albedo), &weight)| remodulate(irradiance, albedo) * weight)
+            .collect();
+
+        Ok(denoised)
+    }
+}
+
+/// Albedos below this are treated as black, and are left out of the (de)modulation.
+const MIN_ALBEDO: f64 = 1e-3;
+
+#[inline]
+fn demodulate(color: &Color, albedo: &Color) -> Color {
+    let channel = |c: f64, a: f64| if a > MIN_ALBEDO { c / a } else { c };
+    Color::new(
+        channel(color.x(), albedo.x()),
+        channel(color.y(), albedo.y()),
+        channel(color.z(), albedo.z()),
+    )
+}
+
+#[inline]
+fn remodulate(irradiance: &Color, albedo: &Color) -> Color {
+    let channel = |c: f64, a: f64| if a > MIN_ALBEDO { c * a } else { c };
+    Color::new(
+        channel(irradiance.x(), albedo.x()),
+        channel(irradiance.y(), albedo.y()),
+        channel(irradiance.z(), albedo.z()),
+    )
+}
+
+/// Compresses the radiance into [0,1) before comparing colors, so that the differences between
+/// bright pixels (like the lights) don't drown out those in the rest of the image.
+#[inline]
+fn compress(color: &Color) -> Color {
+    Color::new(
+        color.x() / (1.0 + color.x()),
+        color.y() / (1.0 + color.y()),
+        color.z() / (1.0 + color.z()),
+    )
+}
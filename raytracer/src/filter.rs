@@ -0,0 +1,168 @@
+use crate::rtweekend::PI;
+use serde::Deserialize;
+
+/// The shapes of reconstruction filter which can be chosen in the configuration.
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterType {
+    /// Every sample counts fully towards the pixels it is closer than the radius to.
+    #[default]
+    Box,
+    /// Falls off linearly from the center of the pixel to the radius.
+    Tent,
+    /// A Gaussian, shifted down to reach zero at the radius.
+    Gaussian,
+    /// The Mitchell-Netravali cubic with B = C = 1/3.
+    Mitchell,
+    /// A sinc windowed by a wider sinc, which reaches zero at the radius.
+    Lanczos,
+}
+
+impl FilterType {
+    /// Returns the radius the filter is usually used with, in pixels.
+    pub fn default_radius(&self) -> f64 {
+        match self {
+            FilterType::Box => 0.5,
+            FilterType::Tent => 1.0,
+            FilterType::Gaussian => 1.5,
+            FilterType::Mitchell => 2.0,
+            FilterType::Lanczos => 2.0,
+        }
+    }
+}
+
+/// # Reconstruction Filters
+/// Each sample is taken at some point inside a pixel, and the color of a pixel is an average of the
+/// samples around it. Averaging just the samples inside the pixel, all with the same weight, is a
+/// box filter. It is cheap, but edges come out jaggy, as whether a sample counts towards a pixel
+/// changes abruptly at the border of the pixel. Filters which weigh the samples less the further
+/// they are from the center of the pixel, and reach out into the neighbouring pixels, give smoother
+/// edges:
+///
+/// * The tent and Gaussian filters blur the image slightly, which hides the aliasing well.
+/// * The Mitchell and Lanczos filters have negative lobes, which sharpen the image, at the cost of
+///   some ringing around high contrast edges.
+///
+/// Every sample is *splatted* onto all the pixels whose centers are within the `radius` of the
+/// sample, in both directions, weighted by the filter. A pixel is then the weighted sum of the
+/// samples divided by the sum of the weights. The filters are separable, so the weight is the
+/// product of the filter applied to the horizontal and the vertical distance.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Filter {
+    pub filter_type: FilterType,
+    pub radius: f64,
+}
+
+impl Filter {
+    pub fn new(filter_type: FilterType, radius: f64) -> Filter {
+        Filter {
+            filter_type,
+            radius,
+        }
+    }
+
+    /// Returns the weight of a sample at the offset (`dx`, `dy`) from the center of a pixel.
+    pub fn evaluate(&self, dx: f64, dy: f64) -> f64 {
+        self.evaluate_1d(dx) * self.evaluate_1d(dy)
+    }
+
+    fn evaluate_1d(&self, d: f64) -> f64 {
+        let (d, r) = (d.abs(), self.radius);
+        if d >= r {
+            return 0.0;
+        }
+
+        match self.filter_type {
+            FilterType::Box => 1.0,
+            FilterType::Tent => r - d,
+            FilterType::Gaussian => {
+                // A standard deviation of half a pixel.
+                const ALPHA: f64 = 2.0;
+                (-ALPHA * d * d).exp() - (-ALPHA * r * r).exp()
+            }
+            FilterType::Mitchell => mitchell(2.0 * d / r),
+            FilterType::Lanczos => sinc(d) * sinc(d / r),
+        }
+    }
+}
+
+impl Default for Filter {
+    fn default() -> Self {
+        Filter::new(FilterType::Box, FilterType::Box.default_radius())
+    }
+}
+
+/// The Mitchell-Netravali cubic over [0, 2], with B = C = 1/3.
+fn mitchell(x: f64) -> f64 {
+    const B: f64 = 1.0 / 3.0;
+    const C: f64 = 1.0 / 3.0;
+    let (x2, x3) = (x * x, x * x * x);
+    let value = if x < 1.0 {
+        (12.0 - 9.0 * B - 6.0 * C) * x3 + (-18.0 + 12.0 * B + 6.0 * C) * x2 + (6.0 - 2.0 * B)
+    } else {
+        (-B - 6.0 * C) * x3
+            + (6.0 * B + 30.0 * C) * x2
+            + (-12.0 * B - 48.0 * C) * x
+            + (8.0 * B + 24.0 * C)
+    };
+
+    value / 6.0
+}
+
+/// The normalized sinc function, sin(πx) / πx.
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-5 {
+        return 1.0;
+    }
+
+    (PI * x).sin() / (PI * x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILTER_TYPES: [FilterType; 5] = [
+        FilterType::Box,
+        FilterType::Tent,
+        FilterType::Gaussian,
+        FilterType::Mitchell,
+        FilterType::Lanczos,
+    ];
+
+    #[test]
+    fn filters_are_symmetric() {
+        for filter_type in FILTER_TYPES {
+            let filter = Filter::new(filter_type, filter_type.default_radius());
+            for (dx, dy) in [(0.1, 0.0), (0.3, 0.7), (0.45, 1.2), (1.9, 0.2)] {
+                let weight = filter.evaluate(dx, dy);
+                for (x, y) in [(-dx, dy), (dx, -dy), (-dx, -dy), (dy, dx)] {
+                    assert_eq!(
+                        filter.evaluate(x, y),
+                        weight,
+                        "{filter_type:?} at ({x}, {y})"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn filters_are_zero_beyond_the_radius() {
+        for filter_type in FILTER_TYPES {
+            let filter = Filter::new(filter_type, filter_type.default_radius());
+            let r = filter.radius;
+            assert!(
+                filter.evaluate(0.0, 0.0) > 0.0,
+                "{filter_type:?} at the center"
+            );
+            for (dx, dy) in [(r, 0.0), (0.0, r), (r + 0.01, 0.0), (0.0, -r - 0.5), (r, r)] {
+                assert_eq!(
+                    filter.evaluate(dx, dy),
+                    0.0,
+                    "{filter_type:?} at ({dx}, {dy})"
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,298 @@
+use crate::filter::Filter;
+use crate::tiles::Tile;
+use crate::tonemapping::DisplayTransform;
+use crate::Color;
+use image::{Rgb, Rgb32FImage, RgbaImage};
+
+/// # Framebuffer
+/// The framebuffer holds the linear (HDR) radiance gathered for every pixel of the image. Rather than
+/// storing the final color, it keeps the running sum of all the samples taken for a pixel along with
+/// the number of samples, so that more samples can be added later on and a single divide is performed
+/// only when the color is read back.
+///
+/// The samples are weighted by a reconstruction [`Filter`], and may count towards the neighbouring
+/// pixels as well, so the sum is divided by the sum of the weights of the samples rather than their
+/// number. With the default box filter, every sample only counts towards its own pixel with a weight
+/// of one, which gives a plain average.
+///
+/// Along with the sum, the framebuffer keeps the sum of the luminance of the samples taken for each
+/// pixel and of its square, from which the variance of each pixel is estimated. This tells how noisy
+/// the pixel still is, which is what adaptive sampling uses to decide where to take more samples.
+///
+/// Pixels are stored in image order, i.e. row 0 is the top row of the image.
+#[derive(Clone)]
+pub struct FrameBuffer {
+    width: u32,
+    height: u32,
+    pub(crate) sum: Vec<Color>,
+    pub(crate) weights: Vec<f64>,
+    pub(crate) sum_luminance: Vec<f64>,
+    pub(crate) sum_squares: Vec<f64>,
+    pub(crate) samples: Vec<u32>,
+}
+
+/// The samples taken for a single pixel in a render pass.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct PixelSamples {
+    pub sum: Color,
+    pub sum_luminance: f64,
+    pub sum_squares: f64,
+    pub count: u32,
+}
+
+impl PixelSamples {
+    pub fn add(&mut self, color: Color) {
+        let luminance = luminance(&color);
+        self.sum += color;
+        self.sum_luminance += luminance;
+        self.sum_squares += luminance * luminance;
+        self.count += 1;
+    }
+}
+
+/// The samples of a tile, splatted onto the pixels around them with a reconstruction filter. As the
+/// filter reaches beyond the tile, this covers the tile grown by the radius of the filter on every
+/// side, clipped to the image.
+pub(crate) struct Splats {
+    region: Tile,
+    sum: Vec<Color>,
+    weights: Vec<f64>,
+}
+
+impl Splats {
+    pub fn new(tile: &Tile, radius: f64, width: u32, height: u32) -> Splats {
+        let margin = (radius - 0.5).ceil().max(0.0) as u32;
+        let region = Tile {
+            x0: tile.x0.saturating_sub(margin),
+            y0: tile.y0.saturating_sub(margin),
+            x1: (tile.x1 + margin).min(width),
+            y1: (tile.y1 + margin).min(height),
+        };
+        let size = ((region.x1 - region.x0) * (region.y1 - region.y0)) as usize;
+
+        Splats {
+            region,
+            sum: vec![Color::default(); size],
+            weights: vec![0.0; size],
+        }
+    }
+
+    /// Adds a sample taken at the point (`sx`, `sy`) of the image, measured in pixels from the top
+    /// left corner, to all the pixels within the radius of the filter.
+    pub fn add(&mut self, sx: f64, sy: f64, color: Color, filter: &Filter) {
+        let region = &self.region;
+        let range = |s: f64, min: u32, max: u32| {
+            let first = (s - 0.5 - filter.radius).ceil().max(min as f64) as u32;
+            let last = (s - 0.5 + filter.radius).floor().min(max as f64 - 1.0) as i64;
+            first..(last + 1).max(first as i64) as u32
+        };
+
+        for py in range(sy, region.y0, region.y1) {
+            for px in range(sx, region.x0, region.x1) {
+                let weight = filter.evaluate(px as f64 + 0.5 - sx, py as f64 + 0.5 - sy);
+                if weight != 0.0 {
+                    let i =
+                        ((py - region.y0) * (region.x1 - region.x0) + (px - region.x0)) as usize;
+                    self.sum[i] += weight * color;
+                    self.weights[i] += weight;
+                }
+            }
+        }
+    }
+}
+
+impl FrameBuffer {
+    pub fn new(width: u32, height: u32) -> FrameBuffer {
+        let size = (width * height) as usize;
+        FrameBuffer {
+            width,
+            height,
+            sum: vec![Color::default(); size],
+            weights: vec![0.0; size],
+            sum_luminance: vec![0.0; size],
+            sum_squares: vec![0.0; size],
+            samples: vec![0; size],
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Returns the (filtered) average linear radiance of the pixel at (x, y), or black if no samples
+    /// have been taken around it yet.
+    pub fn pixel(&self, x: u32, y: u32) -> Color {
+        let i = self.index(x, y);
+        scale(&self.sum[i], self.weights[i])
+    }
+
+    /// Returns the number of samples accumulated for the pixel at (x, y).
+    pub fn sample_count(&self, x: u32, y: u32) -> u32 {
+        self.samples[self.index(x, y)]
+    }
+
+    /// Returns the variance of the luminance of the samples taken for the pixel at (x, y), or zero
+    /// if less than two samples have been taken for it.
+    pub fn variance(&self, x: u32, y: u32) -> f64 {
+        let i = self.index(x, y);
+        match self.samples[i] {
+            0 | 1 => 0.0,
+            n => {
+                let n = n as f64;
+                let mean = self.sum_luminance[i] / n;
+                // Unbiased estimate, clamped as rounding may push it slightly below zero.
+                ((self.sum_squares[i] / n - mean * mean) * n / (n - 1.0)).max(0.0)
+            }
+        }
+    }
+
+    /// Returns the average linear radiance of all the pixels, in image order.
+    pub fn pixels(&self) -> Vec<Color> {
+        (0..self.height)
+            .flat_map(|y| (0..self.width).map(move |x| (x, y)))
+            .map(|(x, y)| self.pixel(x, y))
+            .collect()
+    }
+
+    /// Adds the samples taken for each pixel of the tile, given row by row, with a box filter.
+    pub(crate) fn add_tile_samples(&mut self, tile: &Tile, pixels: &[PixelSamples]) {
+        for ((x, y), pixel) in tile.pixels().zip(pixels) {
+            let i = self.index(x, y);
+            self.sum[i] += pixel.sum;
+            self.weights[i] += pixel.count as f64;
+        }
+        self.add_statistics(tile, pixels);
+    }
+
+    /// Adds the statistics of the samples taken for each pixel of the tile, given row by row, whose
+    /// radiance was splatted with a reconstruction filter, and is added with [`Self::add_splats`].
+    pub(crate) fn add_tile_statistics(&mut self, tile: &Tile, pixels: &[PixelSamples]) {
+        self.add_statistics(tile, pixels);
+    }
+
+    /// Adds the radiance of the samples of a tile splatted with a reconstruction filter. The
+    /// splats of neighbouring tiles overlap, so they must be added in the same order every time
+    /// for the sums, which are rounded differently in a different order, to be the same.
+    pub(crate) fn add_splats(&mut self, splats: &Splats) {
+        let region = &splats.region;
+        for ((x, y), (color, weight)) in region
+            .pixels()
+            .zip(splats.sum.iter().zip(splats.weights.iter()))
+        {
+            let i = self.index(x, y);
+            self.sum[i] += *color;
+            self.weights[i] += weight;
+        }
+    }
+
+    fn add_statistics(&mut self, tile: &Tile, pixels: &[PixelSamples]) {
+        for ((x, y), pixel) in tile.pixels().zip(pixels) {
+            let i = self.index(x, y);
+            self.sum_luminance[i] += pixel.sum_luminance;
+            self.sum_squares[i] += pixel.sum_squares;
+            self.samples[i] += pixel.count;
+        }
+    }
+
+    /// Returns the average linear radiance as a 32-bit float RGB image. Unlike the 8-bit image, the
+    /// values are neither gamma corrected nor clamped, so the full dynamic range is preserved.
+    pub fn to_rgb32f_image(&self) -> Rgb32FImage {
+        Rgb32FImage::from_fn(self.width, self.height, |x, y| {
+            let color = self.pixel(x, y);
+            Rgb([color.x() as f32, color.y() as f32, color.z() as f32])
+        })
+    }
+
+    /// Converts the accumulated radiance to an 8-bit RGBA image for display, using the given display
+    /// transform.
+    pub fn to_rgba_image(&self, display: &DisplayTransform) -> RgbaImage {
+        let mut image = RgbaImage::new(self.width, self.height);
+        image
+            .chunks_mut(4)
+            .zip(self.sum.iter().zip(self.weights.iter()))
+            .for_each(|(pixel, (color, &weight))| write_color(pixel, color, weight, display));
+
+        image
+    }
+
+    #[inline]
+    fn index(&self, x: u32, y: u32) -> usize {
+        (y * self.width + x) as usize
+    }
+}
+
+/// Returns the relative luminance of a linear color, using the Rec. 709 primaries.
+#[inline]
+pub(crate) fn luminance(color: &Color) -> f64 {
+    0.2126 * color.x() + 0.7152 * color.y() + 0.0722 * color.z()
+}
+
+/// Divides the weighted sum of the samples by the sum of their weights. Pixels without any samples
+/// around them, or whose weights cancel out due to the negative lobes of a filter, are black.
+#[inline]
+fn scale(sum: &Color, weight: f64) -> Color {
+    if weight > 1e-8 {
+        *sum / weight
+    } else {
+        Color::default()
+    }
+}
+
+/// To handle the multi-sampled color computation - rather than adding in a fractional contribution
+/// each time we accumulate more light to the color, just add the full color each iteration, and
+/// then perform a single divide at the end (by the sum of the weights of the samples) when writing
+/// out the color.
+#[inline]
+fn write_color(pixel: &mut [u8], pixel_color: &Color, weight: f64, display: &DisplayTransform) {
+    // Divide the color by the weight of the samples, and tone map and encode it for display.
+    let [r, g, b] = display.apply(&scale(pixel_color, weight));
+
+    pixel[0] = r;
+    pixel[1] = g;
+    pixel[2] = b;
+    pixel[3] = 255;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn box_filter_gives_the_average_of_the_samples() {
+        let tile = Tile {
+            x0: 0,
+            y0: 0,
+            x1: 3,
+            y1: 2,
+        };
+        let filter = Filter::default();
+        let mut averaged = FrameBuffer::new(3, 2);
+        let mut filtered = FrameBuffer::new(3, 2);
+        let mut splats = Splats::new(&tile, filter.radius, 3, 2);
+
+        let pixels: Vec<PixelSamples> = tile
+            .pixels()
+            .map(|(x, y)| {
+                let mut pixel = PixelSamples::default();
+                for (i, (du, dv)) in [(0.2, 0.3), (0.7, 0.1), (0.4, 0.9)].iter().enumerate() {
+                    let color = Color::new(x as f64, y as f64, i as f64 * 0.5);
+                    pixel.add(color);
+                    splats.add(x as f64 + du, y as f64 + dv, color, &filter);
+                }
+                pixel
+            })
+            .collect();
+        averaged.add_tile_samples(&tile, &pixels);
+        filtered.add_tile_statistics(&tile, &pixels);
+        filtered.add_splats(&splats);
+
+        for (x, y) in tile.pixels() {
+            let difference = averaged.pixel(x, y) - filtered.pixel(x, y);
+            assert!(difference.length() < 1e-12, "pixel ({x}, {y})");
+        }
+    }
+}
@@ -0,0 +1,65 @@
+use crate::integrators::Integrator;
+use crate::objects::{HitRecord, Hittable};
+use crate::{utils, Color, Ray, Vec3};
+
+/// The fraction of the diagonal of the scene the occluders are looked for within, if no distance is
+/// given.
+const DEFAULT_DISTANCE_FRACTION: f64 = 0.1;
+
+/// # Ambient Occlusion
+/// Shades each point the camera sees by how much of the hemisphere above it is open: a ray is sent
+/// in a direction picked around the normal, and the point is white if the ray gets further than
+/// `distance` without hitting anything, and black otherwise. Averaged over the samples, creases and
+/// corners come out darker than open surfaces, which shows the shape of the scene without needing
+/// any lights or materials.
+///
+/// The directions are picked by the cosine of their angle to the normal, so the ones which face the
+/// surface head on count the most. Without a `distance`, a tenth of the diagonal of the bounding
+/// box of the scene is used.
+pub struct AmbientOcclusion {
+    distance: Option<f64>,
+}
+
+impl AmbientOcclusion {
+    pub fn new(distance: Option<f64>) -> AmbientOcclusion {
+        AmbientOcclusion { distance }
+    }
+}
+
+impl Integrator for AmbientOcclusion {
+    fn ray_color(
+        &self,
+        r: &Ray,
+        world: &dyn Hittable,
+        _lights: Option<&dyn Hittable>,
+        _background: &Color,
+        first_hit: &mut Option<HitRecord>,
+    ) -> Color {
+        let open = Color::new(1.0, 1.0, 1.0);
+        *first_hit = world.hit(r, 0.001, utils::INFINITY);
+        let hit_rec = match first_hit {
+            Some(hit_rec) => hit_rec,
+            None => return open,
+        };
+
+        let distance = self.distance.unwrap_or_else(|| {
+            world
+                .bounding_box(0.0, 1.0)
+                .map_or(utils::INFINITY, |bbox| {
+                    DEFAULT_DISTANCE_FRACTION * (bbox.max() - bbox.min()).length()
+                })
+        });
+        let mut direction = hit_rec.normal + Vec3::random_unit_vector();
+        if direction.near_zero() {
+            direction = hit_rec.normal;
+        }
+
+        // Hits are measured in lengths of the direction, so use a unit direction to measure the
+        // distance.
+        let occlusion_ray = Ray::new_with_time(&hit_rec.p, &direction.unit_vector(), r.time());
+        match world.hit(&occlusion_ray, 0.001, distance) {
+            Some(_) => Color::new(0.0, 0.0, 0.0),
+            None => open,
+        }
+    }
+}
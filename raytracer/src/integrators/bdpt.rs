@@ -0,0 +1,512 @@
+use crate::integrators::{sample_emission, Integrator};
+use crate::objects::{HitRecord, Hittable};
+use crate::samplers::sample_1d;
+use crate::utils::{INFINITY, PI};
+use crate::{Color, Point, Ray, Vec3};
+
+/// # Bidirectional Path Tracing
+/// A path tracer starts the paths at the camera and has to find the lights, through the BSDFs or by
+/// sampling them. Some light is very hard to find that way: the light focused by a glass sphere
+/// (a *caustic*) only reaches a diffuse surface along a few exact directions, and the light which
+/// lights up smoke is found by the paths inside it only by chance. Such light is much easier to
+/// find by starting at the lights, and following the light to where it lands.
+///
+/// For every sample, a *camera subpath* is followed from the camera and a *light subpath* from a
+/// point picked on the lights, each bouncing through the scene like in the path tracer. Every
+/// vertex of the camera subpath is then connected to every vertex of the light subpath with a
+/// shadow ray, which gives many complete paths from the light to the camera:
+///
+/// * With no light vertices, the camera subpath found a light by itself.
+/// * With one light vertex, a point is picked on the lights from the camera vertex, like the next
+///   event estimation of the path tracer.
+/// * With more, the light subpath found a surface which is connected to the camera subpath.
+///
+/// A path of a given length can be made with any of these strategies, so each of them is weighted
+/// with multiple importance sampling. The power heuristic needs the density each of the strategies
+/// would make the path with, which comes from the densities of the vertices of the path per unit
+/// area, walking the path in both directions (Veach 1997, "Robust Monte Carlo Methods for Light
+/// Transport Simulation"). Connections through specular vertices, like a mirror or glass, are
+/// impossible, so the paths through them can only come from the strategies which sample them.
+///
+/// The light subpaths are never connected to the camera itself, as that would add light to other
+/// pixels than the one being sampled.
+pub struct Bdpt {
+    max_depth: u32,
+    russian_roulette_depth: u32,
+}
+
+impl Bdpt {
+    /// Returns an integrator following the subpaths for at most `max_depth` bounces, ending them at
+    /// random after `russian_roulette_depth` bounces.
+    pub fn new(max_depth: u32, russian_roulette_depth: u32) -> Bdpt {
+        Bdpt {
+            max_depth,
+            russian_roulette_depth,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum VertexKind {
+    Camera,
+    Light,
+    Surface,
+}
+
+/// A vertex of a subpath.
+#[derive(Clone)]
+struct Vertex {
+    kind: VertexKind,
+    p: Point,
+    // The direction of the ray which arrived at the vertex.
+    incoming: Vec3,
+    rec: Option<HitRecord>,
+    // The throughput of the subpath up to the vertex.
+    beta: Color,
+    // The density of the vertex per unit area, when made from the previous vertex of its subpath
+    // (forwards) or from the next one (in reverse).
+    pdf_fwd: f64,
+    pdf_rev: f64,
+    // Whether the vertex scattered the subpath off a specular lobe.
+    delta: bool,
+}
+
+impl Vertex {
+    fn camera(r: &Ray) -> Vertex {
+        Vertex {
+            kind: VertexKind::Camera,
+            p: r.origin(),
+            incoming: Vec3::default(),
+            rec: None,
+            beta: Color::new(1.0, 1.0, 1.0),
+            pdf_fwd: 1.0,
+            pdf_rev: 0.0,
+            delta: false,
+        }
+    }
+
+    fn light(rec: HitRecord, beta: Color) -> Vertex {
+        Vertex {
+            kind: VertexKind::Light,
+            p: rec.p,
+            incoming: Vec3::default(),
+            rec: Some(rec),
+            beta,
+            pdf_fwd: 0.0,
+            pdf_rev: 0.0,
+            delta: false,
+        }
+    }
+
+    fn surface(rec: HitRecord, incoming: Vec3, beta: Color) -> Vertex {
+        Vertex {
+            kind: VertexKind::Surface,
+            p: rec.p,
+            incoming,
+            rec: Some(rec),
+            beta,
+            pdf_fwd: 0.0,
+            pdf_rev: 0.0,
+            delta: false,
+        }
+    }
+
+    /// Returns the cosine of the angle between the normal and the direction `w`, which is one for
+    /// the camera and inside volumes, where there is no surface to be tilted.
+    fn cos(&self, w: &Vec3) -> f64 {
+        match &self.rec {
+            Some(rec) if !rec.mat.is_volumetric() => rec.normal.dot(&w.unit_vector()).abs(),
+            _ => 1.0,
+        }
+    }
+
+    /// Converts the density `pdf` of picking the direction towards `next` per unit solid angle to
+    /// the density of picking the vertex `next` per unit area.
+    fn convert_density(&self, pdf: f64, next: &Vertex) -> f64 {
+        let w = next.p - self.p;
+        let distance_squared = w.length_squared();
+        if distance_squared == 0.0 {
+            return 0.0;
+        }
+
+        pdf * next.cos(&w) / distance_squared
+    }
+
+    /// Returns the light emitted by the vertex, towards any direction.
+    fn emitted(&self) -> Color {
+        match &self.rec {
+            Some(rec) => rec.mat.emitted(rec.u, rec.v, &rec.p),
+            None => Color::new(0.0, 0.0, 0.0),
+        }
+    }
+
+    /// Returns the BSDF of the vertex times the cosine, for the light arriving from `next` and
+    /// leaving along the reverse of the incoming ray.
+    fn eval(&self, next: &Vertex, time: f64) -> Color {
+        match (&self.rec, self.kind) {
+            (Some(rec), VertexKind::Surface) => {
+                let r_in = Ray::new_with_time(&(self.p - self.incoming), &self.incoming, time);
+                rec.mat.eval(&r_in, rec, &(next.p - self.p))
+            }
+            _ => Color::new(0.0, 0.0, 0.0),
+        }
+    }
+
+    /// Returns the density of picking `next` per unit area, by scattering the ray arriving from
+    /// `prev` at the vertex, or by emitting light from it, if it is on a light.
+    fn pdf(&self, prev: Option<&Vertex>, next: &Vertex, time: f64) -> f64 {
+        match (self.kind, &self.rec, prev) {
+            (VertexKind::Light, _, _) => self.pdf_light(next),
+            (VertexKind::Surface, Some(rec), Some(prev)) => {
+                let r_in = Ray::new_with_time(&prev.p, &(self.p - prev.p), time);
+                let pdf = rec.mat.pdf(&r_in, rec, &(next.p - self.p));
+                self.convert_density(pdf, next)
+            }
+            _ => 0.0,
+        }
+    }
+
+    /// Returns the density of picking `next` per unit area, by emitting light from the vertex.
+    fn pdf_light(&self, next: &Vertex) -> f64 {
+        let pdf = self.cos(&(next.p - self.p)) / (2.0 * PI);
+        self.convert_density(pdf, next)
+    }
+
+    /// Returns the density of picking the vertex per unit area as the start of a path of light,
+    /// which is taken to be the density of picking it on the lights from `next`.
+    fn pdf_light_origin(&self, next: &Vertex, lights: &dyn Hittable) -> f64 {
+        let w = self.p - next.p;
+        let distance_squared = w.length_squared();
+        if distance_squared == 0.0 {
+            return 0.0;
+        }
+
+        lights.pdf_value(&next.p, &w) * self.cos(&w) / distance_squared
+    }
+}
+
+impl Integrator for Bdpt {
+    fn ray_color(
+        &self,
+        r: &Ray,
+        world: &dyn Hittable,
+        lights: Option<&dyn Hittable>,
+        background: &Color,
+        first_hit: &mut Option<HitRecord>,
+    ) -> Color {
+        let time = r.time();
+        let mut radiance = Color::new(0.0, 0.0, 0.0);
+
+        let mut camera_path = vec![Vertex::camera(r)];
+        let ray = Ray::new_with_time(&r.origin(), &r.direction(), time);
+        let escaped = self.random_walk(
+            world,
+            ray,
+            Color::new(1.0, 1.0, 1.0),
+            1.0,
+            self.max_depth,
+            &mut camera_path,
+        );
+        // Only the camera subpaths can find the background, so it counts fully.
+        if let Some(beta) = escaped {
+            radiance += beta * *background;
+        }
+
+        let mut light_path = vec![];
+        if let Some(lights) = lights {
+            self.light_subpath(world, lights, time, &mut light_path);
+        }
+
+        // A path of `s + t` vertices is made of `s + t - 1` rays, like the ones the path tracer
+        // follows for `max_depth`.
+        for t in 2..=camera_path.len() {
+            for s in 0..=light_path.len().max(1) {
+                if s + t - 1 > self.max_depth as usize {
+                    break;
+                }
+
+                radiance += self.connect(world, lights, &light_path, &camera_path, s, t, time);
+            }
+        }
+        // The vertex after the camera is what the camera ray hit.
+        *first_hit = camera_path.into_iter().nth(1).and_then(|vertex| vertex.rec);
+
+        radiance
+    }
+}
+
+impl Bdpt {
+    /// Follows the `ray` through the world, adding a vertex to the `path` at every bounce, until it
+    /// has `max_vertices` more vertices, the light is absorbed, or the path is ended by Russian
+    /// roulette. `beta` is the throughput of the path so far, and `pdf` the density the direction
+    /// of the ray was picked with. Returns the throughput if the ray escapes the world.
+    ///
+    /// The chance of surviving Russian roulette is the throughput relative to the one the walk
+    /// started with, as the light subpaths start with the light emitted, which is far above one.
+    fn random_walk(
+        &self,
+        world: &dyn Hittable,
+        mut ray: Ray,
+        mut beta: Color,
+        mut pdf: f64,
+        max_vertices: u32,
+        path: &mut Vec<Vertex>,
+    ) -> Option<Color> {
+        let start = beta.x().max(beta.y()).max(beta.z());
+        for depth in 0..max_vertices {
+            let rec = world.hit(&ray, 0.001, INFINITY)?;
+            let mut vertex = Vertex::surface(rec, ray.direction(), beta);
+            let prev = path.last().unwrap();
+            vertex.pdf_fwd = prev.convert_density(pdf, &vertex);
+            path.push(vertex);
+
+            let n = path.len();
+            let rec = path[n - 1].rec.as_ref().unwrap();
+            let sample = match rec.mat.sample(&ray, rec) {
+                Some(sample) => sample,
+                None => break,
+            };
+            let pdf_rev = if sample.specular {
+                path[n - 1].delta = true;
+                pdf = 0.0;
+                0.0
+            } else {
+                pdf = sample.pdf;
+                // The reverse walk arrives along the scattered ray, and leaves along the incoming
+                // one.
+                let direction = sample.scattered.direction();
+                let r_rev = Ray::new_with_time(&(rec.p + direction), &-direction, ray.time());
+                rec.mat.pdf(&r_rev, rec, &-ray.direction())
+            };
+            path[n - 2].pdf_rev = path[n - 1].convert_density(pdf_rev, &path[n - 2]);
+
+            beta = beta * sample.weight;
+            ray = sample.scattered;
+
+            if depth + 1 >= self.russian_roulette_depth {
+                let survival = (beta.x().max(beta.y()).max(beta.z()) / start).min(1.0);
+                if survival <= 0.0 || sample_1d() >= survival {
+                    break;
+                }
+                beta /= survival;
+            }
+        }
+
+        None
+    }
+
+    /// Picks a point on the lights and a direction to emit the light in, and follows it through the
+    /// world.
+    fn light_subpath(
+        &self,
+        world: &dyn Hittable,
+        lights: &dyn Hittable,
+        time: f64,
+        path: &mut Vec<Vertex>,
+    ) {
+        let emission = match sample_emission(lights) {
+            Some(emission) => emission,
+            None => return,
+        };
+
+        let origin = emission.rec.p;
+        path.push(Vertex::light(
+            emission.rec,
+            emission.emitted / emission.pdf_pos,
+        ));
+        let ray = Ray::new_with_time(&origin, &emission.direction, time);
+        // The shortest connection to the camera subpath takes two more rays.
+        let max_vertices = self.max_depth.saturating_sub(2);
+        self.random_walk(
+            world,
+            ray,
+            emission.weight,
+            emission.pdf_dir,
+            max_vertices,
+            path,
+        );
+
+        if path.len() > 1 {
+            path[0].pdf_fwd = path[0].pdf_light_origin(&path[1], lights);
+        }
+    }
+
+    /// Returns the light carried by the path made of the first `s` vertices of the light subpath
+    /// and the first `t` vertices of the camera subpath, weighted against the other strategies
+    /// which could have made it.
+    #[allow(clippy::too_many_arguments)]
+    fn connect(
+        &self,
+        world: &dyn Hittable,
+        lights: Option<&dyn Hittable>,
+        light_path: &[Vertex],
+        camera_path: &[Vertex],
+        s: usize,
+        t: usize,
+        time: f64,
+    ) -> Color {
+        let black = Color::new(0.0, 0.0, 0.0);
+        let pt = &camera_path[t - 1];
+
+        if s == 0 {
+            // The camera subpath found a light by itself.
+            let emitted = pt.emitted();
+            if emitted.length_squared() == 0.0 {
+                return black;
+            }
+            let weight = self.mis_weight(lights, light_path, camera_path, None, s, t, time);
+            return weight * pt.beta * emitted;
+        }
+
+        if pt.delta {
+            return black;
+        }
+
+        if s == 1 {
+            // Pick a point on the lights from the camera vertex. Whatever is seen first in that
+            // direction is the end of the path, so it only counts if it emits light.
+            let lights = match lights {
+                Some(lights) => lights,
+                None => return black,
+            };
+            let direction = lights.random(&pt.p);
+            let pdf = lights.pdf_value(&pt.p, &direction);
+            if pdf <= 0.0 {
+                return black;
+            }
+            let shadow_ray = Ray::new_with_time(&pt.p, &direction, time);
+            let rec = match world.hit(&shadow_ray, 0.001, INFINITY) {
+                Some(rec) => rec,
+                None => return black,
+            };
+            let light = Vertex::light(rec, black);
+            let emitted = light.emitted();
+            let f = pt.eval(&light, time);
+            if emitted.length_squared() == 0.0 || f.length_squared() == 0.0 {
+                return black;
+            }
+
+            let weight = self.mis_weight(
+                Some(lights),
+                light_path,
+                camera_path,
+                Some(&light),
+                s,
+                t,
+                time,
+            );
+            return weight * pt.beta * f * emitted / pdf;
+        }
+
+        let qs = &light_path[s - 1];
+        if qs.delta {
+            return black;
+        }
+
+        let w = qs.p - pt.p;
+        let distance_squared = w.length_squared();
+        let f = qs.eval(pt, time) * pt.eval(qs, time);
+        if distance_squared == 0.0 || f.length_squared() == 0.0 {
+            return black;
+        }
+
+        // The shadow ray has a unit direction, so that the hits are measured in distances, and
+        // stops just short of the light vertex.
+        let distance = distance_squared.sqrt();
+        let shadow_ray = Ray::new_with_time(&pt.p, &(w / distance), time);
+        if world.hit(&shadow_ray, 0.001, distance - 0.001).is_some() {
+            return black;
+        }
+
+        let weight = self.mis_weight(lights, light_path, camera_path, None, s, t, time);
+        weight * qs.beta * pt.beta * f / distance_squared
+    }
+
+    /// Returns the weight of the strategy using `s` light vertices and `t` camera vertices with the
+    /// power heuristic, compared to all the other strategies which could have made the same path.
+    /// `sampled` is the light vertex picked from the camera vertex, if `s` is one.
+    #[allow(clippy::too_many_arguments)]
+    fn mis_weight(
+        &self,
+        lights: Option<&dyn Hittable>,
+        light_path: &[Vertex],
+        camera_path: &[Vertex],
+        sampled: Option<&Vertex>,
+        s: usize,
+        t: usize,
+        time: f64,
+    ) -> f64 {
+        // The densities (forwards, in reverse) of the vertices of the path and whether they are
+        // specular, with the ones around the connection updated for this strategy.
+        let vertices = |path: &[Vertex], n: usize| -> Vec<(f64, f64, bool)> {
+            path[..n]
+                .iter()
+                .map(|v| (v.pdf_fwd, v.pdf_rev, v.delta))
+                .collect()
+        };
+        let mut light = vertices(light_path, s.min(light_path.len()));
+        let mut camera = vertices(camera_path, t);
+
+        let pt = &camera_path[t - 1];
+        let pt_minus = &camera_path[t - 2];
+        if s == 0 {
+            let pdf_origin = lights.map_or(0.0, |lights| pt.pdf_light_origin(pt_minus, lights));
+            // No other strategy can find a light which can't be sampled.
+            if pdf_origin == 0.0 {
+                return 1.0;
+            }
+            camera[t - 1].1 = pdf_origin;
+            camera[t - 2].1 = pt.pdf_light(pt_minus);
+        } else {
+            let qs = match sampled {
+                Some(sampled) => sampled,
+                None => &light_path[s - 1],
+            };
+            let qs_minus = if s > 1 {
+                Some(&light_path[s - 2])
+            } else {
+                None
+            };
+            if let Some(sampled) = sampled {
+                let pdf_origin = lights.map_or(0.0, |lights| sampled.pdf_light_origin(pt, lights));
+                light = vec![(pdf_origin, 0.0, false)];
+            }
+
+            camera[t - 1].1 = qs.pdf(qs_minus, pt, time);
+            camera[t - 2].1 = pt.pdf(Some(qs), pt_minus, time);
+            light[s - 1].1 = pt.pdf(Some(pt_minus), qs, time);
+            light[s - 1].2 = false;
+            if let Some(qs_minus) = qs_minus {
+                light[s - 2].1 = qs.pdf(Some(pt), qs_minus, time);
+            }
+        }
+        camera[t - 1].2 = false;
+
+        // Zero densities come from specular vertices, which are skipped by the checks below, so
+        // they just leave the ratios alone.
+        let remap = |pdf: f64| if pdf != 0.0 { pdf } else { 1.0 };
+        let mut sum = 0.0;
+
+        // Strategies with fewer camera vertices, down to two, as the light subpaths are never
+        // connected to the camera itself.
+        let mut ratio = 1.0;
+        for i in (2..t).rev() {
+            ratio *= remap(camera[i].1) / remap(camera[i].0);
+            if !camera[i].2 && !camera[i - 1].2 {
+                sum += ratio * ratio;
+            }
+        }
+
+        // Strategies with fewer light vertices, down to none.
+        let mut ratio = 1.0;
+        for i in (0..s).rev() {
+            ratio *= remap(light[i].1) / remap(light[i].0);
+            let delta_prev = i > 0 && light[i - 1].2;
+            if !light[i].2 && !delta_prev {
+                sum += ratio * ratio;
+            }
+        }
+
+        1.0 / (1.0 + sum)
+    }
+}
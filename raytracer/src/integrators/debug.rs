@@ -0,0 +1,81 @@
+use crate::integrators::Integrator;
+use crate::objects::{take_nodes_visited, HitRecord, Hittable};
+use crate::{utils, Color, Ray};
+
+/// The number of nodes visited by a ray which shows up as red in the BVH heatmap.
+const HEATMAP_MAX_NODES: f64 = 64.0;
+
+/// Shows the normals of the surfaces the camera rays hit, with each component mapped from [-1,1]
+/// to [0,1] and shown as red, green and blue. The normals face the camera, so they show which side
+/// of the surfaces is seen too. Rays which don't hit anything are black.
+pub struct NormalsIntegrator;
+
+impl Integrator for NormalsIntegrator {
+    fn ray_color(
+        &self,
+        r: &Ray,
+        world: &dyn Hittable,
+        _lights: Option<&dyn Hittable>,
+        _background: &Color,
+        first_hit: &mut Option<HitRecord>,
+    ) -> Color {
+        *first_hit = world.hit(r, 0.001, utils::INFINITY);
+        first_hit
+            .as_ref()
+            .map_or(Color::new(0.0, 0.0, 0.0), |hit_rec| {
+                0.5 * (hit_rec.normal.unit_vector() + Color::new(1.0, 1.0, 1.0))
+            })
+    }
+}
+
+/// Shows the texture coordinates of the surfaces the camera rays hit, with *u* as red and *v* as
+/// green. Rays which don't hit anything are black.
+pub struct UvIntegrator;
+
+impl Integrator for UvIntegrator {
+    fn ray_color(
+        &self,
+        r: &Ray,
+        world: &dyn Hittable,
+        _lights: Option<&dyn Hittable>,
+        _background: &Color,
+        first_hit: &mut Option<HitRecord>,
+    ) -> Color {
+        *first_hit = world.hit(r, 0.001, utils::INFINITY);
+        first_hit
+            .as_ref()
+            .map_or(Color::new(0.0, 0.0, 0.0), |hit_rec| {
+                Color::new(hit_rec.u, hit_rec.v, 0.0)
+            })
+    }
+}
+
+/// Shows how many nodes of the BVH the camera rays visit, going from blue for none, through green,
+/// to red for `HEATMAP_MAX_NODES` or more. The hot spots are the parts of the scene which are slow
+/// to trace, for e.g. where many bounding boxes overlap.
+///
+/// The nodes are only counted with the `bvh-heatmap` feature enabled, which the renderer checks for.
+pub struct BvhHeatmap;
+
+impl Integrator for BvhHeatmap {
+    fn ray_color(
+        &self,
+        r: &Ray,
+        world: &dyn Hittable,
+        _lights: Option<&dyn Hittable>,
+        _background: &Color,
+        first_hit: &mut Option<HitRecord>,
+    ) -> Color {
+        take_nodes_visited();
+        *first_hit = world.hit(r, 0.001, utils::INFINITY);
+        let heat = (take_nodes_visited() as f64 / HEATMAP_MAX_NODES).min(1.0);
+
+        if heat < 0.5 {
+            let t = 2.0 * heat;
+            Color::new(0.0, t, 1.0 - t)
+        } else {
+            let t = 2.0 * heat - 1.0;
+            Color::new(t, 1.0 - t, 0.0)
+        }
+    }
+}
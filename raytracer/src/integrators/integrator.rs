@@ -0,0 +1,173 @@
+use crate::configuration::ImageSettings;
+use crate::integrators::{
+    AmbientOcclusion, Bdpt, BvhHeatmap, NormalsIntegrator, PathTracer, PhotonMap, Spectral,
+    UvIntegrator,
+};
+use crate::objects::{HitRecord, Hittable};
+use crate::samplers::{sample_1d, sample_2d};
+use crate::utils::PI;
+use crate::vec3::Onb;
+use crate::{Color, Ray, Vec3};
+use serde::Deserialize;
+use std::sync::Arc;
+
+/// # Integrators
+/// An integrator works out the color seen along a ray from the camera, which for the final image
+/// means adding up the light arriving along all the paths which end at the camera. Other
+/// integrators show something about the scene instead, like its normals or how expensive it is to
+/// trace, which helps with setting up and debugging scenes.
+///
+/// The `lights` are the objects of the `world` which emit light and can be sampled directly, if
+/// there are any. Rays which don't hit anything see the `background` color. What the ray `r` hits
+/// is put into `first_hit`, so that the arbitrary output variables can be taken from the camera ray
+/// without tracing it again.
+pub trait Integrator: Send + Sync {
+    fn ray_color(
+        &self,
+        r: &Ray,
+        world: &dyn Hittable,
+        lights: Option<&dyn Hittable>,
+        background: &Color,
+        first_hit: &mut Option<HitRecord>,
+    ) -> Color;
+}
+
+/// The integrators which can be chosen in the configuration.
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum IntegratorType {
+    /// Follows the paths of the light, sampling the lights directly at every bounce which isn't
+    /// specular.
+    #[default]
+    PathTracer,
+    /// Follows the paths of the light, finding the lights only by chance.
+    Naive,
+    /// Connects paths followed from the camera with paths followed from the lights.
+    Bdpt,
+    /// The path tracer, with the caustics gathered from photons sent out from the lights.
+    PhotonMapping,
+    /// How much of the hemisphere above each point is open, rather than blocked by nearby objects.
+    AmbientOcclusion,
+    /// The normals of the surfaces the camera rays hit, mapped to colors.
+    Normals,
+    /// The texture coordinates of the surfaces the camera rays hit, as red and green.
+    Uv,
+    /// The number of BVH nodes visited by the camera rays, from blue for few to red for many. This
+    /// needs the `bvh-heatmap` feature.
+    BvhHeatmap,
+}
+
+impl IntegratorType {
+    /// Returns the integrator for rendering the `world` with the given settings. Integrators which
+    /// trace the scene up front, like photon mapping, do so here.
+    pub fn create(
+        &self,
+        settings: &ImageSettings,
+        world: &dyn Hittable,
+        lights: Option<&dyn Hittable>,
+    ) -> Arc<dyn Integrator> {
+        let integrator: Arc<dyn Integrator> = match self {
+            IntegratorType::PathTracer => Arc::new(PathTracer::new(
+                settings.max_depth,
+                settings.russian_roulette_depth,
+                true,
+            )),
+            IntegratorType::Naive => Arc::new(PathTracer::new(
+                settings.max_depth,
+                settings.russian_roulette_depth,
+                false,
+            )),
+            IntegratorType::PhotonMapping => {
+                let path_tracer =
+                    PathTracer::new(settings.max_depth, settings.russian_roulette_depth, true);
+                match lights {
+                    Some(lights) => Arc::new(path_tracer.with_caustics(PhotonMap::caustics(
+                        world,
+                        lights,
+                        settings.caustic_photons,
+                        settings.caustic_photon_lookup,
+                        settings.caustic_photon_radius,
+                        settings.max_depth,
+                    ))),
+                    None => Arc::new(path_tracer),
+                }
+            }
+            IntegratorType::Bdpt => Arc::new(Bdpt::new(
+                settings.max_depth,
+                settings.russian_roulette_depth,
+            )),
+            IntegratorType::AmbientOcclusion => {
+                Arc::new(AmbientOcclusion::new(settings.ambient_occlusion_distance))
+            }
+            IntegratorType::Normals => Arc::new(NormalsIntegrator),
+            IntegratorType::Uv => Arc::new(UvIntegrator),
+            IntegratorType::BvhHeatmap => Arc::new(BvhHeatmap),
+        };
+
+        // The other integrators show something about the scene rather than the light, which has
+        // no spectrum.
+        let traces_light = matches!(
+            self,
+            IntegratorType::PathTracer
+                | IntegratorType::Naive
+                | IntegratorType::Bdpt
+                | IntegratorType::PhotonMapping
+        );
+        if settings.spectral && traces_light {
+            Arc::new(Spectral::new(integrator))
+        } else {
+            integrator
+        }
+    }
+}
+
+/// A point picked on the lights, and the direction the light leaves it in.
+pub(crate) struct Emission {
+    pub rec: HitRecord,
+    /// The light emitted from the point.
+    pub emitted: Color,
+    pub direction: Vec3,
+    /// The density of picking the point per unit area, and the direction per unit solid angle.
+    pub pdf_pos: f64,
+    pub pdf_dir: f64,
+    /// The light carried along the direction, divided by the densities it was picked with.
+    pub weight: Color,
+}
+
+/// Picks a point on the `lights` and a direction for the light to leave it in, for following the
+/// light from where it is emitted. The lights emit from both of their sides, so one of them is
+/// picked at random, and the direction by the cosine of its angle to the normal on that side.
+pub(crate) fn sample_emission(lights: &dyn Hittable) -> Option<Emission> {
+    let (rec, pdf_pos) = lights.sample_surface().filter(|(_, pdf)| *pdf > 0.0)?;
+    let emitted = rec.mat.emitted(rec.u, rec.v, &rec.p);
+    if emitted.length_squared() == 0.0 {
+        return None;
+    }
+
+    let (u, v) = sample_2d();
+    let (cos_theta, phi) = ((1.0 - u).sqrt(), 2.0 * PI * v);
+    let sin_theta = u.sqrt();
+    let side = if sample_1d() < 0.5 {
+        rec.normal
+    } else {
+        -rec.normal
+    };
+    let direction = Onb::new(&side).local(&Vec3::new(
+        sin_theta * phi.cos(),
+        sin_theta * phi.sin(),
+        cos_theta,
+    ));
+    let pdf_dir = cos_theta / (2.0 * PI);
+    if pdf_dir <= 0.0 {
+        return None;
+    }
+
+    Some(Emission {
+        rec,
+        emitted,
+        direction,
+        pdf_pos,
+        pdf_dir,
+        weight: emitted * (cos_theta / (pdf_pos * pdf_dir)),
+    })
+}
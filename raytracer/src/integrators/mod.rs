@@ -0,0 +1,15 @@
+mod ambient_occlusion;
+mod bdpt;
+mod debug;
+mod integrator;
+mod path_tracer;
+mod photon_map;
+mod spectral;
+
+pub use ambient_occlusion::AmbientOcclusion;
+pub use bdpt::Bdpt;
+pub use debug::{BvhHeatmap, NormalsIntegrator, UvIntegrator};
+pub use integrator::*;
+pub use path_tracer::PathTracer;
+pub use photon_map::PhotonMap;
+pub use spectral::Spectral;
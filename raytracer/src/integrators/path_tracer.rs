@@ -0,0 +1,232 @@
+use crate::integrators::{Integrator, PhotonMap};
+use crate::objects::{HitRecord, Hittable};
+use crate::samplers::sample_1d;
+use crate::{utils, Color, Ray};
+use embed_doc_image::embed_doc_image;
+
+/// # Path Tracing
+/// At the core, the ray tracer sends rays through pixels and computes the color seen in the direction
+/// of those rays. The involved steps are (1) calculate the ray from the eye to the pixel, (2) determine
+/// which objects the ray intersects, and (3) compute a color for that intersection point.
+///
+/// In addition to setting up the pixel dimensions for the rendered image, we also need to setup a
+/// virtual viewport through which to pass our scene rays. For the standard square pixel spacing, the
+/// viewport's aspect ratio should be the same as our rendered image. We'll just pick a viewport two
+/// units in height. We'll also set the distance between the projection plane and the projection point
+/// to be one unit. This is referred to as the "focal length"
+///
+/// ![Camera Geometry][camgeom]
+///
+/// The "eye" (or camera center if you think of a camera) is at (0,0,0). The y-axis is pointing upwards,
+/// and the x-axis goes towards the right. In order to respect the convention of a right handed
+/// coordinate system, into the screen is negative z-axis. The screen will be traversed from the upper
+/// left hand corner, and two offset vectors will be used, along the screen sides to move the ray
+/// endpoint across the screen.
+///
+/// A common trick used for visualizing normals (because it's easy and somewhat intuitive to assume
+/// **n** is a unit length vector - so each component is between -1 and 1) is to map each component
+/// to the interval from 0 to 1, and then map x/y/z to r/g/b.
+///
+/// ## Adding Background Color to the Ray Color Function
+/// We want to be able to set a background color (probably black in presence of lights), so the only
+/// light in the scene is coming from the emitters.
+///
+/// What the ray hits is put into `first_hit`, so that the arbitrary output variables can be taken
+/// from the camera ray without tracing it again.
+///
+/// ## Next Event Estimation
+/// A small light, like the one in the ceiling of the Cornell box, is only found by the scattered rays
+/// once in a while, so most paths gather no light at all, and the few which do are very bright. At
+/// every bounce off a surface which isn't specular, a point is therefore also picked on the `lights`
+/// directly, and a shadow ray is sent towards it to see whether it is visible.
+///
+/// The light arriving along a direction is now counted twice, once by each way of picking the
+/// direction, so both are weighted with multiple importance sampling: the power heuristic weighs
+/// each by the square of the density it picks the direction with, which favours sampling the lights
+/// for small lights, and sampling the BSDF for large lights and glossy surfaces. The light found at
+/// the end of a ray scattered off a specular lobe counts fully, as the lights can't pick that
+/// direction.
+///
+/// ## Russian Roulette
+/// Every bounce multiplies the *throughput* of the path, the fraction of the light at its end which
+/// makes it back to the camera, by the weight of the scattered direction, so after a few bounces off
+/// dark surfaces, the rest of the path hardly matters. Rather than tracing it all the way to
+/// `max_depth`, after `russian_roulette_depth` bounces the path is ended at random, with a
+/// probability which grows as the throughput drops. The paths which carry on make up for the ones
+/// which were ended by having their throughput divided by the probability of carrying on, so on
+/// average, the image comes out the same. `max_depth` still bounds the length of a path, in case it
+/// gets stuck between surfaces which reflect most of the light.
+///
+/// The path is followed in a loop, bounce by bounce, adding up the light found along the way.
+///
+/// Without sampling the lights, this is the naive path tracer, which only finds the lights by
+/// chance.
+///
+/// ## Caustics
+/// With a [`PhotonMap`] of the caustics, the light the caustics reflect is looked up in it at every
+/// diffuse bounce. The same light would also be found by the path scattering off the surface
+/// through the specular surfaces onto a light, so the lights found that way are left out.
+#[embed_doc_image("camgeom", "doc_images/camera_geometry.jpg")]
+pub struct PathTracer {
+    max_depth: u32,
+    russian_roulette_depth: u32,
+    sample_lights: bool,
+    caustics: Option<PhotonMap>,
+}
+
+impl PathTracer {
+    /// Returns a path tracer which follows the paths for at most `max_depth` bounces, ending them
+    /// at random after `russian_roulette_depth` bounces, and which picks points on the lights at
+    /// every bounce which isn't specular if `sample_lights` is set.
+    pub fn new(max_depth: u32, russian_roulette_depth: u32, sample_lights: bool) -> PathTracer {
+        PathTracer {
+            max_depth,
+            russian_roulette_depth,
+            sample_lights,
+            caustics: None,
+        }
+    }
+
+    /// Returns the path tracer gathering the light of the caustics from the given photon map.
+    pub fn with_caustics(self, caustics: PhotonMap) -> PathTracer {
+        PathTracer {
+            caustics: Some(caustics),
+            ..self
+        }
+    }
+}
+
+impl Integrator for PathTracer {
+    fn ray_color(
+        &self,
+        r: &Ray,
+        world: &dyn Hittable,
+        lights: Option<&dyn Hittable>,
+        bg_color: &Color,
+        first_hit: &mut Option<HitRecord>,
+    ) -> Color {
+        let (max_depth, russian_roulette_depth) = (self.max_depth, self.russian_roulette_depth);
+        let lights = lights.filter(|_| self.sample_lights);
+        let mut radiance = Color::new(0.0, 0.0, 0.0);
+        let mut throughput = Color::new(1.0, 1.0, 1.0);
+        let mut ray = Ray::new_with_time(&r.origin(), &r.direction(), r.time());
+        let mut bsdf_pdf = None;
+        // Whether the caustics were gathered at the last diffuse bounce, which the path has only
+        // scattered off specular surfaces since.
+        let mut caustics_gathered = false;
+
+        // Once the ray bounce limit is reached, no more light is gathered.
+        for depth in 0..max_depth {
+            let light_weight = match (lights, bsdf_pdf) {
+                (Some(lights), Some(pdf)) => {
+                    power_heuristic(pdf, lights.pdf_value(&ray.origin(), &ray.direction()))
+                }
+                _ => 1.0,
+            };
+
+            // # Fixing the shadow Acne
+            // Some of the reflected rays hit the object they are reflecting off of not at exactly
+            // at **t = 0**, but instead at **t = -0.0000001** or **t = 0.0000001** or whatever
+            // floating point approximation the sphere intersector gives us. So we need to ignore
+            // hits very near zero: So pass the t_min as 0.001.
+            let hit_rec = match world.hit(&ray, 0.001, utils::INFINITY) {
+                Some(hit_rec) => hit_rec,
+                // If the ray hits nothing, return the background color
+                None => {
+                    radiance += throughput * (light_weight * *bg_color);
+                    break;
+                }
+            };
+
+            if !(caustics_gathered && bsdf_pdf.is_none()) {
+                let emitted = hit_rec.mat.emitted(hit_rec.u, hit_rec.v, &hit_rec.p);
+                radiance += throughput * (light_weight * emitted);
+            }
+
+            // The light reaching the surface is gathered even if the scattered direction picked is
+            // absorbed, like the fuzzed directions of a metal which point into it.
+            let sample = hit_rec.mat.sample(&ray, &hit_rec);
+            if !sample.as_ref().is_some_and(|sample| sample.specular) {
+                // The shadow ray ends the path one bounce later, so stop at the same depth as the
+                // scattered ray would.
+                if let Some(lights) = lights.filter(|_| depth + 1 < max_depth) {
+                    radiance += throughput * sample_light(&ray, &hit_rec, bg_color, world, lights);
+                }
+
+                caustics_gathered = false;
+                if let Some(caustics) = &self.caustics {
+                    // The photons only land on surfaces.
+                    if !hit_rec.mat.is_volumetric() {
+                        radiance += throughput * caustics.estimate(&ray, &hit_rec);
+                        caustics_gathered = true;
+                    }
+                }
+            }
+            if depth == 0 {
+                *first_hit = Some(hit_rec);
+            }
+
+            let sample = match sample {
+                Some(sample) => sample,
+                None => break,
+            };
+
+            throughput = throughput * sample.weight;
+            bsdf_pdf = (!sample.specular).then_some(sample.pdf);
+            ray = sample.scattered;
+
+            if depth + 1 >= russian_roulette_depth {
+                let survival = throughput
+                    .x()
+                    .max(throughput.y())
+                    .max(throughput.z())
+                    .min(1.0);
+                if survival <= 0.0 || sample_1d() >= survival {
+                    break;
+                }
+                throughput /= survival;
+            }
+        }
+
+        radiance
+    }
+}
+
+/// Returns the light arriving at the hit point `rec` of the ray `r` from a point picked on the
+/// `lights`, weighted against finding it by sampling the BSDF instead.
+fn sample_light(
+    r: &Ray,
+    rec: &HitRecord,
+    bg_color: &Color,
+    world: &dyn Hittable,
+    lights: &dyn Hittable,
+) -> Color {
+    let direction = lights.random(&rec.p);
+    let light_pdf = lights.pdf_value(&rec.p, &direction);
+    let f = rec.mat.eval(r, rec, &direction);
+    if light_pdf <= 0.0 || f.length_squared() == 0.0 {
+        return Color::new(0.0, 0.0, 0.0);
+    }
+
+    // Whatever the shadow ray hits first is what is seen in that direction, which is the light if
+    // nothing is in the way.
+    let shadow_ray = Ray::new_with_time(&rec.p, &direction, r.time());
+    let incoming = match world.hit(&shadow_ray, 0.001, utils::INFINITY) {
+        Some(hit_rec) => hit_rec.mat.emitted(hit_rec.u, hit_rec.v, &hit_rec.p),
+        None => *bg_color,
+    };
+    let bsdf_pdf = rec.mat.pdf(r, rec, &direction);
+
+    power_heuristic(light_pdf, bsdf_pdf) * f * incoming / light_pdf
+}
+
+/// Returns the weight of a sample picked with the density `pdf`, when the same direction could have
+/// also been picked with the density `other_pdf`.
+fn power_heuristic(pdf: f64, other_pdf: f64) -> f64 {
+    let (a, b) = (pdf * pdf, other_pdf * other_pdf);
+    if a == 0.0 {
+        0.0
+    } else {
+        a / (a + b)
+    }
+}
@@ -0,0 +1,294 @@
+use crate::integrators::sample_emission;
+use crate::objects::{HitRecord, Hittable};
+use crate::spectrum::at_current_wavelength;
+use crate::utils::{INFINITY, PI};
+use crate::{Color, Point, Ray, Vec3};
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+/// The fraction of the diagonal of the scene the photons are gathered within, without a radius.
+const DEFAULT_RADIUS_FRACTION: f64 = 0.01;
+
+/// The cosine of the largest angle between the normals of the surface at a point and the surface a
+/// photon landed on, for the photon to count towards the light reflected at the point.
+const MIN_NORMAL_COSINE: f64 = 0.9;
+
+/// A packet of light which landed on a diffuse surface.
+struct Photon {
+    p: Point,
+    // The direction the photon arrived from the light along, and the normal of the surface it
+    // landed on, on the side it arrived at.
+    direction: Vec3,
+    normal: Vec3,
+    power: Color,
+    // The axis the node of the kd-tree at this photon splits the photons along.
+    axis: usize,
+}
+
+/// # Photon Mapping
+/// Light focused by glass or a mirror onto a diffuse surface, a *caustic*, is almost invisible to a
+/// path tracer: the path from the surface has to be scattered through the glass right onto the
+/// light, which picking a point on the light can't help with, as the glass is in the way. Following
+/// the light from the lights instead finds the caustics easily.
+///
+/// Before rendering, `photon_count` photons are sent out from the lights, each carrying an equal
+/// share of the light emitted. The photons which bounce off at least one specular surface, and then
+/// land on a diffuse one, are stored there. The others are dropped, as the path tracer finds that
+/// light well enough by itself. The light a diffuse surface reflects from the caustics is then the
+/// density of the photons around the point: the `nearest` photons are looked up within `radius`,
+/// and their power reflected by the BSDF is divided by the area of the circle they were found in.
+///
+/// The photons are stored in a kd-tree, which splits them in halves along the axis they spread out
+/// the most on, over and over, so that the photons near a point are found without looking at all of
+/// them. The tree is kept in a single array: the photon in the middle of each range is the node
+/// splitting it, with the photons before it on one side and the ones after it on the other side.
+///
+/// The estimate is blurred over the radius, but it converges to the caustic as the number of
+/// photons grows.
+pub struct PhotonMap {
+    photons: Vec<Photon>,
+    nearest: usize,
+    radius: f64,
+}
+
+impl PhotonMap {
+    /// Sends out `photon_count` photons from the `lights`, follows them through the `world` for at
+    /// most `max_depth` bounces, and keeps the ones which make caustics. Without a `radius`, the
+    /// photons are gathered within a hundredth of the diagonal of the bounding box of the world.
+    pub fn caustics(
+        world: &dyn Hittable,
+        lights: &dyn Hittable,
+        photon_count: u32,
+        nearest: usize,
+        radius: Option<f64>,
+        max_depth: u32,
+    ) -> PhotonMap {
+        let mut photons = vec![];
+        for _ in 0..photon_count {
+            if let Some(photon) = trace_caustic_photon(world, lights, max_depth) {
+                photons.push(photon);
+            }
+        }
+        for photon in photons.iter_mut() {
+            photon.power /= photon_count as f64;
+        }
+        build(&mut photons);
+
+        let radius = radius.unwrap_or_else(|| {
+            world.bounding_box(0.0, 1.0).map_or(INFINITY, |bbox| {
+                DEFAULT_RADIUS_FRACTION * (bbox.max() - bbox.min()).length()
+            })
+        });
+
+        PhotonMap {
+            photons,
+            nearest: nearest.max(1),
+            radius,
+        }
+    }
+
+    /// Returns the light the photons around the hit point `rec` reflect along the reverse of the
+    /// ray `r`.
+    pub(crate) fn estimate(&self, r: &Ray, rec: &HitRecord) -> Color {
+        let mut radiance = Color::new(0.0, 0.0, 0.0);
+        if self.photons.is_empty() {
+            return radiance;
+        }
+
+        let mut heap = BinaryHeap::with_capacity(self.nearest + 1);
+        let mut max_distance_squared = self.radius * self.radius;
+        self.nearest_photons(
+            &rec.p,
+            0,
+            self.photons.len(),
+            &mut heap,
+            &mut max_distance_squared,
+        );
+        if heap.is_empty() {
+            return radiance;
+        }
+
+        // With all the nearest photons found, they lie within the circle through the furthest one,
+        // otherwise within the whole radius.
+        let area = if heap.len() == self.nearest {
+            PI * heap.peek().unwrap().distance_squared
+        } else {
+            PI * self.radius * self.radius
+        };
+        for neighbour in heap.iter() {
+            let photon = &self.photons[neighbour.index];
+            // Photons which landed on another surface nearby, like the other side of a corner,
+            // don't light this one.
+            if rec.normal.dot(&photon.normal) < MIN_NORMAL_COSINE {
+                continue;
+            }
+            let wi = -photon.direction;
+            // The BSDF times the cosine, divided back by the cosine, as the photons landing on a
+            // tilted surface spread out over more area already.
+            let cosine = rec.normal.dot(&wi.unit_vector());
+            if cosine <= 0.0 {
+                continue;
+            }
+            radiance += rec.mat.eval(r, rec, &wi) * at_current_wavelength(photon.power) / cosine;
+        }
+
+        radiance / area
+    }
+
+    /// Adds the photons of the subtree over `start..end` which are closer to `p` than the furthest
+    /// one found so far to the `heap`, which keeps the `nearest` of them.
+    fn nearest_photons(
+        &self,
+        p: &Point,
+        start: usize,
+        end: usize,
+        heap: &mut BinaryHeap<Neighbour>,
+        max_distance_squared: &mut f64,
+    ) {
+        if start >= end {
+            return;
+        }
+
+        let mid = start + (end - start) / 2;
+        let photon = &self.photons[mid];
+        let offset = p[photon.axis] - photon.p[photon.axis];
+        // Look on the side of the split the point is on first, so that the nearest photons are
+        // found early, and the other side can be skipped when it is too far away.
+        let (near, far) = if offset < 0.0 {
+            ((start, mid), (mid + 1, end))
+        } else {
+            ((mid + 1, end), (start, mid))
+        };
+
+        self.nearest_photons(p, near.0, near.1, heap, max_distance_squared);
+
+        let distance_squared = (photon.p - *p).length_squared();
+        if distance_squared < *max_distance_squared {
+            heap.push(Neighbour {
+                distance_squared,
+                index: mid,
+            });
+            if heap.len() > self.nearest {
+                heap.pop();
+            }
+            if heap.len() == self.nearest {
+                *max_distance_squared = heap.peek().unwrap().distance_squared;
+            }
+        }
+
+        if offset * offset < *max_distance_squared {
+            self.nearest_photons(p, far.0, far.1, heap, max_distance_squared);
+        }
+    }
+}
+
+/// A photon found near a point, ordered by the distance to it, so that the heap of the nearest
+/// photons has the furthest one on top.
+struct Neighbour {
+    distance_squared: f64,
+    index: usize,
+}
+
+impl PartialEq for Neighbour {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Neighbour {}
+
+impl PartialOrd for Neighbour {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Neighbour {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.distance_squared.total_cmp(&other.distance_squared)
+    }
+}
+
+/// Follows a photon from the lights until it lands on a diffuse surface, and returns it if it
+/// bounced off a specular surface on the way.
+fn trace_caustic_photon(
+    world: &dyn Hittable,
+    lights: &dyn Hittable,
+    max_depth: u32,
+) -> Option<Photon> {
+    let emission = sample_emission(lights)?;
+    let mut ray = Ray::new(&emission.rec.p, &emission.direction);
+    let mut power = emission.weight;
+    let mut specular = false;
+
+    for _ in 0..max_depth {
+        let rec = world.hit(&ray, 0.001, INFINITY)?;
+        // The photon lands on any surface which isn't specular, even if the direction it would
+        // scatter in is absorbed.
+        if let Some(sample) = rec.mat.sample(&ray, &rec).filter(|sample| sample.specular) {
+            power = power * sample.weight;
+            ray = sample.scattered;
+            specular = true;
+            continue;
+        }
+
+        // The light inside volumes doesn't land on a surface, so it is left to the path tracer.
+        if !specular || rec.mat.is_volumetric() {
+            return None;
+        }
+
+        return Some(Photon {
+            p: rec.p,
+            direction: ray.direction(),
+            normal: rec.normal,
+            power,
+            axis: 0,
+        });
+    }
+
+    None
+}
+
+/// Arranges the photons into a kd-tree.
+fn build(photons: &mut [Photon]) {
+    if photons.is_empty() {
+        return;
+    }
+
+    let (min, max) = photons.iter().fold(
+        (
+            Vec3::new(INFINITY, INFINITY, INFINITY),
+            -Vec3::new(INFINITY, INFINITY, INFINITY),
+        ),
+        |(min, max), photon| {
+            (
+                Vec3::new(
+                    min.x().min(photon.p.x()),
+                    min.y().min(photon.p.y()),
+                    min.z().min(photon.p.z()),
+                ),
+                Vec3::new(
+                    max.x().max(photon.p.x()),
+                    max.y().max(photon.p.y()),
+                    max.z().max(photon.p.z()),
+                ),
+            )
+        },
+    );
+    let extent = max - min;
+    let axis = if extent.x() >= extent.y() && extent.x() >= extent.z() {
+        0
+    } else if extent.y() >= extent.z() {
+        1
+    } else {
+        2
+    };
+
+    let mid = photons.len() / 2;
+    photons.select_nth_unstable_by(mid, |a, b| a.p[axis].total_cmp(&b.p[axis]));
+    photons[mid].axis = axis;
+
+    let (before, rest) = photons.split_at_mut(mid);
+    build(before);
+    build(&mut rest[1..]);
+}
@@ -0,0 +1,61 @@
+use crate::integrators::Integrator;
+use crate::objects::{HitRecord, Hittable};
+use crate::samplers::sample_1d;
+use crate::spectrum::{
+    at_current_wavelength, set_current_wavelength, wavelength_to_rgb, MAX_WAVELENGTH,
+    MIN_WAVELENGTH,
+};
+use crate::{Color, Ray};
+use std::sync::Arc;
+
+/// # Spectral Rendering
+/// Light is made of many wavelengths, and an RGB color is just how the mix of them looks to the
+/// eye. Tracing the three channels together works as long as the materials treat all wavelengths
+/// alike, but glass bends the short wavelengths more than the long ones, which splits white light
+/// into a rainbow (*dispersion*). To render that, each path is traced at a single wavelength,
+/// picked at random, so that the glass can bend it by its own amount.
+///
+/// The colors of the scene are given in RGB, so the materials turn the colors they reflect and
+/// emit into smooth spectra, and use their values at the wavelength of the path, the same in all
+/// three channels. The light which arrives at the film at that wavelength is turned back into a
+/// color with the CIE 1931 color matching functions, which give the XYZ color the eye sees the
+/// wavelength as, and from there into linear sRGB.
+///
+/// A single wavelength only gives one hue, so the colors take more samples to settle than when
+/// tracing the channels together.
+pub struct Spectral {
+    integrator: Arc<dyn Integrator>,
+}
+
+impl Spectral {
+    /// Returns an integrator tracing each path of the given integrator at a random wavelength.
+    pub fn new(integrator: Arc<dyn Integrator>) -> Spectral {
+        Spectral { integrator }
+    }
+}
+
+impl Integrator for Spectral {
+    fn ray_color(
+        &self,
+        r: &Ray,
+        world: &dyn Hittable,
+        lights: Option<&dyn Hittable>,
+        background: &Color,
+        first_hit: &mut Option<HitRecord>,
+    ) -> Color {
+        let wavelength = MIN_WAVELENGTH + sample_1d() * (MAX_WAVELENGTH - MIN_WAVELENGTH);
+        set_current_wavelength(Some(wavelength));
+        let radiance = self.integrator.ray_color(
+            r,
+            world,
+            lights,
+            &at_current_wavelength(*background),
+            first_hit,
+        );
+        set_current_wavelength(None);
+
+        // All the channels carry the light at the wavelength.
+        let radiance = (radiance.x() + radiance.y() + radiance.z()) / 3.0;
+        wavelength_to_rgb(radiance, wavelength)
+    }
+}
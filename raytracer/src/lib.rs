@@ -1,26 +1,47 @@
 extern crate core;
 
+mod aov;
 mod camera;
+mod cancellation;
 mod configuration;
+mod denoiser;
+mod filter;
+mod framebuffer;
+mod matrix;
 mod ray;
 mod renderer;
 mod rtweekend;
 mod scene;
+mod tiles;
+mod tonemapping;
 mod vec3;
 
 // Export all the functions structs and constants for use in other crates.
+pub mod integrators;
+pub mod loaders;
 pub mod materials;
 pub mod objects;
+pub mod samplers;
+pub mod spectrum;
 
 pub mod textures;
 
+pub use aov::Aov;
 pub use camera::Camera;
+pub use cancellation::CancellationToken;
 pub use ray::Ray;
 // Reexport rtweekend symbols encapsulated in utils, for better naming.
 pub mod utils {
     pub use crate::rtweekend::*;
 }
-pub use configuration::{load_configuration, ImageSettings};
-pub use renderer::render;
+pub use configuration::{
+    load_configuration, AdaptiveSampling, ImageFormat, ImageSettings, TileOrder,
+};
+pub use denoiser::Denoiser;
+pub use filter::{Filter, FilterType};
+pub use framebuffer::FrameBuffer;
+pub use matrix::Matrix;
+pub use renderer::{render, RenderOutcome, RenderedImage, Renderer};
 pub use scene::Scene;
+pub use tonemapping::{DisplayTransform, ToneMapping};
 pub use vec3::{Color, Point, Vec3};
@@ -0,0 +1,344 @@
+use crate::materials::lights::DiffuseLight;
+use crate::materials::{Dielectric, LambertianMaterial, Material, Metal};
+use crate::objects::{Hittable, TriangleMesh, World};
+use crate::textures::ImageTexture;
+use crate::utils::PI;
+use crate::{Camera, Color, Matrix, Point, Scene, Vec3};
+use gltf::image::Format;
+use gltf::mesh::Mode;
+use gltf::Node;
+use image::{DynamicImage, ImageBuffer};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+/// The vertical field of view, in degrees, of the camera looking at the scene when the file has no
+/// camera.
+const DEFAULT_VFOV: f64 = 40.0;
+
+/// The color of the background of scenes without any emissive materials, which would be black
+/// otherwise.
+const SKY_COLOR: Color = Color { e: [0.7, 0.8, 1.0] };
+
+/// The state of the import, shared by the nodes of the scene.
+struct Import {
+    buffers: Vec<gltf::buffer::Data>,
+    images: Vec<gltf::image::Data>,
+    // The materials and textures made so far, by their indices in the file, so that each is only
+    // made once.
+    materials: HashMap<Option<usize>, Arc<dyn Material>>,
+    textures: HashMap<usize, ImageTexture>,
+    world: World,
+    camera: Option<Camera>,
+    has_lights: bool,
+}
+
+/// # glTF Scenes
+/// glTF 2.0 is the format most 3D tools export scenes to for rendering. A scene is a tree of
+/// *nodes*, each placed relative to its parent by a transform, and holding a mesh or a camera. The
+/// meshes are made of *primitives*, each a list of triangles with a single material, whose
+/// positions, normals, texture coordinates and indices are stored in binary buffers, either next
+/// to the `.gltf` file, or embedded in it, or in the single `.glb` file.
+///
+/// The default scene of the file, or its first scene, is imported into a [`Scene`]:
+/// * every primitive made of triangles becomes a [`TriangleMesh`], with its positions and normals
+///   transformed into the world by the transforms of the nodes above it,
+/// * the first camera found in the tree sets up the [`Camera`], with its position, orientation and
+///   vertical field of view, and the given `aspect_ratio`, since the aspect ratio of the image is
+///   the one which counts. Without a camera, the scene is looked at from the front, so that it all
+///   fits in the image,
+/// * the background is black if any material emits light, and a sky blue otherwise.
+///
+/// ## Materials
+/// glTF describes the materials in the PBR metallic-roughness model, with a base color, which may
+/// come from a texture, a metallic and a roughness factor, an emissive color, and optionally the
+/// transmission and refractive index of the `KHR_materials_transmission` and `KHR_materials_ior`
+/// extensions. They are mapped onto the materials of the ray tracer as follows:
+/// * an emissive material becomes a [`DiffuseLight`] of the emissive color, scaled by the
+///   `KHR_materials_emissive_strength` extension,
+/// * a material transmitting most of the light becomes a [`Dielectric`] of the refractive index,
+/// * a mostly metallic material becomes a [`Metal`] of the base color, as fuzzy as it is rough,
+/// * and any other material becomes a [`LambertianMaterial`] of the base color texture times the
+///   base color, or of the base color without a texture.
+///
+/// The base color textures are sRGB encoded, and decoded to linear colors when they are sampled.
+pub fn load_gltf(path: &Path, aspect_ratio: f64) -> Result<Scene, String> {
+    let (document, buffers, images) =
+        gltf::import(path).map_err(|err| format!("Couldn't import {}: {err}", path.display()))?;
+    let scene = document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+        .ok_or_else(|| format!("{} has no scenes.", path.display()))?;
+
+    let mut import = Import {
+        buffers,
+        images,
+        materials: HashMap::new(),
+        textures: HashMap::new(),
+        world: World::new(),
+        camera: None,
+        has_lights: false,
+    };
+    for node in scene.nodes() {
+        import.add_node(&node, &Matrix::identity(), aspect_ratio)?;
+    }
+
+    if import.world.is_empty() {
+        return Err(format!("{} has no triangles.", path.display()));
+    }
+    let camera = match import.camera {
+        Some(camera) => camera,
+        None => default_camera(&import.world, aspect_ratio),
+    };
+    let background = if import.has_lights {
+        Color::new(0.0, 0.0, 0.0)
+    } else {
+        SKY_COLOR
+    };
+
+    Ok(Scene::new(import.world, camera, background))
+}
+
+impl Import {
+    /// Adds the mesh and camera of the `node` and the nodes below it, which the `parent` transform
+    /// places into the world.
+    fn add_node(&mut self, node: &Node, parent: &Matrix, aspect_ratio: f64) -> Result<(), String> {
+        // glTF stores the matrices column by column.
+        let local = Matrix::new(node.transform().matrix().map(|c| c.map(f64::from))).transpose();
+        let transform = *parent * local;
+
+        if let Some(mesh) = node.mesh() {
+            for primitive in mesh.primitives() {
+                if let Some(mesh) = self.primitive_mesh(&primitive, &transform)? {
+                    self.world.add(Arc::new(mesh));
+                }
+            }
+        }
+
+        if let (Some(camera), None) = (node.camera(), &self.camera) {
+            // Orthographic cameras have no field of view, so they are skipped.
+            if let gltf::camera::Projection::Perspective(perspective) = camera.projection() {
+                // The camera looks down its -Z axis, with +Y up.
+                let lookfrom = transform.transform_point(&Point::new(0.0, 0.0, 0.0));
+                let forward = transform.transform_vector(&Vec3::new(0.0, 0.0, -1.0));
+                let vup = transform.transform_vector(&Vec3::new(0.0, 1.0, 0.0));
+                self.camera = Some(Camera::new(
+                    lookfrom,
+                    lookfrom + forward,
+                    vup,
+                    f64::from(perspective.yfov()) * 180.0 / PI,
+                    aspect_ratio,
+                    0.0,
+                    1.0,
+                    0.0,
+                    1.0,
+                ));
+            }
+        }
+
+        for child in node.children() {
+            self.add_node(&child, &transform, aspect_ratio)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the mesh of the triangles of the `primitive`, moved into the world by the
+    /// `transform`, or none if the primitive isn't made of triangles.
+    fn primitive_mesh(
+        &mut self,
+        primitive: &gltf::Primitive,
+        transform: &Matrix,
+    ) -> Result<Option<TriangleMesh>, String> {
+        let mode = primitive.mode();
+        if !matches!(
+            mode,
+            Mode::Triangles | Mode::TriangleStrip | Mode::TriangleFan
+        ) {
+            return Ok(None);
+        }
+
+        let buffers = &self.buffers;
+        let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(|data| &data[..]));
+        let positions: Vec<Point> = reader
+            .read_positions()
+            .ok_or_else(|| format!("The mesh primitive {} has no positions.", primitive.index()))?
+            .map(|p| transform.transform_point(&to_vec3(p)))
+            .collect();
+        // The normals are transformed by the inverse transpose, like in `Transform`. A transform
+        // squashing the mesh flat leaves nothing to shade, so its normals are left out.
+        let normal_matrix = transform.inverse().map(|inverse| inverse.transpose());
+        let normals = reader
+            .read_normals()
+            .zip(normal_matrix)
+            .map(|(normals, normal_matrix)| {
+                normals
+                    .map(|n| normal_matrix.transform_vector(&to_vec3(n)))
+                    .collect()
+            });
+        // glTF puts the origin of the texture coordinates at the top left of the image, and the
+        // image textures at the bottom left.
+        let uvs = reader.read_tex_coords(0).map(|uvs| {
+            uvs.into_f32()
+                .map(|[u, v]| (f64::from(u), 1.0 - f64::from(v)))
+                .collect()
+        });
+        let vertices: Vec<usize> = match reader.read_indices() {
+            Some(indices) => indices.into_u32().map(|i| i as usize).collect(),
+            None => (0..positions.len()).collect(),
+        };
+
+        let indices = match mode {
+            Mode::Triangles => vertices
+                .chunks_exact(3)
+                .map(|t| [t[0], t[1], t[2]])
+                .collect(),
+            // Every other triangle of a strip goes around the other way, so two of its corners
+            // are swapped to keep them all facing the same way.
+            Mode::TriangleStrip => (0..vertices.len().saturating_sub(2))
+                .map(|i| {
+                    if i % 2 == 0 {
+                        [vertices[i], vertices[i + 1], vertices[i + 2]]
+                    } else {
+                        [vertices[i + 1], vertices[i], vertices[i + 2]]
+                    }
+                })
+                .collect(),
+            _ => (1..vertices.len().saturating_sub(1))
+                .map(|i| [vertices[0], vertices[i], vertices[i + 1]])
+                .collect::<Vec<_>>(),
+        };
+        if indices.is_empty() {
+            return Ok(None);
+        }
+
+        let material = self.material(&primitive.material())?;
+        TriangleMesh::new(positions, normals, uvs, indices, material)
+            .map(Some)
+            .map_err(|err| format!("The mesh primitive {}: {err}", primitive.index()))
+    }
+
+    /// Returns the material of the ray tracer closest to the glTF `material`.
+    fn material(&mut self, material: &gltf::Material) -> Result<Arc<dyn Material>, String> {
+        if let Some(made) = self.materials.get(&material.index()) {
+            return Ok(made.clone());
+        }
+
+        let pbr = material.pbr_metallic_roughness();
+        let strength = f64::from(material.emissive_strength().unwrap_or(1.0));
+        let emissive = to_vec3(material.emissive_factor()) * strength;
+        let transmission = material
+            .transmission()
+            .map_or(0.0, |transmission| transmission.transmission_factor());
+        let [r, g, b, _] = pbr.base_color_factor();
+        let base_color = to_vec3([r, g, b]);
+
+        let made: Arc<dyn Material> = if emissive.x().max(emissive.y()).max(emissive.z()) > 0.0 {
+            self.has_lights = true;
+            Arc::new(DiffuseLight::new(emissive))
+        } else if transmission > 0.5 {
+            Arc::new(Dielectric::new(f64::from(material.ior().unwrap_or(1.5))))
+        } else if pbr.metallic_factor() > 0.5 {
+            Arc::new(Metal::new(base_color, f64::from(pbr.roughness_factor())))
+        } else {
+            match pbr.base_color_texture() {
+                Some(info) => Arc::new(LambertianMaterial::new_with_texture(Arc::new(
+                    self.texture(info.texture().source().index())?
+                        .with_factor(base_color),
+                ))),
+                None => Arc::new(LambertianMaterial::new(base_color)),
+            }
+        };
+
+        self.materials.insert(material.index(), made.clone());
+        Ok(made)
+    }
+
+    /// Returns the texture of the sRGB encoded image at `index` in the file. The textures share
+    /// their images, so they are cheap to clone.
+    fn texture(&mut self, index: usize) -> Result<ImageTexture, String> {
+        if let Some(texture) = self.textures.get(&index) {
+            return Ok(texture.clone());
+        }
+
+        let data = self
+            .images
+            .get(index)
+            .ok_or_else(|| format!("The image {index} is missing."))?;
+        let image = to_image(data).ok_or_else(|| format!("The image {index} is malformed."))?;
+        let texture = ImageTexture::from_image(image).with_srgb();
+        self.textures.insert(index, texture.clone());
+
+        Ok(texture)
+    }
+}
+
+/// Returns the camera looking at the whole `world` from the front, along -Z.
+fn default_camera(world: &World, aspect_ratio: f64) -> Camera {
+    let (center, radius) = match world.bounding_box(0.0, 1.0) {
+        Some(bbox) => (
+            0.5 * (bbox.min() + bbox.max()),
+            0.5 * (bbox.max() - bbox.min()).length(),
+        ),
+        None => (Point::new(0.0, 0.0, 0.0), 1.0),
+    };
+    // The distance at which a sphere around the world just fits between the top and the bottom of
+    // the image.
+    let half_fov = (DEFAULT_VFOV / 2.0).to_radians();
+    let distance = radius / half_fov.sin();
+
+    Camera::new(
+        center + Vec3::new(0.0, 0.0, distance),
+        center,
+        Vec3::new(0.0, 1.0, 0.0),
+        DEFAULT_VFOV,
+        aspect_ratio,
+        0.0,
+        1.0,
+        0.0,
+        1.0,
+    )
+}
+
+/// Converts the pixels of an image read from the file back into an image.
+fn to_image(data: &gltf::image::Data) -> Option<DynamicImage> {
+    let (width, height, pixels) = (data.width, data.height, data.pixels.clone());
+    // The channels wider than a byte are stored in the byte order of the machine.
+    let u16s = || {
+        data.pixels
+            .chunks_exact(2)
+            .map(|b| u16::from_ne_bytes([b[0], b[1]]))
+            .collect::<Vec<_>>()
+    };
+    let f32s = || {
+        data.pixels
+            .chunks_exact(4)
+            .map(|b| f32::from_ne_bytes([b[0], b[1], b[2], b[3]]))
+            .collect::<Vec<_>>()
+    };
+
+    Some(match data.format {
+        Format::R8 => DynamicImage::ImageLuma8(ImageBuffer::from_raw(width, height, pixels)?),
+        Format::R8G8 => DynamicImage::ImageLumaA8(ImageBuffer::from_raw(width, height, pixels)?),
+        Format::R8G8B8 => DynamicImage::ImageRgb8(ImageBuffer::from_raw(width, height, pixels)?),
+        Format::R8G8B8A8 => DynamicImage::ImageRgba8(ImageBuffer::from_raw(width, height, pixels)?),
+        Format::R16 => DynamicImage::ImageLuma16(ImageBuffer::from_raw(width, height, u16s())?),
+        Format::R16G16 => DynamicImage::ImageLumaA16(ImageBuffer::from_raw(width, height, u16s())?),
+        Format::R16G16B16 => {
+            DynamicImage::ImageRgb16(ImageBuffer::from_raw(width, height, u16s())?)
+        }
+        Format::R16G16B16A16 => {
+            DynamicImage::ImageRgba16(ImageBuffer::from_raw(width, height, u16s())?)
+        }
+        Format::R32G32B32FLOAT => {
+            DynamicImage::ImageRgb32F(ImageBuffer::from_raw(width, height, f32s())?)
+        }
+        Format::R32G32B32A32FLOAT => {
+            DynamicImage::ImageRgba32F(ImageBuffer::from_raw(width, height, f32s())?)
+        }
+    })
+}
+
+fn to_vec3(v: [f32; 3]) -> Vec3 {
+    Vec3::new(f64::from(v[0]), f64::from(v[1]), f64::from(v[2]))
+}
@@ -0,0 +1,9 @@
+mod gltf;
+mod obj;
+mod ply;
+mod stl;
+
+pub use self::gltf::load_gltf;
+pub use obj::load_obj;
+pub use ply::{load_ply, PlyMesh};
+pub use stl::load_stl;
@@ -0,0 +1,573 @@
+use crate::materials::lights::DiffuseLight;
+use crate::materials::{Dielectric, LambertianMaterial, Material, Metal};
+use crate::objects::{TriangleMesh, World};
+use crate::textures::ImageTexture;
+use crate::{Color, Point, Vec3};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// The diffuse color of the faces without a material, and of the materials without one.
+const DEFAULT_DIFFUSE: f64 = 0.8;
+
+/// A corner of a face: the indices of its position, and of its texture coordinates and normal, if
+/// it has them.
+type Corner = (usize, Option<usize>, Option<usize>);
+
+/// The materials of MTL files by their names.
+type Materials = HashMap<String, Arc<dyn Material>>;
+
+/// The triangles of a group of the file which share a material.
+struct Group {
+    material: String,
+    triangles: Vec<[Corner; 3]>,
+}
+
+/// # Wavefront OBJ Files
+/// OBJ is a plain text format, with a statement per line. The statements used are
+/// * `v x y z`, `vt u v` and `vn x y z`, which add a position, texture coordinates and a normal to
+///   the lists the faces refer to,
+/// * `f v/vt/vn ...`, which adds a face with a corner for each argument, given by the indices of
+///   its position, texture coordinates and normal in the lists, starting from 1, or counting back
+///   from the last one added if negative. The texture coordinates and the normal can be left out,
+///   as in `v//vn`, `v/vt` or just `v`. Faces with more than three corners are split into a fan of
+///   triangles around the first corner, which is right for the convex faces exporters write,
+/// * `g name` and `o name`, which start a group of faces,
+/// * `mtllib file` and `usemtl name`, which read the materials in an MTL file, and pick the one
+///   the faces after it are made of.
+///
+/// Each group of faces with the same material becomes a [`TriangleMesh`] in the world returned.
+/// The corners shared between the faces of a group are stored once in the mesh. A mesh has normals
+/// and texture coordinates only if all of its corners have them.
+///
+/// ## Materials
+/// An MTL file describes the materials in the Phong model, with a diffuse color `Kd`, a specular
+/// color `Ks` and exponent `Ns`, a refractive index `Ni`, an emitted color `Ke`, a diffuse
+/// texture `map_Kd`, and the opacity `d` (or the transparency `Tr`) and lighting model `illum`.
+/// They are mapped onto the materials of the ray tracer as follows:
+/// * a material emitting light becomes a [`DiffuseLight`] of the emitted color,
+/// * a transparent material (`d` below one, or `illum` 4, 6, 7 or 9) becomes a [`Dielectric`] of
+///   the refractive index,
+/// * a material more specular than diffuse becomes a [`Metal`] of the specular color, fuzzier for
+///   smaller exponents,
+/// * and any other material becomes a [`LambertianMaterial`] of the diffuse texture times the
+///   diffuse color, or of the diffuse color without a texture. The texture is sRGB encoded, and
+///   decoded to linear colors when it is sampled.
+///
+/// Statements which don't matter to the ray tracer are skipped. Anything that can't be read, like
+/// a missing file, a malformed number, or an index out of range, is reported as an error with the
+/// file and line it was found at.
+pub fn load_obj(path: &Path) -> Result<World, String> {
+    let obj = parse_obj(&read(path)?, &path.display().to_string())?;
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+
+    let mut materials = Materials::new();
+    for file in &obj.material_libraries {
+        materials.extend(load_mtl(&directory.join(file))?);
+    }
+
+    let default_material: Arc<dyn Material> = Arc::new(LambertianMaterial::new(Color::new(
+        DEFAULT_DIFFUSE,
+        DEFAULT_DIFFUSE,
+        DEFAULT_DIFFUSE,
+    )));
+    let mut world = World::new();
+    for group in obj.groups {
+        let material = if group.material.is_empty() {
+            default_material.clone()
+        } else {
+            materials.get(&group.material).cloned().ok_or_else(|| {
+                format!(
+                    "{} uses the unknown material {}.",
+                    path.display(),
+                    group.material
+                )
+            })?
+        };
+        world.add(Arc::new(build_mesh(
+            &group.triangles,
+            &obj.positions,
+            &obj.uvs,
+            &obj.normals,
+            material,
+        )?));
+    }
+
+    Ok(world)
+}
+
+/// The statements of an OBJ file which matter to the ray tracer.
+struct Obj {
+    positions: Vec<Point>,
+    uvs: Vec<(f64, f64)>,
+    normals: Vec<Vec3>,
+    groups: Vec<Group>,
+    /// The MTL files named by `mtllib`, relative to the OBJ file.
+    material_libraries: Vec<String>,
+}
+
+/// Reads the `source` of an OBJ file, reporting errors at the lines of the file called `name`.
+fn parse_obj(source: &str, name: &str) -> Result<Obj, String> {
+    let mut obj = Obj {
+        positions: vec![],
+        uvs: vec![],
+        normals: vec![],
+        groups: vec![],
+        material_libraries: vec![],
+    };
+    // The index of the group each name and material go to, so that the faces of a group which is
+    // picked again later are added to it.
+    let mut group_indices: HashMap<(String, String), usize> = HashMap::new();
+    let mut group_name = String::new();
+    let mut material_name = String::new();
+
+    for (number, line) in source.lines().enumerate() {
+        let error = |message: String| format!("{name}:{}: {message}", number + 1);
+        let mut tokens = statement(line).split_whitespace();
+        let keyword = match tokens.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+
+        match keyword {
+            "v" => obj.positions.push(parse_vec3(tokens).map_err(error)?),
+            "vn" => obj.normals.push(parse_vec3(tokens).map_err(error)?),
+            "vt" => {
+                let values = parse_floats(tokens).map_err(error)?;
+                match values.as_slice() {
+                    [u] => obj.uvs.push((*u, 0.0)),
+                    [u, v, ..] => obj.uvs.push((*u, *v)),
+                    [] => return Err(error("Texture coordinates need a value.".to_string())),
+                }
+            }
+            "f" => {
+                let corners = tokens
+                    .map(|corner| {
+                        parse_corner(
+                            corner,
+                            obj.positions.len(),
+                            obj.uvs.len(),
+                            obj.normals.len(),
+                        )
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(error)?;
+                if corners.len() < 3 {
+                    return Err(error("A face needs at least three corners.".to_string()));
+                }
+
+                let key = (group_name.clone(), material_name.clone());
+                let groups = &mut obj.groups;
+                let index = *group_indices.entry(key).or_insert_with(|| {
+                    groups.push(Group {
+                        material: material_name.clone(),
+                        triangles: vec![],
+                    });
+                    groups.len() - 1
+                });
+                for i in 1..corners.len() - 1 {
+                    groups[index]
+                        .triangles
+                        .push([corners[0], corners[i], corners[i + 1]]);
+                }
+            }
+            "g" | "o" => group_name = tokens.collect::<Vec<_>>().join(" "),
+            "usemtl" => material_name = tokens.collect::<Vec<_>>().join(" "),
+            "mtllib" => obj.material_libraries.extend(tokens.map(String::from)),
+            _ => {}
+        }
+    }
+
+    if obj.groups.is_empty() {
+        return Err(format!("{name} has no faces."));
+    }
+
+    Ok(obj)
+}
+
+/// Returns the mesh of the triangles, with each of their distinct corners stored once.
+fn build_mesh(
+    triangles: &[[Corner; 3]],
+    positions: &[Point],
+    uvs: &[(f64, f64)],
+    normals: &[Vec3],
+    material: Arc<dyn Material>,
+) -> Result<TriangleMesh, String> {
+    let corners = || triangles.iter().flatten();
+    let has_uvs = corners().all(|corner| corner.1.is_some());
+    let has_normals = corners().all(|corner| corner.2.is_some());
+
+    let mut vertices: HashMap<Corner, usize> = HashMap::new();
+    let mut mesh_positions = vec![];
+    let mut mesh_uvs = vec![];
+    let mut mesh_normals = vec![];
+    let indices = triangles
+        .iter()
+        .map(|triangle| {
+            triangle.map(|corner| {
+                // Without normals for all the corners, the corners which only differ in their
+                // normal are the same vertex, and the same goes for the texture coordinates.
+                let key = (
+                    corner.0,
+                    corner.1.filter(|_| has_uvs),
+                    corner.2.filter(|_| has_normals),
+                );
+                *vertices.entry(key).or_insert_with(|| {
+                    mesh_positions.push(positions[corner.0]);
+                    if let Some(uv) = key.1 {
+                        mesh_uvs.push(uvs[uv]);
+                    }
+                    if let Some(normal) = key.2 {
+                        mesh_normals.push(normals[normal]);
+                    }
+                    mesh_positions.len() - 1
+                })
+            })
+        })
+        .collect();
+
+    TriangleMesh::new(
+        mesh_positions,
+        has_normals.then_some(mesh_normals),
+        has_uvs.then_some(mesh_uvs),
+        indices,
+        material,
+    )
+}
+
+/// The properties of a material in an MTL file.
+struct MtlMaterial {
+    diffuse: Color,
+    specular: Color,
+    specular_exponent: f64,
+    refractive_index: f64,
+    emission: Color,
+    opacity: f64,
+    illumination: u32,
+    diffuse_texture: Option<PathBuf>,
+}
+
+impl Default for MtlMaterial {
+    fn default() -> Self {
+        MtlMaterial {
+            diffuse: Color::new(DEFAULT_DIFFUSE, DEFAULT_DIFFUSE, DEFAULT_DIFFUSE),
+            specular: Color::new(0.0, 0.0, 0.0),
+            specular_exponent: 0.0,
+            refractive_index: 1.5,
+            emission: Color::new(0.0, 0.0, 0.0),
+            opacity: 1.0,
+            illumination: 2,
+            diffuse_texture: None,
+        }
+    }
+}
+
+impl MtlMaterial {
+    /// Returns the material of the ray tracer closest to this one.
+    fn to_material(&self) -> Result<Arc<dyn Material>, String> {
+        let max = |c: &Color| c.x().max(c.y()).max(c.z());
+
+        if max(&self.emission) > 0.0 {
+            return Ok(Arc::new(DiffuseLight::new(self.emission)));
+        }
+        if self.opacity < 1.0 || matches!(self.illumination, 4 | 6 | 7 | 9) {
+            return Ok(Arc::new(Dielectric::new(self.refractive_index)));
+        }
+        if max(&self.specular) > max(&self.diffuse) && self.diffuse_texture.is_none() {
+            // The width of the Phong lobe of the exponent, which is about the roughness of the
+            // surface.
+            let fuzz = (2.0 / (self.specular_exponent + 2.0)).sqrt();
+            return Ok(Arc::new(Metal::new(self.specular, fuzz)));
+        }
+
+        Ok(match &self.diffuse_texture {
+            Some(file) => Arc::new(LambertianMaterial::new_with_texture(Arc::new(
+                ImageTexture::open(file)?
+                    .with_srgb()
+                    .with_factor(self.diffuse),
+            ))),
+            None => Arc::new(LambertianMaterial::new(self.diffuse)),
+        })
+    }
+}
+
+/// Reads the materials in the MTL file at `path`.
+fn load_mtl(path: &Path) -> Result<Materials, String> {
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+    parse_mtl(&read(path)?, &path.display().to_string(), directory)?
+        .into_iter()
+        .map(|(name, material)| Ok((name, material.to_material()?)))
+        .collect()
+}
+
+/// Reads the `source` of an MTL file, reporting errors at the lines of the file called `name`.
+/// The textures are looked up in the `directory` of the file.
+fn parse_mtl(
+    source: &str,
+    name: &str,
+    directory: &Path,
+) -> Result<Vec<(String, MtlMaterial)>, String> {
+    let mut parsed: Vec<(String, MtlMaterial)> = vec![];
+    for (number, line) in source.lines().enumerate() {
+        let error = |message: String| format!("{name}:{}: {message}", number + 1);
+        let mut tokens = statement(line).split_whitespace();
+        let keyword = match tokens.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+
+        if keyword == "newmtl" {
+            let name = tokens.collect::<Vec<_>>().join(" ");
+            parsed.push((name, MtlMaterial::default()));
+            continue;
+        }
+        let material = match parsed.last_mut() {
+            Some((_, material)) => material,
+            None => return Err(error(format!("{keyword} comes before any newmtl."))),
+        };
+
+        match keyword {
+            "Kd" => material.diffuse = parse_vec3(tokens).map_err(error)?,
+            "Ks" => material.specular = parse_vec3(tokens).map_err(error)?,
+            "Ke" => material.emission = parse_vec3(tokens).map_err(error)?,
+            "Ns" => material.specular_exponent = parse_float(tokens).map_err(error)?,
+            "Ni" => material.refractive_index = parse_float(tokens).map_err(error)?,
+            "d" => material.opacity = parse_float(tokens).map_err(error)?,
+            "Tr" => material.opacity = 1.0 - parse_float(tokens).map_err(error)?,
+            "illum" => {
+                material.illumination = parse_float(tokens).map_err(error)? as u32;
+            }
+            // The options of the texture come before the file name, which is the last argument.
+            "map_Kd" => match tokens.last() {
+                Some(file) => material.diffuse_texture = Some(directory.join(file)),
+                None => return Err(error("map_Kd needs a file name.".to_string())),
+            },
+            _ => {}
+        }
+    }
+
+    Ok(parsed)
+}
+
+fn read(path: &Path) -> Result<String, String> {
+    fs::read_to_string(path).map_err(|err| format!("Couldn't read {}: {err}", path.display()))
+}
+
+/// Returns the line without the comment at its end.
+fn statement(line: &str) -> &str {
+    line.split('#').next().unwrap_or("")
+}
+
+fn parse_floats<'a>(tokens: impl Iterator<Item = &'a str>) -> Result<Vec<f64>, String> {
+    tokens
+        .map(|token| {
+            token
+                .parse::<f64>()
+                .map_err(|_| format!("{token} is not a number."))
+        })
+        .collect()
+}
+
+fn parse_float<'a>(tokens: impl Iterator<Item = &'a str>) -> Result<f64, String> {
+    parse_floats(tokens)?
+        .first()
+        .copied()
+        .ok_or_else(|| "Expected a number.".to_string())
+}
+
+fn parse_vec3<'a>(tokens: impl Iterator<Item = &'a str>) -> Result<Vec3, String> {
+    match parse_floats(tokens)?.as_slice() {
+        // Positions may have a fourth, homogeneous coordinate, which is left out.
+        [x, y, z, ..] => Ok(Vec3::new(*x, *y, *z)),
+        _ => Err("Expected three numbers.".to_string()),
+    }
+}
+
+/// Parses a corner of a face, given the number of positions, texture coordinates and normals read
+/// so far.
+fn parse_corner(
+    corner: &str,
+    position_count: usize,
+    uv_count: usize,
+    normal_count: usize,
+) -> Result<Corner, String> {
+    let mut indices = corner.split('/');
+    let position = match indices.next() {
+        Some(index) => parse_index(index, position_count)?,
+        None => return Err(format!("The corner {corner} has no position.")),
+    };
+    let uv = match indices.next() {
+        Some("") | None => None,
+        Some(index) => Some(parse_index(index, uv_count)?),
+    };
+    let normal = match indices.next() {
+        Some("") | None => None,
+        Some(index) => Some(parse_index(index, normal_count)?),
+    };
+
+    Ok((position, uv, normal))
+}
+
+/// Parses an index into a list of `count` elements, starting from 1, or counting back from the end
+/// if negative, and returns it starting from 0.
+fn parse_index(index: &str, count: usize) -> Result<usize, String> {
+    let parsed = index
+        .parse::<i64>()
+        .map_err(|_| format!("{index} is not an index."))?;
+    let resolved = if parsed > 0 {
+        parsed - 1
+    } else {
+        count as i64 + parsed
+    };
+
+    if parsed == 0 || resolved < 0 || resolved >= count as i64 {
+        return Err(format!(
+            "The index {index} is out of range, there are only {count} elements."
+        ));
+    }
+
+    Ok(resolved as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRIANGLE: &str = "v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\nvt 1 0\nvt 0 1\nvn 0 0 1\n";
+
+    /// Returns the triangles of the only group in the `source`.
+    fn triangles(source: &str) -> Vec<[Corner; 3]> {
+        let obj = parse_obj(source, "test.obj").unwrap();
+        assert_eq!(obj.groups.len(), 1);
+        obj.groups.into_iter().next().unwrap().triangles
+    }
+
+    #[test]
+    fn reads_all_the_forms_of_corners() {
+        let faces = "f 1 2 3\nf 1/1 2/2 3/3\nf 1//1 2//1 3//1\nf 1/1/1 2/2/1 3/3/1\n";
+        assert_eq!(
+            triangles(&format!("{TRIANGLE}{faces}")),
+            vec![
+                [(0, None, None), (1, None, None), (2, None, None)],
+                [(0, Some(0), None), (1, Some(1), None), (2, Some(2), None)],
+                [(0, None, Some(0)), (1, None, Some(0)), (2, None, Some(0))],
+                [
+                    (0, Some(0), Some(0)),
+                    (1, Some(1), Some(0)),
+                    (2, Some(2), Some(0))
+                ],
+            ]
+        );
+    }
+
+    #[test]
+    fn counts_negative_indices_back_from_the_last_element() {
+        let source = format!("{TRIANGLE}v 1 1 0\nf -4/-3/-1 -2/-2/-1 -1/-1/-1\n");
+        assert_eq!(
+            triangles(&source),
+            vec![[
+                (0, Some(0), Some(0)),
+                (2, Some(1), Some(0)),
+                (3, Some(2), Some(0))
+            ]]
+        );
+    }
+
+    #[test]
+    fn splits_polygons_into_a_fan() {
+        let source = "v 0 0 0\nv 1 0 0\nv 2 1 0\nv 1 2 0\nv 0 1 0\nf 1 2 3 4 5\n";
+        let fan = triangles(source)
+            .iter()
+            .map(|triangle| triangle.map(|corner| corner.0))
+            .collect::<Vec<_>>();
+        assert_eq!(fan, vec![[0, 1, 2], [0, 2, 3], [0, 3, 4]]);
+    }
+
+    #[test]
+    fn groups_faces_by_name_and_material() {
+        let source = format!(
+            "{TRIANGLE}mtllib a.mtl b.mtl\ng first\nf 1 2 3\nusemtl red # a comment\nf 1 2 3\n\
+             g second\nusemtl red\nf 1 2 3\ng first\nusemtl\nf 3 2 1\n"
+        );
+        let obj = parse_obj(&source, "test.obj").unwrap();
+        assert_eq!(obj.material_libraries, vec!["a.mtl", "b.mtl"]);
+        let groups = obj
+            .groups
+            .iter()
+            .map(|group| (group.material.as_str(), group.triangles.len()))
+            .collect::<Vec<_>>();
+        assert_eq!(groups, vec![("", 2), ("red", 1), ("red", 1)]);
+    }
+
+    #[test]
+    fn reports_errors_at_their_line() {
+        let error = |source: &str| parse_obj(source, "test.obj").err().unwrap();
+        assert_eq!(
+            error("v 0 0 0\n\nv 1 x 0\n"),
+            "test.obj:3: x is not a number."
+        );
+        assert_eq!(
+            error(&format!("{TRIANGLE}f 1 2 4\n")),
+            "test.obj:8: The index 4 is out of range, there are only 3 elements."
+        );
+        assert_eq!(
+            error(&format!("{TRIANGLE}f 1 2 0\n")),
+            "test.obj:8: The index 0 is out of range, there are only 3 elements."
+        );
+        assert_eq!(
+            error(&format!("{TRIANGLE}f 1/1/2 2 3\n")),
+            "test.obj:8: The index 2 is out of range, there are only 1 elements."
+        );
+        assert_eq!(
+            error(&format!("{TRIANGLE}f 1 2\n")),
+            "test.obj:8: A face needs at least three corners."
+        );
+        assert_eq!(
+            error("vt\n"),
+            "test.obj:1: Texture coordinates need a value."
+        );
+        assert_eq!(error(TRIANGLE), "test.obj has no faces.");
+    }
+
+    #[test]
+    fn reads_mtl_materials() {
+        let source = "# materials\nnewmtl glass\nNi 1.33\nd 0.5\n\nnewmtl wood\n\
+                      Kd 0.5 0.25 0.125\nKs 0.1 0.1 0.1\nNs 20\nmap_Kd -bm 1 wood.png\n";
+        let materials = parse_mtl(source, "test.mtl", Path::new("textures")).unwrap();
+        assert_eq!(materials.len(), 2);
+
+        let (name, glass) = &materials[0];
+        assert_eq!(name, "glass");
+        assert_eq!((glass.refractive_index, glass.opacity), (1.33, 0.5));
+
+        let (name, wood) = &materials[1];
+        assert_eq!(name, "wood");
+        assert_eq!(
+            (wood.diffuse.x(), wood.diffuse.y(), wood.diffuse.z()),
+            (0.5, 0.25, 0.125)
+        );
+        assert_eq!(wood.specular_exponent, 20.0);
+        assert_eq!(
+            wood.diffuse_texture.as_deref(),
+            Some(Path::new("textures/wood.png"))
+        );
+    }
+
+    #[test]
+    fn reports_mtl_errors_at_their_line() {
+        let error = |source: &str| parse_mtl(source, "test.mtl", Path::new("")).err().unwrap();
+        assert_eq!(
+            error("\nKd 1 1 1\n"),
+            "test.mtl:2: Kd comes before any newmtl."
+        );
+        assert_eq!(
+            error("newmtl red\nKd 1 1\n"),
+            "test.mtl:2: Expected three numbers."
+        );
+        assert_eq!(
+            error("newmtl red\nmap_Kd\n"),
+            "test.mtl:2: map_Kd needs a file name."
+        );
+    }
+}
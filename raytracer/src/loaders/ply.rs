@@ -0,0 +1,592 @@
+use crate::materials::Material;
+use crate::objects::TriangleMesh;
+use crate::textures::VertexColorTexture;
+use crate::{Color, Point, Vec3};
+use std::fs;
+use std::path::Path;
+use std::str::SplitAsciiWhitespace;
+use std::sync::Arc;
+
+/// The names the texture coordinates of the vertices go by in PLY files.
+const UV_NAMES: [(&str, &str); 4] = [
+    ("u", "v"),
+    ("s", "t"),
+    ("texture_u", "texture_v"),
+    ("texture_s", "texture_t"),
+];
+
+/// The type of a value in a PLY file.
+#[derive(Clone, Copy)]
+enum Scalar {
+    Int8,
+    UInt8,
+    Int16,
+    UInt16,
+    Int32,
+    UInt32,
+    Float32,
+    Float64,
+}
+
+/// A property of the elements of a PLY file: a single value, or a list of values preceded by
+/// their count.
+enum Kind {
+    Scalar(Scalar),
+    List(Scalar, Scalar),
+}
+
+struct Property {
+    name: String,
+    kind: Kind,
+}
+
+/// A kind of element of a PLY file, like the vertices or the faces, and how many of them follow.
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+/// The elements of a PLY file, after its header.
+enum Body<'a> {
+    Ascii(SplitAsciiWhitespace<'a>),
+    Binary { bytes: &'a [u8], big_endian: bool },
+}
+
+/// # PLY Meshes
+/// The Polygon File Format, or PLY, is what most 3D scanners save the meshes they measure as. A
+/// PLY file starts with a plain text header, which lists the kinds of elements in the file, how
+/// many of each there are, and the properties each of them has, with their types, like
+///
+/// ```text
+///     ply
+///     format binary_little_endian 1.0
+///     element vertex 8
+///     property float x
+///     property float y
+///     property float z
+///     property uchar red
+///     property uchar green
+///     property uchar blue
+///     element face 6
+///     property list uchar int vertex_indices
+///     end_header
+/// ```
+///
+/// followed by the values of the properties of every element, as plain text, or in binary, in
+/// either byte order.
+///
+/// The vertices have their position in the `x`, `y` and `z` properties, and may have a normal in
+/// `nx`, `ny` and `nz`, a color in `red`, `green` and `blue`, and texture coordinates in `u` and
+/// `v`, or one of the other names they go by. The faces list the indices of their corners in
+/// `vertex_indices` or `vertex_index`, and faces with more than three corners are split into a fan
+/// of triangles around the first one. Any other elements and properties are skipped.
+///
+/// The colors of the vertices are blended over the triangles of the mesh, and can be used as a
+/// texture, see [`PlyMesh::vertex_colors`].
+pub struct PlyMesh {
+    positions: Vec<Point>,
+    normals: Option<Vec<Vec3>>,
+    uvs: Option<Vec<(f64, f64)>>,
+    colors: Option<Vec<Color>>,
+    indices: Vec<[usize; 3]>,
+}
+
+/// Reads the mesh in the PLY file at `path`. Anything that can't be read, like a malformed header,
+/// a file cut short, or an index out of range, is reported as an error.
+pub fn load_ply(path: &Path) -> Result<PlyMesh, String> {
+    let bytes = fs::read(path).map_err(|err| format!("Couldn't read {}: {err}", path.display()))?;
+    parse_ply(&bytes).map_err(|message| format!("{}: {message}", path.display()))
+}
+
+/// Reads the mesh in the `bytes` of a PLY file.
+fn parse_ply(bytes: &[u8]) -> Result<PlyMesh, String> {
+    let (elements, mut body) = parse_header(bytes)?;
+
+    let mut mesh = PlyMesh {
+        positions: vec![],
+        normals: None,
+        uvs: None,
+        colors: None,
+        indices: vec![],
+    };
+    for element in &elements {
+        match element.name.as_str() {
+            "vertex" => mesh.read_vertices(element, &mut body)?,
+            "face" => mesh.read_faces(element, &mut body)?,
+            _ => {
+                for _ in 0..element.count {
+                    for property in &element.properties {
+                        body.read_property(&property.kind)?;
+                    }
+                }
+            }
+        }
+    }
+
+    if let Some(index) = mesh
+        .indices
+        .iter()
+        .flatten()
+        .find(|&&i| i >= mesh.positions.len())
+    {
+        return Err(format!(
+            "The vertex index {index} is out of range, there are only {} vertices.",
+            mesh.positions.len()
+        ));
+    }
+    if mesh.indices.is_empty() {
+        return Err("There are no faces.".to_string());
+    }
+
+    Ok(mesh)
+}
+
+impl PlyMesh {
+    /// Returns the texture of the colors of the vertices, if they have any, for the material of the
+    /// mesh returned by [`PlyMesh::into_mesh`].
+    pub fn vertex_colors(&self) -> Option<VertexColorTexture> {
+        self.colors.as_ref().map(|_| VertexColorTexture)
+    }
+
+    /// Returns the triangle mesh made of the `material`, to add to the world, with the colors of
+    /// the vertices if they have any.
+    pub fn into_mesh(self, material: Arc<dyn Material>) -> Result<TriangleMesh, String> {
+        let mesh = TriangleMesh::new(
+            self.positions,
+            self.normals,
+            self.uvs,
+            self.indices,
+            material,
+        )?;
+
+        match self.colors {
+            Some(colors) => mesh.with_colors(colors),
+            None => Ok(mesh),
+        }
+    }
+
+    fn read_vertices(&mut self, element: &Element, body: &mut Body) -> Result<(), String> {
+        let find = |name: &str| {
+            element
+                .properties
+                .iter()
+                .position(|property| property.name == name)
+        };
+        let find_all = |names: &[&str]| {
+            names
+                .iter()
+                .map(|name| find(name))
+                .collect::<Option<Vec<_>>>()
+        };
+
+        let position = find_all(&["x", "y", "z"])
+            .ok_or_else(|| "The vertices have no positions.".to_string())?;
+        let normal = find_all(&["nx", "ny", "nz"]);
+        let color = find_all(&["red", "green", "blue"]);
+        let uv = UV_NAMES.iter().find_map(|(u, v)| find_all(&[u, v]));
+        // The colors are usually stored as bytes, and scaled to be between zero and one.
+        let color_scale =
+            color
+                .as_ref()
+                .map_or(1.0, |color| match element.properties[color[0]].kind {
+                    Kind::Scalar(Scalar::UInt8) => 1.0 / 255.0,
+                    Kind::Scalar(Scalar::UInt16) => 1.0 / 65535.0,
+                    _ => 1.0,
+                });
+
+        let mut normals = vec![];
+        let mut colors = vec![];
+        let mut uvs = vec![];
+        let mut values = vec![0.0; element.properties.len()];
+        for _ in 0..element.count {
+            for (value, property) in values.iter_mut().zip(&element.properties) {
+                *value = match property.kind {
+                    Kind::Scalar(scalar) => body.read(scalar)?,
+                    ref list => {
+                        body.read_property(list)?;
+                        0.0
+                    }
+                };
+            }
+            let vec3 = |indices: &[usize]| {
+                Vec3::new(values[indices[0]], values[indices[1]], values[indices[2]])
+            };
+
+            self.positions.push(vec3(&position));
+            if let Some(normal) = &normal {
+                normals.push(vec3(normal));
+            }
+            if let Some(color) = &color {
+                colors.push(color_scale * vec3(color));
+            }
+            if let Some(uv) = &uv {
+                uvs.push((values[uv[0]], values[uv[1]]));
+            }
+        }
+
+        self.normals = normal.map(|_| normals);
+        self.colors = color.map(|_| colors);
+        self.uvs = uv.map(|_| uvs);
+
+        Ok(())
+    }
+
+    fn read_faces(&mut self, element: &Element, body: &mut Body) -> Result<(), String> {
+        let corners = element
+            .properties
+            .iter()
+            .position(|property| {
+                matches!(property.kind, Kind::List(..))
+                    && (property.name == "vertex_indices" || property.name == "vertex_index")
+            })
+            .ok_or_else(|| "The faces have no vertex indices.".to_string())?;
+
+        for _ in 0..element.count {
+            for (index, property) in element.properties.iter().enumerate() {
+                let values = body.read_property(&property.kind)?;
+                if index != corners {
+                    continue;
+                }
+                if values.len() < 3 {
+                    return Err(format!(
+                        "A face has {} corners, at least three are needed.",
+                        values.len()
+                    ));
+                }
+                if let Some(value) = values.iter().find(|&&value| value < 0.0) {
+                    return Err(format!("The vertex index {value} is negative."));
+                }
+
+                let corners: Vec<usize> = values.iter().map(|&value| value as usize).collect();
+                for i in 1..corners.len() - 1 {
+                    self.indices.push([corners[0], corners[i], corners[i + 1]]);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Reads the header at the start of the file, and returns the elements it lists, and the body
+/// right after it.
+fn parse_header(bytes: &[u8]) -> Result<(Vec<Element>, Body<'_>), String> {
+    let mut elements: Vec<Element> = vec![];
+    let mut format = None;
+    let mut start = 0;
+
+    for (number, line) in bytes.split(|&b| b == b'\n').enumerate() {
+        start += line.len() + 1;
+        let line = String::from_utf8_lossy(line);
+        let mut tokens = line.split_whitespace();
+        let keyword = tokens.next();
+        if number == 0 {
+            if keyword != Some("ply") {
+                return Err("This is not a PLY file.".to_string());
+            }
+            continue;
+        }
+
+        match keyword {
+            Some("format") => format = tokens.next().map(str::to_string),
+            Some("element") => {
+                let (name, count) = match (tokens.next(), tokens.next()) {
+                    (Some(name), Some(count)) => (name, count),
+                    _ => return Err(format!("Malformed element: {line}")),
+                };
+                elements.push(Element {
+                    name: name.to_string(),
+                    count: count
+                        .parse()
+                        .map_err(|_| format!("{count} is not an element count."))?,
+                    properties: vec![],
+                });
+            }
+            Some("property") => {
+                let tokens: Vec<&str> = tokens.collect();
+                let (kind, name) = match tokens.as_slice() {
+                    ["list", count, item, name] => {
+                        (Kind::List(parse_scalar(count)?, parse_scalar(item)?), name)
+                    }
+                    [scalar, name] => (Kind::Scalar(parse_scalar(scalar)?), name),
+                    _ => return Err(format!("Malformed property: {line}")),
+                };
+                elements
+                    .last_mut()
+                    .ok_or_else(|| format!("The property {name} belongs to no element."))?
+                    .properties
+                    .push(Property {
+                        name: name.to_string(),
+                        kind,
+                    });
+            }
+            Some("end_header") => {
+                let body = bytes.get(start..).unwrap_or(&[]);
+                let body = match format.as_deref() {
+                    Some("ascii") => Body::Ascii(
+                        std::str::from_utf8(body)
+                            .map_err(|_| "The values are not plain text.".to_string())?
+                            .split_ascii_whitespace(),
+                    ),
+                    Some("binary_little_endian") => Body::Binary {
+                        bytes: body,
+                        big_endian: false,
+                    },
+                    Some("binary_big_endian") => Body::Binary {
+                        bytes: body,
+                        big_endian: true,
+                    },
+                    Some(format) => return Err(format!("Unknown format {format}.")),
+                    None => return Err("The header has no format.".to_string()),
+                };
+                return Ok((elements, body));
+            }
+            // Comments and anything else that doesn't matter to the ray tracer.
+            _ => {}
+        }
+    }
+
+    Err("The header has no end.".to_string())
+}
+
+fn parse_scalar(name: &str) -> Result<Scalar, String> {
+    Ok(match name {
+        "char" | "int8" => Scalar::Int8,
+        "uchar" | "uint8" => Scalar::UInt8,
+        "short" | "int16" => Scalar::Int16,
+        "ushort" | "uint16" => Scalar::UInt16,
+        "int" | "int32" => Scalar::Int32,
+        "uint" | "uint32" => Scalar::UInt32,
+        "float" | "float32" => Scalar::Float32,
+        "double" | "float64" => Scalar::Float64,
+        _ => return Err(format!("Unknown type {name}.")),
+    })
+}
+
+impl Scalar {
+    fn size(self) -> usize {
+        match self {
+            Scalar::Int8 | Scalar::UInt8 => 1,
+            Scalar::Int16 | Scalar::UInt16 => 2,
+            Scalar::Int32 | Scalar::UInt32 | Scalar::Float32 => 4,
+            Scalar::Float64 => 8,
+        }
+    }
+}
+
+impl Body<'_> {
+    /// Reads the values of a property, a single one unless the property is a list.
+    fn read_property(&mut self, kind: &Kind) -> Result<Vec<f64>, String> {
+        match *kind {
+            Kind::Scalar(scalar) => Ok(vec![self.read(scalar)?]),
+            Kind::List(count, item) => {
+                let count = self.read(count)?;
+                (0..count as usize).map(|_| self.read(item)).collect()
+            }
+        }
+    }
+
+    /// Reads the next value, of the given type.
+    fn read(&mut self, scalar: Scalar) -> Result<f64, String> {
+        let (bytes, big_endian) = match self {
+            Body::Ascii(tokens) => {
+                let token = tokens
+                    .next()
+                    .ok_or_else(|| "The file ends too early.".to_string())?;
+                return token
+                    .parse::<f64>()
+                    .map_err(|_| format!("{token} is not a number."));
+            }
+            Body::Binary { bytes, big_endian } => (bytes, *big_endian),
+        };
+
+        if bytes.len() < scalar.size() {
+            return Err("The file ends too early.".to_string());
+        }
+        let (value, rest) = bytes.split_at(scalar.size());
+        *bytes = rest;
+
+        // The bytes are turned around into little endian order, if they aren't already.
+        let mut le = [0; 8];
+        le[..scalar.size()].copy_from_slice(value);
+        if big_endian {
+            le[..scalar.size()].reverse();
+        }
+        let [b0, b1, b2, b3, ..] = le;
+        Ok(match scalar {
+            Scalar::Int8 => f64::from(b0 as i8),
+            Scalar::UInt8 => f64::from(b0),
+            Scalar::Int16 => f64::from(i16::from_le_bytes([b0, b1])),
+            Scalar::UInt16 => f64::from(u16::from_le_bytes([b0, b1])),
+            Scalar::Int32 => f64::from(i32::from_le_bytes([b0, b1, b2, b3])),
+            Scalar::UInt32 => f64::from(u32::from_le_bytes([b0, b1, b2, b3])),
+            Scalar::Float32 => f64::from(f32::from_le_bytes([b0, b1, b2, b3])),
+            Scalar::Float64 => f64::from_le_bytes(le),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::LambertianMaterial;
+    use crate::objects::Hittable;
+    use crate::utils::INFINITY;
+    use crate::Ray;
+
+    /// The header of a square of four vertices with normals, colors and texture coordinates, made
+    /// of a single face, with an element of another kind in between, in the given format.
+    fn header(format: &str) -> String {
+        format!(
+            "ply\nformat {format} 1.0\ncomment a square\nelement vertex 4\nproperty float x\n\
+             property float y\nproperty float z\nproperty double nx\nproperty double ny\n\
+             property double nz\nproperty uchar red\nproperty uchar green\nproperty uchar blue\n\
+             property float s\nproperty float t\nelement material 1\nproperty list uchar int ids\n\
+             property ushort shine\nelement face 1\nproperty uchar flags\n\
+             property list uchar int vertex_indices\nend_header\n"
+        )
+    }
+
+    /// The corners of the square, and their colors.
+    const CORNERS: [([f32; 3], [u8; 3]); 4] = [
+        ([0.0, 0.0, 0.0], [255, 0, 0]),
+        ([1.0, 0.0, 0.0], [0, 255, 0]),
+        ([1.0, 1.0, 0.0], [0, 0, 255]),
+        ([0.0, 1.0, 0.0], [255, 255, 255]),
+    ];
+
+    fn binary(big_endian: bool) -> Vec<u8> {
+        let format = if big_endian {
+            "binary_big_endian"
+        } else {
+            "binary_little_endian"
+        };
+        let mut bytes = header(format).into_bytes();
+        let f32_bytes = |value: f32| {
+            if big_endian {
+                value.to_be_bytes()
+            } else {
+                value.to_le_bytes()
+            }
+        };
+        let f64_bytes = |value: f64| {
+            if big_endian {
+                value.to_be_bytes()
+            } else {
+                value.to_le_bytes()
+            }
+        };
+        let i32_bytes = |value: i32| {
+            if big_endian {
+                value.to_be_bytes()
+            } else {
+                value.to_le_bytes()
+            }
+        };
+
+        for (position, color) in CORNERS {
+            for coordinate in position {
+                bytes.extend(f32_bytes(coordinate));
+            }
+            for coordinate in [0.0, 0.0, 1.0] {
+                bytes.extend(f64_bytes(coordinate));
+            }
+            bytes.extend(color);
+            bytes.extend(f32_bytes(position[0]));
+            bytes.extend(f32_bytes(position[1]));
+        }
+        bytes.push(2);
+        bytes.extend(i32_bytes(7));
+        bytes.extend(i32_bytes(-7));
+        bytes.extend(if big_endian { [1, 0] } else { [0, 1] });
+        bytes.extend([0, 4]);
+        for index in 0..4 {
+            bytes.extend(i32_bytes(index));
+        }
+
+        bytes
+    }
+
+    fn ascii() -> Vec<u8> {
+        let mut source = header("ascii");
+        for (position, color) in CORNERS {
+            let [x, y, z] = position;
+            let [r, g, b] = color;
+            source.push_str(&format!("{x} {y} {z} 0 0 1 {r} {g} {b} {x} {y}\n"));
+        }
+        source.push_str("2 7 -7 256\n0 4 0 1 2 3\n");
+
+        source.into_bytes()
+    }
+
+    fn assert_square(mesh: &PlyMesh) {
+        let coordinates = |v: &Vec3| [v.x(), v.y(), v.z()];
+        for (i, (position, color)) in CORNERS.iter().enumerate() {
+            assert_eq!(coordinates(&mesh.positions[i]), position.map(f64::from));
+            let color = color.map(|c| f64::from(c) / 255.0);
+            assert_eq!(coordinates(&mesh.colors.as_ref().unwrap()[i]), color);
+            let uv = (f64::from(position[0]), f64::from(position[1]));
+            assert_eq!(mesh.uvs.as_ref().unwrap()[i], uv);
+            assert_eq!(
+                coordinates(&mesh.normals.as_ref().unwrap()[i]),
+                [0.0, 0.0, 1.0]
+            );
+        }
+        assert_eq!(mesh.indices, vec![[0, 1, 2], [0, 2, 3]]);
+    }
+
+    #[test]
+    fn reads_ascii_files() {
+        assert_square(&parse_ply(&ascii()).unwrap());
+    }
+
+    #[test]
+    fn reads_binary_files_in_either_byte_order() {
+        assert_square(&parse_ply(&binary(false)).unwrap());
+        assert_square(&parse_ply(&binary(true)).unwrap());
+    }
+
+    #[test]
+    fn keeps_the_texture_coordinates_with_colors() {
+        let mesh = parse_ply(&ascii()).unwrap();
+        assert!(mesh.vertex_colors().is_some());
+        let material = Arc::new(LambertianMaterial::new(Color::new(0.5, 0.5, 0.5)));
+        let mesh = mesh.into_mesh(material).unwrap();
+
+        let r = Ray::new(&Point::new(0.25, 0.5, 1.0), &Vec3::new(0.0, 0.0, -1.0));
+        let rec = mesh.hit(&r, 0.001, INFINITY).unwrap();
+        assert_eq!((rec.u, rec.v), (0.25, 0.5));
+        let expected = 0.5 * Color::new(1.0, 0.0, 0.0)
+            + 0.25 * Color::new(0.0, 0.0, 1.0)
+            + 0.25 * Color::new(1.0, 1.0, 1.0);
+        assert!((rec.color.unwrap() - expected).length() < 1e-12, "{rec:?}");
+    }
+
+    #[test]
+    fn reports_malformed_files() {
+        let error = |bytes: &[u8]| parse_ply(bytes).err().unwrap();
+        let ascii = String::from_utf8(ascii()).unwrap();
+
+        let mut cut = binary(false);
+        cut.truncate(cut.len() - 2);
+        assert_eq!(error(&cut), "The file ends too early.");
+        assert_eq!(error(b"solid\n"), "This is not a PLY file.");
+        assert_eq!(
+            error(ascii.replace("format ascii", "format text").as_bytes()),
+            "Unknown format text."
+        );
+        assert_eq!(
+            error(ascii.replace("0 4 0 1 2 3", "0 4 0 1 2 4").as_bytes()),
+            "The vertex index 4 is out of range, there are only 4 vertices."
+        );
+        assert_eq!(
+            error(ascii.replace("0 4 0 1 2 3", "0 2 0 1").as_bytes()),
+            "A face has 2 corners, at least three are needed."
+        );
+        assert_eq!(
+            error(ascii.replace("end_header\n", "").as_bytes()),
+            "The header has no end."
+        );
+    }
+}
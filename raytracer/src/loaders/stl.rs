@@ -0,0 +1,265 @@
+use crate::materials::Material;
+use crate::objects::TriangleMesh;
+use crate::Point;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+/// The size of the header of a binary STL file, and of the count of triangles after it.
+const BINARY_HEADER_SIZE: usize = 84;
+
+/// The size of a triangle in a binary STL file: its normal and corners, as 12 floats, and two
+/// bytes of attributes.
+const BINARY_TRIANGLE_SIZE: usize = 50;
+
+/// # STL Meshes
+/// STL is the format CAD programs export parts as, for 3D printing. It is a plain list of
+/// triangles, each with its normal and its three corners, with nothing else about the surface. It
+/// comes in two flavours:
+/// * the binary one, with a header of 80 bytes, the number of triangles, and 50 bytes for each
+///   triangle: its normal and corners, as little endian floats, and two bytes of attributes,
+/// * and the plain text one, which lists the triangles as
+///
+/// ```text
+///     solid name
+///       facet normal nx ny nz
+///         outer loop
+///           vertex x y z
+///           vertex x y z
+///           vertex x y z
+///         endloop
+///       endfacet
+///     endsolid name
+/// ```
+///
+/// Some programs start the header of their binary files with `solid` too, so a file is taken to be
+/// binary when its size matches the number of triangles in its header.
+///
+/// The normals are left out, since the corners go around the outward side counterclockwise
+/// anyway, and the triangles are flat. The corners shared between triangles are stored once in the
+/// mesh returned, which is made of the given `material`.
+pub fn load_stl(path: &Path, material: Arc<dyn Material>) -> Result<TriangleMesh, String> {
+    let bytes = fs::read(path).map_err(|err| format!("Couldn't read {}: {err}", path.display()))?;
+    let (positions, indices) = parse_stl(&bytes, &path.display().to_string())?;
+
+    TriangleMesh::new(positions, None, None, indices, material)
+}
+
+/// Reads the `bytes` of an STL file, reporting errors for the file called `name`, and returns the
+/// distinct corners of the triangles, and the indices of the corners of each triangle in them.
+fn parse_stl(bytes: &[u8], name: &str) -> Result<(Vec<Point>, Vec<[usize; 3]>), String> {
+    let triangles = if is_binary(bytes) {
+        read_binary(bytes)
+    } else if bytes.trim_ascii_start().starts_with(b"solid") {
+        let source =
+            std::str::from_utf8(bytes).map_err(|_| format!("{name} is not plain text."))?;
+        read_ascii(source).map_err(|err| format!("{name}:{err}"))?
+    } else {
+        return Err(format!("{name} is not an STL file."));
+    };
+    if triangles.is_empty() {
+        return Err(format!("{name} has no triangles."));
+    }
+
+    // Positions are only the same if they are exactly the same, which they are for the corners
+    // shared between triangles, since exporters write them out the same way every time.
+    let mut vertices: HashMap<[u64; 3], usize> = HashMap::new();
+    let mut positions = vec![];
+    let indices = triangles
+        .iter()
+        .map(|triangle| {
+            triangle.map(|corner| {
+                let key = [corner.x(), corner.y(), corner.z()].map(f64::to_bits);
+                *vertices.entry(key).or_insert_with(|| {
+                    positions.push(corner);
+                    positions.len() - 1
+                })
+            })
+        })
+        .collect();
+
+    Ok((positions, indices))
+}
+
+fn is_binary(bytes: &[u8]) -> bool {
+    bytes.len() >= BINARY_HEADER_SIZE
+        && u32::from_le_bytes([bytes[80], bytes[81], bytes[82], bytes[83]]) as usize
+            * BINARY_TRIANGLE_SIZE
+            == bytes.len() - BINARY_HEADER_SIZE
+}
+
+fn read_binary(bytes: &[u8]) -> Vec<[Point; 3]> {
+    bytes[BINARY_HEADER_SIZE..]
+        .chunks_exact(BINARY_TRIANGLE_SIZE)
+        .map(|triangle| {
+            let float = |i: usize| {
+                let start = 12 + 4 * i;
+                f64::from(f32::from_le_bytes([
+                    triangle[start],
+                    triangle[start + 1],
+                    triangle[start + 2],
+                    triangle[start + 3],
+                ]))
+            };
+            // The normal comes first, and is skipped.
+            [0, 1, 2].map(|corner| {
+                Point::new(
+                    float(3 * corner),
+                    float(3 * corner + 1),
+                    float(3 * corner + 2),
+                )
+            })
+        })
+        .collect()
+}
+
+/// Reads the triangles of a plain text file, or returns the line the first error is on, and the
+/// error.
+fn read_ascii(source: &str) -> Result<Vec<[Point; 3]>, String> {
+    let mut triangles = vec![];
+    let mut corners: Vec<Point> = vec![];
+    for (number, line) in source.lines().enumerate() {
+        let error = |message: String| format!("{}: {message}", number + 1);
+        let mut tokens = line.split_whitespace();
+        match tokens.next() {
+            Some("vertex") => {
+                let coordinates = tokens
+                    .map(|token| {
+                        token
+                            .parse::<f64>()
+                            .map_err(|_| error(format!("{token} is not a number.")))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                match coordinates.as_slice() {
+                    [x, y, z] => corners.push(Point::new(*x, *y, *z)),
+                    _ => return Err(error("Expected three numbers.".to_string())),
+                }
+            }
+            Some("endfacet") => {
+                match corners.as_slice() {
+                    [v0, v1, v2] => triangles.push([*v0, *v1, *v2]),
+                    _ => {
+                        return Err(error(format!(
+                            "The facet has {} corners, instead of three.",
+                            corners.len()
+                        )))
+                    }
+                }
+                corners.clear();
+            }
+            // The normals, and the lines around the facets and their corners.
+            _ => {}
+        }
+    }
+
+    Ok(triangles)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two triangles making up a unit square, sharing two of their corners.
+    const SQUARE: [[[f32; 3]; 3]; 2] = [
+        [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0]],
+        [[0.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 1.0, 0.0]],
+    ];
+
+    fn ascii() -> String {
+        let mut source = "solid square\n".to_string();
+        for triangle in SQUARE {
+            source.push_str("  facet normal 0 0 1\n    outer loop\n");
+            for [x, y, z] in triangle {
+                source.push_str(&format!("      vertex {x} {y} {z}\n"));
+            }
+            source.push_str("    endloop\n  endfacet\n");
+        }
+        source.push_str("endsolid square\n");
+
+        source
+    }
+
+    /// Returns the binary file of the square, with a header starting with `solid`, like some
+    /// programs write.
+    fn binary() -> Vec<u8> {
+        let mut bytes = b"solid square".to_vec();
+        bytes.resize(80, b' ');
+        bytes.extend((SQUARE.len() as u32).to_le_bytes());
+        for triangle in SQUARE {
+            for value in [0.0, 0.0, 1.0].iter().chain(triangle.iter().flatten()) {
+                bytes.extend(value.to_le_bytes());
+            }
+            bytes.extend([0, 0]);
+        }
+
+        bytes
+    }
+
+    fn assert_square(parsed: (Vec<Point>, Vec<[usize; 3]>)) {
+        let (positions, indices) = parsed;
+        let coordinates = positions
+            .iter()
+            .map(|p| [p.x(), p.y(), p.z()])
+            .collect::<Vec<_>>();
+        // The corners shared by the triangles are only stored once.
+        assert_eq!(
+            coordinates,
+            vec![
+                [0.0, 0.0, 0.0],
+                [1.0, 0.0, 0.0],
+                [1.0, 1.0, 0.0],
+                [0.0, 1.0, 0.0]
+            ]
+        );
+        assert_eq!(indices, vec![[0, 1, 2], [0, 2, 3]]);
+    }
+
+    #[test]
+    fn reads_ascii_files() {
+        assert_square(parse_stl(ascii().as_bytes(), "test.stl").unwrap());
+    }
+
+    #[test]
+    fn reads_binary_files_starting_with_solid() {
+        let bytes = binary();
+        assert!(is_binary(&bytes));
+        assert!(!is_binary(ascii().as_bytes()));
+        assert_square(parse_stl(&bytes, "test.stl").unwrap());
+    }
+
+    #[test]
+    fn reads_files_with_the_wrong_size_as_ascii() {
+        let mut bytes = binary();
+        bytes.pop();
+        assert!(!is_binary(&bytes));
+        assert_eq!(
+            parse_stl(&bytes, "test.stl").err().unwrap(),
+            "test.stl is not plain text."
+        );
+    }
+
+    #[test]
+    fn reports_malformed_files() {
+        let error = |bytes: &[u8]| parse_stl(bytes, "test.stl").err().unwrap();
+        let ascii = ascii();
+
+        assert_eq!(
+            error(ascii.replacen("vertex 1 0 0", "vertex 1 x 0", 1).as_bytes()),
+            "test.stl:5: x is not a number."
+        );
+        assert_eq!(
+            error(ascii.replacen("vertex 1 0 0", "vertex 1 0", 1).as_bytes()),
+            "test.stl:5: Expected three numbers."
+        );
+        assert_eq!(
+            error(ascii.replacen("      vertex 1 0 0\n", "", 1).as_bytes()),
+            "test.stl:7: The facet has 2 corners, instead of three."
+        );
+        assert_eq!(
+            error(b"solid empty\nendsolid empty\n"),
+            "test.stl has no triangles."
+        );
+        assert_eq!(error(b"ply\n"), "test.stl is not an STL file.");
+    }
+}
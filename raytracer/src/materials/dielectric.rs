@@ -1,5 +1,9 @@
-use crate::utils::random_in_unit_interval;
-use crate::{materials::Material, objects::HitRecord};
+use crate::samplers::sample_1d;
+use crate::spectrum::current_wavelength;
+use crate::{
+    materials::{BsdfSample, Material},
+    objects::HitRecord,
+};
 use crate::{Color, Ray, Vec3};
 use embed_doc_image::embed_doc_image;
 
@@ -86,27 +90,76 @@ use embed_doc_image::embed_doc_image;
 ///     sinθ = √(1 - cos²θ)
 ///     cosθ = R.n
 /// ```
+///
+/// # Dispersion
+/// The refractive index of real glass depends on the wavelength of the light, which is what splits
+/// white light into a rainbow in a prism. Such glass takes its index as a [`RefractiveIndex`] given
+/// by Cauchy's or Sellmeier's equation, and bends each path by the index at the wavelength it is
+/// traced at, when rendering spectrally. Otherwise, the index at the yellow sodium line (587.6 nm),
+/// which glass is usually specified by, is used.
 #[embed_doc_image("rayrefract", "doc_images/ray_refraction.jpg")]
 #[derive(Debug, Clone, Copy)]
 pub struct Dielectric {
-    ir: f64, // Index of Refraction
+    ir: RefractiveIndex,
+}
+
+/// The wavelength in nanometers the refractive index is taken at when tracing the RGB channels
+/// together.
+const REFERENCE_WAVELENGTH: f64 = 587.6;
+
+/// The index of refraction of a dielectric, as a function of the wavelength of the light.
+#[derive(Debug, Clone, Copy)]
+pub enum RefractiveIndex {
+    /// The same index at all wavelengths.
+    Constant(f64),
+    /// Cauchy's equation, **n = a + b / λ²**, with the wavelength **λ** in micrometers. It fits
+    /// most glasses well over the visible range, for e.g. a = 1.5046 and b = 0.00420 for crown
+    /// glass, or a = 2.385 and b = 0.0117 for diamond.
+    Cauchy { a: f64, b: f64 },
+    /// Sellmeier's equation, **n² = 1 + Σ bᵢλ² / (λ² - cᵢ)**, with the wavelength **λ** in
+    /// micrometers. The coefficients are given in the catalogues of the glass makers, for e.g.
+    /// b = [1.03961212, 0.231792344, 1.01046945] and c = [0.00600069867, 0.0200179144, 103.560653]
+    /// for BK7.
+    Sellmeier { b: [f64; 3], c: [f64; 3] },
+}
+
+impl RefractiveIndex {
+    /// Returns the index at the `wavelength`, in nanometers.
+    pub fn at(&self, wavelength: f64) -> f64 {
+        let micrometers = wavelength / 1000.0;
+        let l2 = micrometers * micrometers;
+        match self {
+            RefractiveIndex::Constant(n) => *n,
+            RefractiveIndex::Cauchy { a, b } => a + b / l2,
+            RefractiveIndex::Sellmeier { b, c } => {
+                let n2 = 1.0 + (0..3).map(|i| b[i] * l2 / (l2 - c[i])).sum::<f64>();
+                n2.sqrt()
+            }
+        }
+    }
 }
 
 impl Material for Dielectric {
     fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Ray, Color)> {
+        self.sample(r_in, rec)
+            .map(|sample| (sample.scattered, sample.weight))
+    }
+
+    /// Both the reflection and the refraction are specular lobes. One of them is picked with the
+    /// probability of the light taking it, which cancels out with the Fresnel term in the BSDF, so
+    /// the weight is one either way.
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
         let attenuation = Color::new(1.0, 1.0, 1.0);
-        let refraction_ratio = if rec.front_face {
-            1.0 / self.ir
-        } else {
-            self.ir
-        };
+        let ir = self
+            .ir
+            .at(current_wavelength().unwrap_or(REFERENCE_WAVELENGTH));
+        let refraction_ratio = if rec.front_face { 1.0 / ir } else { ir };
 
         let unit_direction = r_in.direction().unit_vector();
         let cos_theta = f64::min(-unit_direction.dot(&rec.normal), 1.0);
         let sin_theta = f64::sqrt(1.0 - cos_theta * cos_theta);
         let cannot_refract = refraction_ratio * sin_theta > 1.0;
-        let direction = if cannot_refract
-            || reflectance(cos_theta, refraction_ratio) > random_in_unit_interval()
+        let direction = if cannot_refract || reflectance(cos_theta, refraction_ratio) > sample_1d()
         {
             Vec3::reflect(&unit_direction, &rec.normal)
         } else {
@@ -115,12 +168,29 @@ impl Material for Dielectric {
 
         let scattered = Ray::new_with_time(&rec.p, &direction, r_in.time());
 
-        Some((scattered, attenuation))
+        Some(BsdfSample {
+            scattered,
+            weight: attenuation,
+            pdf: 0.0,
+            specular: true,
+        })
+    }
+
+    fn albedo(&self, _rec: &HitRecord) -> Color {
+        // Glass doesn't absorb any light.
+        Color::new(1.0, 1.0, 1.0)
     }
 }
 
 impl Dielectric {
     pub fn new(ir: f64) -> Dielectric {
+        Dielectric {
+            ir: RefractiveIndex::Constant(ir),
+        }
+    }
+
+    /// Returns a dielectric whose refractive index depends on the wavelength.
+    pub fn new_with_index(ir: RefractiveIndex) -> Dielectric {
         Dielectric { ir }
     }
 }
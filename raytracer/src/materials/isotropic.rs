@@ -1,21 +1,50 @@
-use crate::materials::Material;
+use crate::materials::{BsdfSample, Material};
 use crate::objects::HitRecord;
+use crate::spectrum::at_current_wavelength;
 use crate::textures::{SolidColor, Texture};
+use crate::utils::PI;
 use crate::Vec3;
 use crate::{Color, Ray};
 use std::sync::Arc;
 
+/// The phase function of a participating medium (like smoke or fog) which scatters the light
+/// equally in all directions. The directions are picked uniformly over the unit sphere, which has a
+/// density of **1 / 4π**, the same as the phase function itself.
 pub struct Isotropic {
     albedo: Arc<dyn Texture>,
 }
 
 impl Material for Isotropic {
     fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Ray, Color)> {
-        let scattered_ray =
-            Ray::new_with_time(&rec.p, &Vec3::random_vector_in_unit_sphere(), r_in.time());
-        let attenuation = self.albedo.value(rec.u, rec.v, &rec.p);
+        self.sample(r_in, rec)
+            .map(|sample| (sample.scattered, sample.weight))
+    }
+
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
+        let scattered = Ray::new_with_time(&rec.p, &Vec3::random_unit_vector(), r_in.time());
+
+        Some(BsdfSample {
+            scattered,
+            weight: at_current_wavelength(self.albedo.value_at(rec)),
+            pdf: 1.0 / (4.0 * PI),
+            specular: false,
+        })
+    }
+
+    fn is_volumetric(&self) -> bool {
+        true
+    }
+
+    fn eval(&self, _r_in: &Ray, rec: &HitRecord, _wi: &Vec3) -> Color {
+        at_current_wavelength(self.albedo.value_at(rec)) / (4.0 * PI)
+    }
+
+    fn pdf(&self, _r_in: &Ray, _rec: &HitRecord, _wi: &Vec3) -> f64 {
+        1.0 / (4.0 * PI)
+    }
 
-        Some((scattered_ray, attenuation))
+    fn albedo(&self, rec: &HitRecord) -> Color {
+        self.albedo.value_at(rec)
     }
 }
 
@@ -1,13 +1,19 @@
 use crate::materials::material;
 use crate::objects::HitRecord;
+use crate::spectrum::at_current_wavelength;
 use crate::textures::{SolidColor, Texture};
+use crate::utils::PI;
 use crate::{Color, Ray, Vec3};
-use material::Material;
+use material::{BsdfSample, Material};
 use std::sync::Arc;
 
 /// The Lambertian material, it can either scatter always and attenuate by its reflectance **R**, or
 /// it can scatter with no attenuation but absorb the fraction **1 - R** of the rays, or it could be
 /// a mixture of those strategies.
+///
+/// The BSDF of the material is **R / π**. Adding a random unit vector to the normal gives directions
+/// distributed by the cosine of the angle to the normal, with a density of **cos θ / π**, so that the
+/// weight of a sample, the BSDF times the cosine divided by the density, is just **R**.
 #[derive(Clone)]
 pub struct LambertianMaterial {
     albedo: Arc<dyn Texture>,
@@ -15,6 +21,11 @@ pub struct LambertianMaterial {
 
 impl Material for LambertianMaterial {
     fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Ray, Color)> {
+        self.sample(r_in, rec)
+            .map(|sample| (sample.scattered, sample.weight))
+    }
+
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
         let mut scatter_direction = rec.normal + Vec3::random_unit_vector();
 
         // Catch degenerate scatter direction
@@ -22,10 +33,29 @@ impl Material for LambertianMaterial {
             scatter_direction = rec.normal;
         }
 
-        Some((
-            Ray::new_with_time(&rec.p, &scatter_direction, r_in.time()),
-            self.albedo.value(rec.u, rec.v, &rec.p),
-        ))
+        Some(BsdfSample {
+            scattered: Ray::new_with_time(&rec.p, &scatter_direction, r_in.time()),
+            weight: at_current_wavelength(self.albedo.value_at(rec)),
+            pdf: self.pdf(r_in, rec, &scatter_direction),
+            specular: false,
+        })
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, wi: &Vec3) -> Color {
+        at_current_wavelength(self.albedo.value_at(rec)) * self.pdf(r_in, rec, wi)
+    }
+
+    fn pdf(&self, _r_in: &Ray, rec: &HitRecord, wi: &Vec3) -> f64 {
+        let cosine = rec.normal.dot(&wi.unit_vector());
+        if cosine > 0.0 {
+            cosine / PI
+        } else {
+            0.0
+        }
+    }
+
+    fn albedo(&self, rec: &HitRecord) -> Color {
+        self.albedo.value_at(rec)
     }
 }
 
@@ -1,5 +1,6 @@
 use crate::materials::Material;
 use crate::objects::HitRecord;
+use crate::spectrum::at_current_wavelength;
 use crate::textures::{SolidColor, Texture};
 use crate::{Color, Point, Ray};
 use std::sync::Arc;
@@ -18,8 +19,16 @@ impl Material for DiffuseLight {
         None
     }
 
+    fn is_emissive(&self) -> bool {
+        true
+    }
+
     fn emitted(&self, u: f64, v: f64, p: &Point) -> Color {
-        self.emit.value(u, v, p)
+        at_current_wavelength(self.emit.value(u, v, p))
+    }
+
+    fn albedo(&self, rec: &HitRecord) -> Color {
+        self.emit.value_at(rec)
     }
 }
 
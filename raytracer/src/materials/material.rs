@@ -1,12 +1,40 @@
 use crate::objects::HitRecord;
-use crate::{Color, Point, Ray};
+use crate::{Color, Point, Ray, Vec3};
 use core::fmt::Debug;
 use std::fmt::Formatter;
 
+/// A direction sampled from the scattering distribution of a material.
+pub struct BsdfSample {
+    /// The scattered ray, leaving from the hit point.
+    pub scattered: Ray,
+    /// The color the light coming back along the scattered ray is multiplied with: the BSDF times
+    /// the cosine of the angle to the normal, divided by the probability density of the direction.
+    pub weight: Color,
+    /// The probability density of picking the direction, per unit solid angle. This is zero for
+    /// specular lobes.
+    pub pdf: f64,
+    /// Whether the direction was picked from a specular (delta) lobe, like a mirror or glass. Only
+    /// this one direction scatters light, so the BSDF can't be evaluated for any other direction,
+    /// and the direction can't be picked by sampling the lights.
+    pub specular: bool,
+}
+
 /// This trait represents the material which reflects the light falling on it, and also in a particular
 /// color. If the material absorbs all light, then it is a black one, and if it reflects everything,
 /// it would be a white one.
-pub trait Material {
+///
+/// # Importance Sampling
+/// For the renderer to pick the scattered directions itself (for e.g. towards the lights), or to
+/// combine several ways of picking them, a material also describes its scattering distribution:
+///
+/// * `sample` picks a scattered direction, along with its probability density.
+/// * `eval` returns the BSDF for a pair of directions, times the cosine of the angle between the
+///   scattered direction and the normal.
+/// * `pdf` returns the probability density of `sample` picking a given direction.
+///
+/// Both `eval` and `pdf` are zero for specular lobes, which are flagged by `sample` instead. The
+/// default implementations treat `scatter` as a specular lobe, which suits any material.
+pub trait Material: Send + Sync {
     /// Returns the reflected ray, and the color of the material. If the material is black, then it
     /// returns none.
     /// # Arguments
@@ -15,11 +43,53 @@ pub trait Material {
     /// `rec`: The object having this material property being hit.
     fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Ray, Color)>;
 
+    /// Picks a scattered direction for the incident ray `r_in`, or returns none if the light is
+    /// absorbed.
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
+        self.scatter(r_in, rec)
+            .map(|(scattered, attenuation)| BsdfSample {
+                scattered,
+                weight: attenuation,
+                pdf: 0.0,
+                specular: true,
+            })
+    }
+
+    /// Returns the BSDF times the cosine of the angle to the normal, for light arriving from the
+    /// direction `wi` and leaving along the reverse of the incident ray `r_in`.
+    fn eval(&self, _r_in: &Ray, _rec: &HitRecord, _wi: &Vec3) -> Color {
+        Color::new(0.0, 0.0, 0.0)
+    }
+
+    /// Returns the probability density (per unit solid angle) of `sample` picking the direction `wi`
+    /// for the incident ray `r_in`.
+    fn pdf(&self, _r_in: &Ray, _rec: &HitRecord, _wi: &Vec3) -> f64 {
+        0.0
+    }
+
+    /// Returns whether the material scatters the light inside of a volume, like smoke, rather than
+    /// off a surface. The normal of the hit record means nothing for such materials.
+    fn is_volumetric(&self) -> bool {
+        false
+    }
+
+    /// Returns whether the material emits light, so that the objects made of it can be sampled as
+    /// lights.
+    fn is_emissive(&self) -> bool {
+        false
+    }
+
     fn emitted(&self, _u: f64, _v: f64, _p: &Point) -> Color {
         // To avoid all non-emitting materials provide an implementation, we return black as the
         // default color from the trait.
         Color::new(0.0, 0.0, 0.0)
     }
+
+    /// Returns the base color of the material at the hit point, without any lighting. This is used
+    /// for the albedo pass, which guides denoising and compositing.
+    fn albedo(&self, _rec: &HitRecord) -> Color {
+        Color::new(0.0, 0.0, 0.0)
+    }
 }
 
 impl Debug for dyn Material {
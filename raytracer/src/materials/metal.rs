@@ -1,5 +1,7 @@
-use super::material::Material;
+use super::material::{BsdfSample, Material};
 use crate::objects::HitRecord;
+use crate::rtweekend::PI;
+use crate::spectrum::at_current_wavelength;
 use crate::{Color, Ray, Vec3};
 use embed_doc_image::embed_doc_image;
 
@@ -24,6 +26,15 @@ use embed_doc_image::embed_doc_image;
 /// parameter that is just the radius of the sphere (so zero is no perturbation). The catch is that
 /// for big spheres or grazing rays, we may scatter below the surface. We can just have the surface
 /// absorb those.
+///
+/// The endpoint is picked uniformly within the sphere of radius `fuzz`, whose volume is
+/// *V = 4/3 π fuzz³*, around the tip of the unit reflected direction **r**. The directions which
+/// end up below the surface are absorbed. So the density of a direction **ω** (per unit solid
+/// angle) comes from the part of the sphere which lies along **ω**: if the ray from the hit point
+/// along **ω** enters the sphere at *t₁* and leaves it at *t₂*, the density is *(t₂³ - t₁³) / 3V*.
+/// Solving *|tω - r|² = fuzz²* gives *t = ω.r ± √((ω.r)² - 1 + fuzz²)*. Since the light which
+/// isn't absorbed is just tinted by the albedo, the BSDF times the cosine is the albedo times the
+/// density. A metal without any fuzz is a specular lobe.
 #[embed_doc_image("rayreflect", "doc_images/ray_reflection.jpg")]
 #[embed_doc_image("fuzzreflect", "doc_images/generating_fuzzed_reflection_rays.jpg")]
 #[derive(Debug, Clone, Copy)]
@@ -34,6 +45,11 @@ pub struct Metal {
 
 impl Material for Metal {
     fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Ray, Color)> {
+        self.sample(r_in, rec)
+            .map(|sample| (sample.scattered, sample.weight))
+    }
+
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
         let reflected_ray = Vec3::reflect(&r_in.direction().unit_vector(), &rec.normal);
         let scattered = Ray::new_with_time(
             &rec.p,
@@ -41,11 +57,46 @@ impl Material for Metal {
             r_in.time(),
         );
         if scattered.direction().dot(&rec.normal) > 0.0 {
-            Some((scattered, self.albedo))
+            Some(BsdfSample {
+                pdf: self.pdf(r_in, rec, &scattered.direction()),
+                scattered,
+                weight: at_current_wavelength(self.albedo),
+                specular: self.fuzz <= 0.0,
+            })
         } else {
             None
         }
     }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, wi: &Vec3) -> Color {
+        at_current_wavelength(self.albedo) * self.pdf(r_in, rec, wi)
+    }
+
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, wi: &Vec3) -> f64 {
+        let direction = wi.unit_vector();
+        if self.fuzz <= 0.0 || direction.dot(&rec.normal) <= 0.0 {
+            return 0.0;
+        }
+
+        let reflected_ray = Vec3::reflect(&r_in.direction().unit_vector(), &rec.normal);
+        let b = direction.dot(&reflected_ray);
+        let discriminant = b * b - 1.0 + self.fuzz * self.fuzz;
+        if discriminant <= 0.0 {
+            return 0.0;
+        }
+        let (t1, t2) = (b - discriminant.sqrt(), b + discriminant.sqrt());
+        if t2 <= 0.0 {
+            return 0.0;
+        }
+        let t1 = t1.max(0.0);
+        let volume = 4.0 / 3.0 * PI * self.fuzz.powi(3);
+
+        (t2.powi(3) - t1.powi(3)) / (3.0 * volume)
+    }
+
+    fn albedo(&self, _rec: &HitRecord) -> Color {
+        self.albedo
+    }
 }
 
 impl Metal {
@@ -58,3 +109,67 @@ impl Metal {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Point;
+    use std::sync::Arc;
+
+    /// A ray hitting a horizontal metal surface at the origin, arriving along `direction`.
+    fn hit(metal: Metal, direction: Vec3) -> (Ray, HitRecord) {
+        let mut rec = HitRecord::new(Arc::new(metal));
+        rec.p = Point::new(0.0, 0.0, 0.0);
+        rec.normal = Vec3::new(0.0, 1.0, 0.0);
+
+        (Ray::new(&(-direction), &direction), rec)
+    }
+
+    /// Integrates the density of the metal over the sphere of directions, on a grid of equal areas.
+    fn integrate_pdf(metal: &Metal, r_in: &Ray, rec: &HitRecord) -> f64 {
+        const N: usize = 400;
+        let cell = 4.0 * PI / (N * N) as f64;
+        let mut total = 0.0;
+        for i in 0..N {
+            let cos_theta = -1.0 + 2.0 * (i as f64 + 0.5) / N as f64;
+            let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+            for j in 0..N {
+                let phi = 2.0 * PI * (j as f64 + 0.5) / N as f64;
+                let wi = Vec3::new(sin_theta * phi.cos(), cos_theta, sin_theta * phi.sin());
+                total += metal.pdf(r_in, rec, &wi) * cell;
+            }
+        }
+        total
+    }
+
+    #[test]
+    fn weight_is_eval_over_pdf() {
+        let metal = Metal::new(Color::new(0.8, 0.6, 0.2), 0.4);
+        let (r_in, rec) = hit(metal, Vec3::new(1.0, -1.0, 0.0));
+        for _ in 0..200 {
+            let Some(sample) = metal.sample(&r_in, &rec) else {
+                continue;
+            };
+            let wi = sample.scattered.direction();
+            let pdf = metal.pdf(&r_in, &rec, &wi);
+            assert!(!sample.specular);
+            assert!(pdf > 0.0 && (sample.pdf - pdf).abs() < 1e-9 * pdf);
+            assert!((metal.eval(&r_in, &rec, &wi) / pdf - sample.weight).length() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn pdf_integrates_to_at_most_one() {
+        // Looking straight down, none of the fuzzed directions fall below the surface.
+        let metal = Metal::new(Color::new(0.8, 0.8, 0.8), 0.3);
+        let (r_in, rec) = hit(metal, Vec3::new(0.0, -1.0, 0.0));
+        let total = integrate_pdf(&metal, &r_in, &rec);
+        assert!((total - 1.0).abs() < 0.01, "{total}");
+
+        // At a grazing angle, the directions below the surface are absorbed.
+        let metal = Metal::new(Color::new(0.8, 0.8, 0.8), 0.8);
+        let (r_in, rec) = hit(metal, Vec3::new(1.0, -0.2, 0.0));
+        let total = integrate_pdf(&metal, &r_in, &rec);
+        assert!(total > 0.1 && total < 1.0, "{total}");
+    }
+}
@@ -0,0 +1,333 @@
+use crate::rtweekend::degrees_to_radians;
+use crate::vec3::{Point, Vec3};
+use std::ops::Mul;
+
+/// # Affine Transformations
+/// Moving, rotating, scaling and shearing are all *affine* transformations: they map a point
+/// **P** to **AP** + **t**, for a 3x3 matrix **A** and an offset **t**. Writing the points with a
+/// fourth coordinate of one, and the directions with a fourth coordinate of zero, turns them into
+/// a single 4x4 matrix, which moves points but leaves directions unmoved:
+///
+/// ```math
+///     \begin{pmatrix} P' \\ 1 \end{pmatrix} =
+///     \begin{pmatrix} A & t \\ 0 & 1 \end{pmatrix}
+///     \begin{pmatrix} P \\ 1 \end{pmatrix}
+/// ```
+///
+/// Doing one transformation after another is then just multiplying their matrices, so that any
+/// number of them can be combined into a single one. The matrix `b * a` does `a` first, and `b`
+/// after it.
+///
+/// The rotations are counterclockwise when looking down the axis towards the origin, and their
+/// angles are in degrees.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Matrix {
+    m: [[f64; 4]; 4],
+}
+
+impl Matrix {
+    /// Returns the matrix of the given rows.
+    pub fn new(m: [[f64; 4]; 4]) -> Matrix {
+        Matrix { m }
+    }
+
+    /// Returns the matrix which leaves everything where it is.
+    pub fn identity() -> Matrix {
+        Matrix::new([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    /// Returns the matrix moving the points by the `offset`.
+    pub fn translation(offset: &Vec3) -> Matrix {
+        Matrix::new([
+            [1.0, 0.0, 0.0, offset.x()],
+            [0.0, 1.0, 0.0, offset.y()],
+            [0.0, 0.0, 1.0, offset.z()],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    /// Returns the matrix scaling the points away from the origin by the given factor along each
+    /// axis.
+    pub fn scaling(factors: &Vec3) -> Matrix {
+        Matrix::new([
+            [factors.x(), 0.0, 0.0, 0.0],
+            [0.0, factors.y(), 0.0, 0.0],
+            [0.0, 0.0, factors.z(), 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    /// Returns the matrix shearing the points, by adding to each coordinate the others times the
+    /// given factors: *x' = x + xy . y + xz . z*, and so on.
+    pub fn shear(xy: f64, xz: f64, yx: f64, yz: f64, zx: f64, zy: f64) -> Matrix {
+        Matrix::new([
+            [1.0, xy, xz, 0.0],
+            [yx, 1.0, yz, 0.0],
+            [zx, zy, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    /// Returns the matrix rotating the points by the angle around the `axis` through the origin.
+    /// With the axis made unit length as **k**, this is Rodrigues' rotation formula,
+    ///
+    /// ```math
+    ///     v' = cos(θ) . v + sin(θ) . (k × v) + (1 - cos(θ)) . (k · v) . k
+    /// ```
+    pub fn rotation(axis: &Vec3, angle_degrees: f64) -> Matrix {
+        let k = axis.unit_vector();
+        let (sin, cos) = degrees_to_radians(angle_degrees).sin_cos();
+        let c = 1.0 - cos;
+        let (x, y, z) = (k.x(), k.y(), k.z());
+
+        Matrix::new([
+            [
+                cos + x * x * c,
+                x * y * c - z * sin,
+                x * z * c + y * sin,
+                0.0,
+            ],
+            [
+                y * x * c + z * sin,
+                cos + y * y * c,
+                y * z * c - x * sin,
+                0.0,
+            ],
+            [
+                z * x * c - y * sin,
+                z * y * c + x * sin,
+                cos + z * z * c,
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    /// Returns the matrix rotating the points by the Euler angles: first around the x axis, then
+    /// around the y axis, and last around the z axis.
+    pub fn euler_rotation(x_degrees: f64, y_degrees: f64, z_degrees: f64) -> Matrix {
+        Matrix::rotation(&Vec3::new(0.0, 0.0, 1.0), z_degrees)
+            * Matrix::rotation(&Vec3::new(0.0, 1.0, 0.0), y_degrees)
+            * Matrix::rotation(&Vec3::new(1.0, 0.0, 0.0), x_degrees)
+    }
+
+    /// Returns the matrix of the rotation given by the quaternion *w + xi + yj + zk*, which is
+    /// made unit length first. Quaternions are what animation tools and glTF files store
+    /// rotations as, since they blend smoothly, and don't lock up like Euler angles.
+    pub fn quaternion_rotation(x: f64, y: f64, z: f64, w: f64) -> Matrix {
+        let length = (x * x + y * y + z * z + w * w).sqrt();
+        let (x, y, z, w) = (x / length, y / length, z / length, w / length);
+
+        Matrix::new([
+            [
+                1.0 - 2.0 * (y * y + z * z),
+                2.0 * (x * y - z * w),
+                2.0 * (x * z + y * w),
+                0.0,
+            ],
+            [
+                2.0 * (x * y + z * w),
+                1.0 - 2.0 * (x * x + z * z),
+                2.0 * (y * z - x * w),
+                0.0,
+            ],
+            [
+                2.0 * (x * z - y * w),
+                2.0 * (y * z + x * w),
+                1.0 - 2.0 * (x * x + y * y),
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    /// Returns the matrix with the rows and the columns swapped.
+    pub fn transpose(&self) -> Matrix {
+        let mut transposed = [[0.0; 4]; 4];
+        for (i, row) in transposed.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = self.m[j][i];
+            }
+        }
+
+        Matrix::new(transposed)
+    }
+
+    /// Returns the matrix undoing this one, or none if it squashes space flat, like a scaling by
+    /// zero, so that it can't be undone. It is found by Gauss-Jordan elimination, which turns the
+    /// matrix into the identity by adding multiples of rows to each other, while doing the same to
+    /// the identity, which turns it into the inverse.
+    pub fn inverse(&self) -> Option<Matrix> {
+        let mut m = self.m;
+        let mut inverse = Matrix::identity().m;
+
+        for column in 0..4 {
+            // Picking the row with the largest value in the column keeps the rounding errors
+            // small.
+            let pivot =
+                (column..4).max_by(|&a, &b| m[a][column].abs().total_cmp(&m[b][column].abs()))?;
+            if m[pivot][column].abs() < 1e-12 {
+                return None;
+            }
+            m.swap(column, pivot);
+            inverse.swap(column, pivot);
+
+            let scale = 1.0 / m[column][column];
+            for j in 0..4 {
+                m[column][j] *= scale;
+                inverse[column][j] *= scale;
+            }
+            for row in 0..4 {
+                let factor = m[row][column];
+                if row == column || factor == 0.0 {
+                    continue;
+                }
+                for j in 0..4 {
+                    m[row][j] -= factor * m[column][j];
+                    inverse[row][j] -= factor * inverse[column][j];
+                }
+            }
+        }
+
+        Some(Matrix::new(inverse))
+    }
+
+    /// Returns the value in the given row and column.
+    pub fn get(&self, row: usize, column: usize) -> f64 {
+        self.m[row][column]
+    }
+
+    /// Transforms a point, which is moved by the translation.
+    pub fn transform_point(&self, p: &Point) -> Point {
+        self.transform_vector(p) + Vec3::new(self.m[0][3], self.m[1][3], self.m[2][3])
+    }
+
+    /// Transforms a direction, which isn't moved by the translation.
+    pub fn transform_vector(&self, v: &Vec3) -> Vec3 {
+        let row = |i: usize| self.m[i][0] * v.x() + self.m[i][1] * v.y() + self.m[i][2] * v.z();
+        Vec3::new(row(0), row(1), row(2))
+    }
+}
+
+impl Mul for Matrix {
+    type Output = Matrix;
+
+    fn mul(self, rhs: Matrix) -> Matrix {
+        let mut product = [[0.0; 4]; 4];
+        for (i, row) in product.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.m[i][k] * rhs.m[k][j]).sum();
+            }
+        }
+
+        Matrix::new(product)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: &Matrix, b: &Matrix) {
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!(
+                    (a.get(i, j) - b.get(i, j)).abs() < 1e-12,
+                    "{a:?} is not {b:?}"
+                );
+            }
+        }
+    }
+
+    /// A matrix doing a bit of everything.
+    fn affine() -> Matrix {
+        Matrix::translation(&Vec3::new(1.0, -2.0, 3.0))
+            * Matrix::rotation(&Vec3::new(1.0, 2.0, 3.0), 40.0)
+            * Matrix::scaling(&Vec3::new(2.0, 0.5, 3.0))
+            * Matrix::shear(0.3, 0.0, 0.0, -0.2, 0.1, 0.0)
+    }
+
+    #[test]
+    fn inverts_matrices() {
+        let m = affine();
+        let inverse = m.inverse().unwrap();
+        assert_close(&(m * inverse), &Matrix::identity());
+        assert_close(&(inverse * m), &Matrix::identity());
+
+        let p = Point::new(0.5, 1.5, -2.5);
+        let back = inverse.transform_point(&m.transform_point(&p));
+        assert!((back - p).length() < 1e-12, "{back:?} is not {p:?}");
+    }
+
+    #[test]
+    fn finds_no_inverse_of_singular_matrices() {
+        assert!(Matrix::scaling(&Vec3::new(1.0, 0.0, 2.0))
+            .inverse()
+            .is_none());
+        // The third row is the sum of the other two.
+        let flat = Matrix::new([
+            [1.0, 2.0, 3.0, 0.0],
+            [4.0, 5.0, 6.0, 0.0],
+            [5.0, 7.0, 9.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+        assert!(flat.inverse().is_none());
+    }
+
+    #[test]
+    fn rotates_counterclockwise() {
+        // A quarter turn around the y axis takes the x axis to -z.
+        let turned = Matrix::rotation(&Vec3::new(0.0, 1.0, 0.0), 90.0)
+            .transform_vector(&Vec3::new(1.0, 0.0, 0.0));
+        assert!(
+            (turned - Vec3::new(0.0, 0.0, -1.0)).length() < 1e-12,
+            "{turned:?}"
+        );
+    }
+
+    #[test]
+    fn rotates_by_euler_angles_about_each_axis_in_turn() {
+        let (x, y, z) = (30.0, -50.0, 70.0);
+        let euler = Matrix::euler_rotation(x, y, z);
+
+        let v = Vec3::new(0.3, -1.2, 2.0);
+        let mut turned = v;
+        for (axis, angle) in [
+            (Vec3::new(1.0, 0.0, 0.0), x),
+            (Vec3::new(0.0, 1.0, 0.0), y),
+            (Vec3::new(0.0, 0.0, 1.0), z),
+        ] {
+            turned = Matrix::rotation(&axis, angle).transform_vector(&turned);
+        }
+        let difference = euler.transform_vector(&v) - turned;
+        assert!(difference.length() < 1e-12, "{difference:?}");
+
+        assert_close(
+            &Matrix::euler_rotation(0.0, y, 0.0),
+            &Matrix::rotation(&Vec3::new(0.0, 1.0, 0.0), y),
+        );
+    }
+
+    #[test]
+    fn rotates_by_quaternions_like_around_their_axis() {
+        let axis = Vec3::new(1.0, -2.0, 0.5);
+        let angle: f64 = 75.0;
+        let (sin, cos) = (angle.to_radians() / 2.0).sin_cos();
+        let k = axis.unit_vector() * sin;
+
+        assert_close(
+            &Matrix::quaternion_rotation(k.x(), k.y(), k.z(), cos),
+            &Matrix::rotation(&axis, angle),
+        );
+        // Quaternions of any length give the same rotation.
+        assert_close(
+            &Matrix::quaternion_rotation(3.0 * k.x(), 3.0 * k.y(), 3.0 * k.z(), 3.0 * cos),
+            &Matrix::rotation(&axis, angle),
+        );
+    }
+}
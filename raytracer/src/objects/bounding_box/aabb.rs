@@ -95,7 +95,7 @@ impl AABB {
         self.maximum
     }
 
-    pub fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> bool {
+    pub fn hit(&self, r: &Ray, mut t_min: f64, mut t_max: f64) -> bool {
         for a in 0..3 {
             let inv_d = 1.0 / r.direction()[a];
             let mut t0 = (self.min()[a] - r.origin()[a]) * inv_d;
@@ -105,10 +105,12 @@ impl AABB {
                 (t1, t0) = (t0, t1); // swap t0 and t1;
             }
 
-            let t_min_temp = if t0 > t_min { t0 } else { t_min };
-            let t_max_temp = if t1 < t_max { t1 } else { t_max };
+            // The ray is inside the box where it is between the planes of all three slabs, so
+            // narrow the interval down slab by slab.
+            t_min = if t0 > t_min { t0 } else { t_min };
+            t_max = if t1 < t_max { t1 } else { t_max };
 
-            if t_max_temp <= t_min_temp {
+            if t_max <= t_min {
                 return false;
             }
         }
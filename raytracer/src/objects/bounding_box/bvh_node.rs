@@ -3,9 +3,28 @@ use crate::objects::{HitRecord, Hittable, AABB};
 use crate::utils::random_int;
 use crate::Ray;
 use embed_doc_image::embed_doc_image;
+use std::cell::Cell;
 use std::cmp::Ordering;
 use std::sync::Arc;
 
+thread_local! {
+    // The number of nodes of the BVHs the rays traced on this thread visited, for the BVH heatmap.
+    static NODES_VISITED: Cell<u32> = const { Cell::new(0) };
+}
+
+/// Returns the number of nodes of the BVHs visited on this thread since the last call.
+pub(crate) fn take_nodes_visited() -> u32 {
+    NODES_VISITED.with(|nodes| nodes.replace(0))
+}
+
+/// Counts a node visited on this thread, for the BVHs kept inside objects, like meshes. This does
+/// nothing unless the `bvh-heatmap` feature is enabled, as it is done for every node of every ray.
+#[inline(always)]
+pub(crate) fn count_node_visit() {
+    #[cfg(feature = "bvh-heatmap")]
+    NODES_VISITED.with(|nodes| nodes.set(nodes.get() + 1));
+}
+
 /// # Bounding Volume Hierarchies
 /// The ray-object intersection is the main time-bottleneck in a ray tracer, and the time is linear
 /// with the number of objects. But it's a repeated search on the same model, so we ought to be able
@@ -64,6 +83,7 @@ impl Hittable for BVHNode {
     /// A BVH is also going to be a `hittable` -- just like lists of `hittables`. It's really a container
     /// but it can respond to the query "does this ray hit you?".
     fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        count_node_visit();
         if !self.bbox.hit(r, t_min, t_max) {
             return None;
         }
@@ -179,7 +199,3 @@ impl BVHNode {
         }
     }
 }
-
-// For parallel rendering
-unsafe impl Send for BVHNode {}
-unsafe impl Sync for BVHNode {}
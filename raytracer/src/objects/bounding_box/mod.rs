@@ -3,3 +3,4 @@ mod bvh_node;
 
 pub use aabb::AABB;
 pub use bvh_node::BVHNode;
+pub(crate) use bvh_node::{count_node_visit, take_nodes_visited};
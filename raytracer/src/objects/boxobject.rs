@@ -1,7 +1,7 @@
 use super::hittablelist::HittableList;
 use crate::materials::Material;
 use crate::objects::{HitRecord, Hittable, XYRect, XZRect, YZRect, AABB};
-use crate::{Point, Ray};
+use crate::{Point, Ray, Vec3};
 use std::sync::Arc;
 
 pub struct Box {
@@ -18,6 +18,24 @@ impl Hittable for Box {
     fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<AABB> {
         Some(AABB::new(self.min, self.max))
     }
+
+    fn is_light(&self) -> bool {
+        self.sides.is_light()
+    }
+
+    /// The box is sampled as a mixture of its sides, so a direction counts towards both the side it
+    /// enters the box through and the one it leaves it through.
+    fn pdf_value(&self, origin: &Point, direction: &Vec3) -> f64 {
+        self.sides.pdf_value(origin, direction)
+    }
+
+    fn random(&self, origin: &Point) -> Vec3 {
+        self.sides.random(origin)
+    }
+
+    fn sample_surface(&self) -> Option<(HitRecord, f64)> {
+        self.sides.sample_surface()
+    }
 }
 
 impl Box {
@@ -15,11 +15,13 @@ pub struct HitRecord {
     pub u: f64,                 // U,V texture coordinates for color mapping.
     pub v: f64,
     pub front_face: bool,
+    pub object_id: u32, // Index of the object in the world, starting from 1. 0 if not known.
+    pub color: Option<Color>, // Color of the vertices blended at the point, for meshes with them.
 }
 
 /// This trait represents an object that can be hit by a ray and return a result that can be used for
 /// shading or omitting the object from the scene.
-pub trait Hittable {
+pub trait Hittable: Send + Sync {
     /// Returns a HitRecord object containing the details of point of impact, normal at the point of
     /// impact, position (t) on the ray and a bool indicating the front/back face, if the object
     /// implementing this trait is hit. Otherwise returns None.
@@ -31,6 +33,41 @@ pub trait Hittable {
     fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord>;
 
     fn bounding_box(&self, time0: f64, time1: f64) -> Option<AABB>;
+
+    /// Returns whether the object emits light, and supports picking directions towards it with
+    /// `random`, so that the renderer can sample it directly as a light.
+    fn is_light(&self) -> bool {
+        false
+    }
+
+    /// Returns the probability density (per unit solid angle) of `random` picking the `direction`
+    /// from the `origin`, which is zero if the object isn't in that direction.
+    fn pdf_value(&self, _origin: &Point, _direction: &Vec3) -> f64 {
+        0.0
+    }
+
+    /// Returns a direction from the `origin` towards a random point on the object.
+    fn random(&self, _origin: &Point) -> Vec3 {
+        Vec3::new(1.0, 0.0, 0.0)
+    }
+
+    /// Picks a random point on the surface of the object, for e.g. to start a path of light from.
+    /// Returns the hit record of the point, with the outward normal, and the probability density of
+    /// picking it per unit area, or none if the object doesn't support picking points on it.
+    fn sample_surface(&self) -> Option<(HitRecord, f64)> {
+        None
+    }
+}
+
+/// Converts the density of picking a point uniformly on a surface of the given `area`, which the ray
+/// `r` hit at `rec`, to a density per unit solid angle as seen from the origin of the ray. The
+/// further away the surface is, and the more it is tilted away from the ray, the smaller it looks,
+/// and the more the directions towards it are concentrated.
+pub(crate) fn solid_angle_pdf(r: &Ray, rec: &HitRecord, area: f64) -> f64 {
+    let distance_squared = rec.t * rec.t * r.direction().length_squared();
+    let cosine = (r.direction().dot(&rec.normal) / r.direction().length()).abs();
+
+    distance_squared / (cosine * area)
 }
 
 impl HitRecord {
@@ -43,6 +80,8 @@ impl HitRecord {
             u: 0.0,
             v: 0.0,
             front_face: false,
+            object_id: 0,
+            color: None,
         }
     }
 
@@ -63,6 +102,8 @@ impl HitRecord {
             u,
             v,
             front_face,
+            object_id: 0,
+            color: None,
         }
     }
 
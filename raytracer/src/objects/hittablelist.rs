@@ -1,5 +1,8 @@
+use crate::objects::identified::Identified;
 use crate::objects::{HitRecord, Hittable, AABB};
 use crate::ray::Ray;
+use crate::samplers::sample_1d;
+use crate::{Point, Vec3};
 use std::sync::Arc;
 use std::vec::Vec;
 
@@ -50,6 +53,47 @@ impl Hittable for HittableList {
             }
         })
     }
+
+    /// A list only counts as a light if all of its objects are lights, as sampling any of the other
+    /// objects would waste the samples.
+    fn is_light(&self) -> bool {
+        !self.objects.is_empty() && self.objects.iter().all(|obj| obj.is_light())
+    }
+
+    /// The directions are picked towards one of the objects at random, so the density is the
+    /// average of the densities of the objects.
+    fn pdf_value(&self, origin: &Point, direction: &Vec3) -> f64 {
+        if self.objects.is_empty() {
+            return 0.0;
+        }
+
+        let sum: f64 = self
+            .objects
+            .iter()
+            .map(|obj| obj.pdf_value(origin, direction))
+            .sum();
+        sum / self.objects.len() as f64
+    }
+
+    fn random(&self, origin: &Point) -> Vec3 {
+        if self.objects.is_empty() {
+            return Vec3::new(1.0, 0.0, 0.0);
+        }
+
+        self.objects[self.random_index()].random(origin)
+    }
+
+    /// Picks one of the objects at random, and a point on it, so the density is the one of the
+    /// object divided by the number of objects.
+    fn sample_surface(&self) -> Option<(HitRecord, f64)> {
+        if self.objects.is_empty() {
+            return None;
+        }
+
+        self.objects[self.random_index()]
+            .sample_surface()
+            .map(|(rec, pdf)| (rec, pdf / self.objects.len() as f64))
+    }
 }
 
 impl HittableList {
@@ -68,6 +112,41 @@ impl HittableList {
     pub fn clear(&mut self) {
         self.objects.clear();
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
+    fn random_index(&self) -> usize {
+        ((sample_1d() * self.objects.len() as f64) as usize).min(self.objects.len() - 1)
+    }
+
+    /// Returns the objects of the list which emit light, and can be sampled as lights.
+    pub(crate) fn lights(&self) -> HittableList {
+        HittableList {
+            objects: self
+                .objects
+                .iter()
+                .filter(|obj| obj.is_light())
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Returns a copy of the list where the hits on each object are tagged with the position of the
+    /// object in the list, starting from 1.
+    pub(crate) fn with_object_ids(&self) -> HittableList {
+        HittableList {
+            objects: self
+                .objects
+                .iter()
+                .enumerate()
+                .map(|(i, obj)| -> Arc<dyn Hittable> {
+                    Arc::new(Identified::new(i as u32 + 1, obj.clone()))
+                })
+                .collect(),
+        }
+    }
 }
 
 impl Default for HittableList {
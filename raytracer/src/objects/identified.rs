@@ -0,0 +1,48 @@
+use crate::objects::{HitRecord, Hittable, AABB};
+use crate::{Point, Ray, Vec3};
+use std::sync::Arc;
+
+/// Wraps one of the objects of the world, tagging the hits on it with the index of the object so
+/// that the renderer can tell the objects apart, for e.g. to produce an object ID pass.
+pub(crate) struct Identified {
+    id: u32,
+    obj: Arc<dyn Hittable>,
+}
+
+impl Hittable for Identified {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        self.obj.hit(r, t_min, t_max).map(|mut hit_rec| {
+            hit_rec.object_id = self.id;
+            hit_rec
+        })
+    }
+
+    fn bounding_box(&self, time0: f64, time1: f64) -> Option<AABB> {
+        self.obj.bounding_box(time0, time1)
+    }
+
+    fn is_light(&self) -> bool {
+        self.obj.is_light()
+    }
+
+    fn pdf_value(&self, origin: &Point, direction: &Vec3) -> f64 {
+        self.obj.pdf_value(origin, direction)
+    }
+
+    fn random(&self, origin: &Point) -> Vec3 {
+        self.obj.random(origin)
+    }
+
+    fn sample_surface(&self) -> Option<(HitRecord, f64)> {
+        self.obj.sample_surface().map(|(mut rec, pdf)| {
+            rec.object_id = self.id;
+            (rec, pdf)
+        })
+    }
+}
+
+impl Identified {
+    pub(crate) fn new(id: u32, obj: Arc<dyn Hittable>) -> Identified {
+        Identified { id, obj }
+    }
+}
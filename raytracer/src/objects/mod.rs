@@ -2,22 +2,25 @@ mod bounding_box;
 mod boxobject;
 mod hittable;
 mod hittablelist;
-mod rotatey;
+mod identified;
 mod sphere;
-mod translate;
+mod transform;
+mod triangle;
+mod triangle_mesh;
 mod xyrect;
 mod xzrect;
 mod yzrect;
 
-pub(crate) use bounding_box::BVHNode;
 pub use bounding_box::AABB;
+pub(crate) use bounding_box::{take_nodes_visited, BVHNode};
 pub use hittable::*;
 pub use sphere::*;
 //Export HittableList as world, since it is just a collection of hittable objects.
 pub use boxobject::Box;
 pub use hittablelist::HittableList as World;
-pub use rotatey::RotateY;
-pub use translate::Translate;
+pub use transform::Transform;
+pub use triangle::Triangle;
+pub use triangle_mesh::TriangleMesh;
 pub use xyrect::XYRect;
 pub use xzrect::XZRect;
 pub use yzrect::YZRect;
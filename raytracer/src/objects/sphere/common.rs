@@ -45,6 +45,8 @@ pub(in crate::objects::sphere) fn hit(
         mat: material.clone(),
         u,
         v,
+        object_id: 0,
+        color: None,
     };
     hit_rec.set_face_normal(r, &outward_normal);
 
@@ -101,7 +103,7 @@ pub(in crate::objects::sphere) fn hit(
 ///     θ = cos^-1(-y)
 /// ```
 #[inline]
-fn get_sphere_uv(p: &Point) -> (f64, f64) {
+pub(in crate::objects::sphere) fn get_sphere_uv(p: &Point) -> (f64, f64) {
     // p: a given point on the sphere of radius one, centered at the origin.
     // u: returned value [0,1] of angle around the Y axis from X=-1.
     // v: returned value [0,1] of angle from Y=-1 to Y=+1.
@@ -1,6 +1,9 @@
 use super::common;
 use crate::materials::{Dielectric, Material};
 use crate::objects::{HitRecord, Hittable, AABB};
+use crate::samplers::sample_2d;
+use crate::utils::{INFINITY, PI};
+use crate::vec3::Onb;
 use crate::{Point, Ray, Vec3};
 use embed_doc_image::embed_doc_image;
 use std::sync::Arc;
@@ -73,6 +76,66 @@ impl Hittable for Sphere {
             self.center + radius_dir,
         ))
     }
+
+    fn is_light(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    fn pdf_value(&self, origin: &Point, direction: &Vec3) -> f64 {
+        if self
+            .hit(&Ray::new(origin, direction), 0.001, INFINITY)
+            .is_none()
+        {
+            return 0.0;
+        }
+
+        match self.cos_theta_max(origin) {
+            Some(cos_theta_max) => 1.0 / (2.0 * PI * (1.0 - cos_theta_max)),
+            None => 1.0 / (4.0 * PI),
+        }
+    }
+
+    /// # Sampling a Sphere
+    /// From a point outside of the sphere, the sphere covers a cone of directions around the one
+    /// towards its center. Picking a direction uniformly within the cone, rather than a point on
+    /// the sphere, never wastes a sample on the far side of the sphere. The cone covers a solid angle
+    /// of 2π(1 - cos θ<sub>max</sub>), where θ<sub>max</sub> is the angle between the axis of the
+    /// cone and its edge, and like for the unit sphere, the cosine of the angle to the axis is picked
+    /// uniformly between cos θ<sub>max</sub> and 1.
+    ///
+    /// From a point inside of the sphere, every direction hits it, so the direction is picked
+    /// uniformly over all of them.
+    fn random(&self, origin: &Point) -> Vec3 {
+        let cos_theta_max = match self.cos_theta_max(origin) {
+            Some(cos_theta_max) => cos_theta_max,
+            None => return Vec3::random_unit_vector(),
+        };
+
+        let (u, v) = sample_2d();
+        let z = 1.0 + v * (cos_theta_max - 1.0);
+        let r = f64::sqrt(f64::max(0.0, 1.0 - z * z));
+        let phi = 2.0 * PI * u;
+
+        Onb::new(&(self.center - *origin)).local(&Vec3::new(r * phi.cos(), r * phi.sin(), z))
+    }
+
+    fn sample_surface(&self) -> Option<(HitRecord, f64)> {
+        let normal = Vec3::random_unit_vector();
+        let (u, v) = common::get_sphere_uv(&normal);
+        let rec = HitRecord {
+            p: self.center + self.radius * normal,
+            normal,
+            mat: self.material.clone(),
+            t: 0.0,
+            u,
+            v,
+            front_face: true,
+            object_id: 0,
+            color: None,
+        };
+
+        Some((rec, 1.0 / (4.0 * PI * self.radius * self.radius)))
+    }
 }
 
 impl Sphere {
@@ -83,6 +146,15 @@ impl Sphere {
             material,
         }
     }
+
+    /// Returns the cosine of the angle between the direction from the `origin` towards the center of
+    /// the sphere and the edge of the sphere, or none if the origin is inside of the sphere.
+    fn cos_theta_max(&self, origin: &Point) -> Option<f64> {
+        let distance_squared = (self.center - *origin).length_squared();
+        let radius_squared = self.radius * self.radius;
+        (distance_squared > radius_squared)
+            .then(|| f64::sqrt(1.0 - radius_squared / distance_squared))
+    }
 }
 
 impl Default for Sphere {
@@ -0,0 +1,291 @@
+use crate::objects::{HitRecord, Hittable, AABB};
+use crate::{Matrix, Point, Ray, Vec3};
+use std::sync::Arc;
+
+/// # Instance Transformation
+/// A [`Matrix`] can move, rotate around any axis, scale unevenly and shear an object all at once.
+/// Instead of moving the object, the ray is moved into the space of the object by the inverse of
+/// the matrix, which is computed once up front. The direction of the ray isn't made unit length again, so that the distance *t* along it
+/// to the hit point is the same in both spaces.
+///
+/// ## Normals
+/// The point hit is moved back into the world by the matrix, but the normal can't be, since the
+/// normal of a stretched surface isn't the stretched normal. A normal **n** is perpendicular to
+/// every direction **v** along the surface, and so must the transformed normal be to **Av**, which
+/// holds for the *inverse transpose* of the matrix **A**,
+///
+/// ```math
+///     ((A^{-1})^T n) · (Av) = n^T A^{-1} A v = n · v = 0
+/// ```
+///
+/// For rotations the inverse transpose is the rotation itself, so it is only different for scaling
+/// and shearing. Which side of the surface the ray hit doesn't change either, since the sign of the
+/// dot product of the ray direction and the normal is kept, as shown above.
+///
+/// ## Bounding Box
+/// The bounding box of the transformed object is that of the eight transformed corners of the box
+/// of the object. Rather than transforming each of them, every coordinate of a transformed corner
+/// is a sum of one term for each coordinate of the corner, so the smallest and the largest of it
+/// are found by picking the smaller and the larger term for each, as James Arvo does.
+///
+/// ## Lights
+/// Scaling and shearing stretch the surface, and the directions towards it, unevenly, so the
+/// densities of picking points and directions on the object change by how much they are stretched.
+/// A patch of surface with the unit normal **n** grows by
+///
+/// ```math
+///     |det(A)| . |(A^{-1})^T n|
+/// ```
+///
+/// and a patch of directions around the unit direction **d** by *|det(A)| / |Ad|<sup>3</sup>*.
+pub struct Transform {
+    obj: Arc<dyn Hittable>,
+    matrix: Matrix,
+    inverse: Matrix,
+    // The inverse transpose of the matrix, which transforms the normals.
+    normal_matrix: Matrix,
+    // How much the matrix scales volumes by.
+    determinant: f64,
+}
+
+impl Hittable for Transform {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let object_ray = Ray::new_with_time(
+            &self.inverse.transform_point(&r.origin()),
+            &self.inverse.transform_vector(&r.direction()),
+            r.time(),
+        );
+
+        self.obj.hit(&object_ray, t_min, t_max).map(|mut hit_rec| {
+            hit_rec.p = self.matrix.transform_point(&hit_rec.p);
+            // The normal already faces the ray, and keeps facing it.
+            hit_rec.normal = self.normal_to_world(&hit_rec.normal);
+            hit_rec
+        })
+    }
+
+    fn bounding_box(&self, time0: f64, time1: f64) -> Option<AABB> {
+        self.obj.bounding_box(time0, time1).map(|obj_box| {
+            let mut min = Point::new(
+                self.matrix.get(0, 3),
+                self.matrix.get(1, 3),
+                self.matrix.get(2, 3),
+            );
+            let mut max = min;
+
+            for i in 0..3 {
+                for j in 0..3 {
+                    let a = self.matrix.get(i, j) * obj_box.min()[j];
+                    let b = self.matrix.get(i, j) * obj_box.max()[j];
+                    min[i] += a.min(b);
+                    max[i] += a.max(b);
+                }
+            }
+
+            AABB::new(min, max)
+        })
+    }
+
+    fn is_light(&self) -> bool {
+        self.obj.is_light()
+    }
+
+    fn pdf_value(&self, origin: &Point, direction: &Vec3) -> f64 {
+        let object_direction = self.inverse.transform_vector(direction);
+        let pdf = self
+            .obj
+            .pdf_value(&self.inverse.transform_point(origin), &object_direction);
+        if pdf == 0.0 {
+            return 0.0;
+        }
+
+        let stretch = self
+            .matrix
+            .transform_vector(&object_direction.unit_vector())
+            .length();
+        pdf * stretch.powi(3) / self.determinant.abs()
+    }
+
+    fn random(&self, origin: &Point) -> Vec3 {
+        self.matrix
+            .transform_vector(&self.obj.random(&self.inverse.transform_point(origin)))
+    }
+
+    fn sample_surface(&self) -> Option<(HitRecord, f64)> {
+        self.obj.sample_surface().map(|(mut rec, pdf)| {
+            let stretch =
+                self.determinant.abs() * self.normal_matrix.transform_vector(&rec.normal).length();
+            rec.p = self.matrix.transform_point(&rec.p);
+            rec.normal = self.normal_to_world(&rec.normal);
+            (rec, pdf / stretch)
+        })
+    }
+}
+
+impl Transform {
+    /// Returns the object transformed by the `matrix`, or an error if the matrix squashes it flat,
+    /// so that it can't be undone.
+    pub fn new(obj: Arc<dyn Hittable>, matrix: Matrix) -> Result<Transform, String> {
+        let inverse = matrix
+            .inverse()
+            .ok_or_else(|| "The transformation matrix can't be inverted.".to_string())?;
+        Ok(Transform::with_inverse(obj, matrix, inverse))
+    }
+
+    /// Returns the object moved by the `offset`.
+    pub fn translate(obj: Arc<dyn Hittable>, offset: Vec3) -> Transform {
+        Transform::with_inverse(
+            obj,
+            Matrix::translation(&offset),
+            Matrix::translation(&-offset),
+        )
+    }
+
+    /// Returns the object rotated around the y axis by `angle_degrees`, counterclockwise when
+    /// looking down from above.
+    pub fn rotate_y(obj: Arc<dyn Hittable>, angle_degrees: f64) -> Transform {
+        let axis = Vec3::new(0.0, 1.0, 0.0);
+        Transform::with_inverse(
+            obj,
+            Matrix::rotation(&axis, angle_degrees),
+            Matrix::rotation(&axis, -angle_degrees),
+        )
+    }
+
+    fn with_inverse(obj: Arc<dyn Hittable>, matrix: Matrix, inverse: Matrix) -> Transform {
+        let columns = [
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+        ]
+        .map(|axis| matrix.transform_vector(&axis));
+
+        Transform {
+            obj,
+            matrix,
+            inverse,
+            normal_matrix: inverse.transpose(),
+            determinant: columns[0].cross(&columns[1]).dot(&columns[2]),
+        }
+    }
+
+    fn normal_to_world(&self, normal: &Vec3) -> Vec3 {
+        self.normal_matrix.transform_vector(normal).unit_vector()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::{LambertianMaterial, Material};
+    use crate::objects::{Box, Triangle};
+    use crate::utils::INFINITY;
+    use crate::Color;
+
+    fn material() -> Arc<dyn Material> {
+        Arc::new(LambertianMaterial::new(Color::new(0.5, 0.5, 0.5)))
+    }
+
+    /// A matrix which scales unevenly and shears, so that the normals aren't simply transformed
+    /// like the directions.
+    fn stretch() -> Matrix {
+        Matrix::translation(&Vec3::new(0.5, 1.0, -1.0))
+            * Matrix::scaling(&Vec3::new(3.0, 1.0, 0.5))
+            * Matrix::shear(0.8, 0.0, 0.0, 0.0, 0.4, -0.6)
+    }
+
+    #[test]
+    fn rejects_singular_matrices() {
+        let matrix = Matrix::scaling(&Vec3::new(1.0, 1.0, 0.0));
+        let triangle = Triangle::new(
+            Point::new(0.0, 0.0, 0.0),
+            Point::new(1.0, 0.0, 0.0),
+            Point::new(0.0, 1.0, 0.0),
+            material(),
+        );
+        assert!(Transform::new(Arc::new(triangle), matrix).is_err());
+    }
+
+    #[test]
+    fn keeps_the_normals_perpendicular_to_the_surface() {
+        let corners = [
+            Point::new(0.0, 0.0, 0.0),
+            Point::new(1.0, 0.0, 1.0),
+            Point::new(0.0, 1.0, 1.0),
+        ];
+        let triangle = Triangle::new(corners[0], corners[1], corners[2], material());
+        let matrix = stretch();
+        let transform = Transform::new(Arc::new(triangle), matrix).unwrap();
+
+        let world = corners.map(|corner| matrix.transform_point(&corner));
+        let target = (world[0] + world[1] + world[2]) / 3.0;
+        let origin = target + Vec3::new(0.3, -0.2, 4.0);
+        let r = Ray::new(&origin, &(target - origin));
+        let rec = transform.hit(&r, 0.001, INFINITY).unwrap();
+
+        assert!(
+            (rec.p - target).length() < 1e-9,
+            "{:?} is not {target:?}",
+            rec.p
+        );
+        assert!((rec.normal.length() - 1.0).abs() < 1e-12);
+        for edge in [world[1] - world[0], world[2] - world[0]] {
+            assert!(rec.normal.dot(&edge).abs() < 1e-12, "{:?}", rec.normal);
+        }
+        // The normal faces the ray, as the normal of the triangle did.
+        assert!(rec.normal.dot(&r.direction()) < 0.0);
+    }
+
+    #[test]
+    fn rotates_and_then_translates() {
+        let (min, max) = (Point::new(0.0, 0.0, 0.0), Point::new(1.0, 2.0, 3.0));
+        let rotated = Transform::rotate_y(Arc::new(Box::new(min, max, material())), 90.0);
+        let moved = Transform::translate(Arc::new(rotated), Vec3::new(10.0, 0.0, 0.0));
+
+        // A quarter turn takes the x axis to -z and the z axis to x.
+        let bbox = moved.bounding_box(0.0, 1.0).unwrap();
+        assert!((bbox.min() - Point::new(10.0, 0.0, -1.0)).length() < 1e-12);
+        assert!((bbox.max() - Point::new(13.0, 2.0, 0.0)).length() < 1e-12);
+
+        let r = Ray::new(&Point::new(11.5, 1.0, 5.0), &Vec3::new(0.0, 0.0, -1.0));
+        let rec = moved.hit(&r, 0.001, INFINITY).unwrap();
+        assert!((rec.p - Point::new(11.5, 1.0, 0.0)).length() < 1e-12);
+        assert!((rec.normal - Vec3::new(0.0, 0.0, 1.0)).length() < 1e-12);
+    }
+
+    #[test]
+    fn bounds_the_transformed_corners() {
+        let (min, max) = (Point::new(-1.0, 0.0, 2.0), Point::new(2.0, 0.5, 3.0));
+        let matrix = Matrix::rotation(&Vec3::new(1.0, 1.0, 0.0), 35.0) * stretch();
+        let transform = Transform::new(Arc::new(Box::new(min, max, material())), matrix).unwrap();
+        let bbox = transform.bounding_box(0.0, 1.0).unwrap();
+
+        let mut expected_min = Point::new(INFINITY, INFINITY, INFINITY);
+        let mut expected_max = -expected_min;
+        for corner in 0..8 {
+            let pick = |axis: usize| {
+                if corner & (1 << axis) == 0 {
+                    min[axis]
+                } else {
+                    max[axis]
+                }
+            };
+            let p = matrix.transform_point(&Point::new(pick(0), pick(1), pick(2)));
+            for a in 0..3 {
+                expected_min[a] = expected_min[a].min(p[a]);
+                expected_max[a] = expected_max[a].max(p[a]);
+            }
+        }
+
+        assert!(
+            (bbox.min() - expected_min).length() < 1e-12,
+            "{:?}",
+            bbox.min()
+        );
+        assert!(
+            (bbox.max() - expected_max).length() < 1e-12,
+            "{:?}",
+            bbox.max()
+        );
+    }
+}
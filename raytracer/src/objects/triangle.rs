@@ -0,0 +1,405 @@
+use super::{solid_angle_pdf, HitRecord, Hittable, AABB};
+use crate::materials::Material;
+use crate::samplers::sample_2d;
+use crate::utils::INFINITY;
+use crate::{Point, Ray, Vec3};
+use std::sync::Arc;
+
+/// The smallest width of the bounding box of a triangle along each axis, so that the box of a
+/// triangle lying in an axis-aligned plane isn't infinitely thin.
+const MIN_BOX_WIDTH: f64 = 0.0001;
+
+/// # Triangles
+/// Triangles are what most models are made of: any surface can be approximated by enough of them,
+/// and they are simple to intersect. A triangle is given by its three corners **P<sub>0</sub>**,
+/// **P<sub>1</sub>** and **P<sub>2</sub>**, and every point of it is a weighted average of them,
+///
+/// ```math
+///     P(b_1, b_2) = (1 - b_1 - b_2)P_0 + b_1P_1 + b_2P_2
+/// ```
+///
+/// where the *barycentric coordinates* **b<sub>1</sub>** and **b<sub>2</sub>** are positive, and
+/// add up to at most one.
+///
+/// ## Möller–Trumbore Intersection
+/// The ray **A** + *t***b** hits the triangle where it equals such a point, which with the edges
+/// **E<sub>1</sub> = P<sub>1</sub> - P<sub>0</sub>** and **E<sub>2</sub> = P<sub>2</sub> -
+/// P<sub>0</sub>** is a system of three linear equations in *t*, **b<sub>1</sub>** and
+/// **b<sub>2</sub>**:
+///
+/// ```math
+///     -t\mathbf{b} + b_1E_1 + b_2E_2 = A - P_0
+/// ```
+///
+/// Möller and Trumbore solve it with Cramer's rule, writing the determinants as scalar triple
+/// products, so that the cross products can be shared between them. If the determinant is zero, the
+/// ray is parallel to the triangle and misses it. The ray also misses it if either of the
+/// barycentric coordinates is negative, or they add up to more than one, which is checked as soon
+/// as each of them is known, before computing *t*.
+///
+/// ## Normals and Texture Coordinates
+/// The texture coordinates are the barycentric coordinates, unless the corners have their own,
+/// which are then blended with the barycentric coordinates. The same goes for the normals: a mesh
+/// approximating a curved surface looks faceted with the normal of the flat triangle, so the
+/// normals of the curved surface at the corners can be given, and blended across the triangle
+/// instead. The outward side of the triangle is the one its corners go around counterclockwise on.
+pub struct Triangle {
+    vertices: [Point; 3],
+    normals: Option<[Vec3; 3]>,
+    uvs: Option<[(f64, f64); 3]>,
+    material: Arc<dyn Material>,
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let (t, b1, b2) = intersect(r, t_min, t_max, &self.vertices)?;
+
+        Some(hit_record(
+            r,
+            t,
+            (b1, b2),
+            &self.vertices,
+            self.normals.as_ref(),
+            self.uvs.as_ref(),
+            &self.material,
+        ))
+    }
+
+    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<AABB> {
+        Some(bounds(&self.vertices))
+    }
+
+    fn is_light(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    fn pdf_value(&self, origin: &Point, direction: &Vec3) -> f64 {
+        let r = Ray::new(origin, direction);
+        match self.hit(&r, 0.001, INFINITY) {
+            Some(rec) => solid_angle_pdf(&r, &rec, area(&self.vertices)),
+            None => 0.0,
+        }
+    }
+
+    fn random(&self, origin: &Point) -> Vec3 {
+        let (b1, b2) = sample_barycentric();
+        interpolate(&self.vertices, b1, b2) - *origin
+    }
+
+    fn sample_surface(&self) -> Option<(HitRecord, f64)> {
+        Some((
+            sample_record(
+                sample_barycentric(),
+                &self.vertices,
+                self.normals.as_ref(),
+                self.uvs.as_ref(),
+                &self.material,
+            ),
+            1.0 / area(&self.vertices),
+        ))
+    }
+}
+
+impl Triangle {
+    /// Returns the triangle with the given corners, whose outward side is the one the corners go
+    /// around counterclockwise on.
+    pub fn new(v0: Point, v1: Point, v2: Point, material: Arc<dyn Material>) -> Triangle {
+        Triangle {
+            vertices: [v0, v1, v2],
+            normals: None,
+            uvs: None,
+            material,
+        }
+    }
+
+    /// Returns the triangle shaded with the given normals at its corners, blended across it.
+    pub fn with_normals(self, normals: [Vec3; 3]) -> Triangle {
+        Triangle {
+            normals: Some(normals),
+            ..self
+        }
+    }
+
+    /// Returns the triangle with the given texture coordinates at its corners, blended across it.
+    pub fn with_uvs(self, uvs: [(f64, f64); 3]) -> Triangle {
+        Triangle {
+            uvs: Some(uvs),
+            ..self
+        }
+    }
+}
+
+/// Returns the distance along the ray `r` to the point it hits the triangle with the given corners
+/// at, and the barycentric coordinates **b<sub>1</sub>** and **b<sub>2</sub>** of the point, if the
+/// ray hits it between `t_min` and `t_max`.
+pub(in crate::objects) fn intersect(
+    r: &Ray,
+    t_min: f64,
+    t_max: f64,
+    vertices: &[Point; 3],
+) -> Option<(f64, f64, f64)> {
+    let e1 = vertices[1] - vertices[0];
+    let e2 = vertices[2] - vertices[0];
+    let pvec = r.direction().cross(&e2);
+    let det = e1.dot(&pvec);
+    if det.abs() < 1e-12 {
+        return None;
+    }
+    let inv_det = 1.0 / det;
+
+    let tvec = r.origin() - vertices[0];
+    let b1 = tvec.dot(&pvec) * inv_det;
+    if !(0.0..=1.0).contains(&b1) {
+        return None;
+    }
+
+    let qvec = tvec.cross(&e1);
+    let b2 = r.direction().dot(&qvec) * inv_det;
+    if b2 < 0.0 || b1 + b2 > 1.0 {
+        return None;
+    }
+
+    let t = e2.dot(&qvec) * inv_det;
+    if t <= t_min || t >= t_max {
+        return None;
+    }
+
+    Some((t, b1, b2))
+}
+
+/// Returns the hit record of the ray `r` hitting the triangle at the distance `t`, at the point
+/// with the barycentric coordinates `b`.
+pub(in crate::objects) fn hit_record(
+    r: &Ray,
+    t: f64,
+    b: (f64, f64),
+    vertices: &[Point; 3],
+    normals: Option<&[Vec3; 3]>,
+    uvs: Option<&[(f64, f64); 3]>,
+    material: &Arc<dyn Material>,
+) -> HitRecord {
+    let (b1, b2) = b;
+    let geometric_normal = (vertices[1] - vertices[0])
+        .cross(&(vertices[2] - vertices[0]))
+        .unit_vector();
+    let front_face = r.direction().dot(&geometric_normal) < 0.0;
+    let normal = shading_normal(&geometric_normal, normals, b1, b2);
+    let (u, v) = texture_coordinates(uvs, b1, b2);
+
+    HitRecord {
+        p: r.at(t),
+        normal: if front_face { normal } else { -normal },
+        mat: material.clone(),
+        t,
+        u,
+        v,
+        front_face,
+        object_id: 0,
+        color: None,
+    }
+}
+
+/// Returns the hit record of the point with the barycentric coordinates `b` on the triangle, picked
+/// with [`sample_barycentric`], with the outward normal.
+pub(in crate::objects) fn sample_record(
+    b: (f64, f64),
+    vertices: &[Point; 3],
+    normals: Option<&[Vec3; 3]>,
+    uvs: Option<&[(f64, f64); 3]>,
+    material: &Arc<dyn Material>,
+) -> HitRecord {
+    let (b1, b2) = b;
+    let geometric_normal = (vertices[1] - vertices[0])
+        .cross(&(vertices[2] - vertices[0]))
+        .unit_vector();
+    let (u, v) = texture_coordinates(uvs, b1, b2);
+
+    HitRecord {
+        p: interpolate(vertices, b1, b2),
+        normal: shading_normal(&geometric_normal, normals, b1, b2),
+        mat: material.clone(),
+        t: 0.0,
+        u,
+        v,
+        front_face: true,
+        object_id: 0,
+        color: None,
+    }
+}
+
+/// Returns the bounding box of the triangle with the given corners, padded along the axes it is
+/// flat on.
+pub(in crate::objects) fn bounds(vertices: &[Point; 3]) -> AABB {
+    let mut min = vertices[0];
+    let mut max = vertices[0];
+    for vertex in &vertices[1..] {
+        for a in 0..3 {
+            min[a] = min[a].min(vertex[a]);
+            max[a] = max[a].max(vertex[a]);
+        }
+    }
+    for a in 0..3 {
+        if max[a] - min[a] < MIN_BOX_WIDTH {
+            min[a] -= MIN_BOX_WIDTH / 2.0;
+            max[a] += MIN_BOX_WIDTH / 2.0;
+        }
+    }
+
+    AABB::new(min, max)
+}
+
+/// Returns the area of the triangle with the given corners.
+pub(in crate::objects) fn area(vertices: &[Point; 3]) -> f64 {
+    0.5 * (vertices[1] - vertices[0])
+        .cross(&(vertices[2] - vertices[0]))
+        .length()
+}
+
+/// Returns the barycentric coordinates of a point picked uniformly on a triangle.
+pub(in crate::objects) fn sample_barycentric() -> (f64, f64) {
+    // Folding the unit square in half along its diagonal would also work, but taking the square
+    // root keeps the points picked by nearby samples close to each other.
+    let (u, v) = sample_2d();
+    let su = u.sqrt();
+    (su * (1.0 - v), su * v)
+}
+
+/// Returns the point of the triangle with the given corners at the barycentric coordinates.
+pub(in crate::objects) fn interpolate(vertices: &[Point; 3], b1: f64, b2: f64) -> Point {
+    (1.0 - b1 - b2) * vertices[0] + b1 * vertices[1] + b2 * vertices[2]
+}
+
+/// Returns the normal blended from the `normals` at the corners, turned to the same side as the
+/// normal of the flat triangle, or the latter without normals at the corners.
+fn shading_normal(geometric_normal: &Vec3, normals: Option<&[Vec3; 3]>, b1: f64, b2: f64) -> Vec3 {
+    let normal = match normals {
+        Some(normals) => interpolate(normals, b1, b2),
+        None => return *geometric_normal,
+    };
+    if normal.near_zero() {
+        return *geometric_normal;
+    }
+
+    let normal = normal.unit_vector();
+    if normal.dot(geometric_normal) < 0.0 {
+        -normal
+    } else {
+        normal
+    }
+}
+
+/// Returns the texture coordinates blended from the `uvs` at the corners, or the barycentric
+/// coordinates without them.
+fn texture_coordinates(uvs: Option<&[(f64, f64); 3]>, b1: f64, b2: f64) -> (f64, f64) {
+    match uvs {
+        Some(uvs) => {
+            let b0 = 1.0 - b1 - b2;
+            (
+                b0 * uvs[0].0 + b1 * uvs[1].0 + b2 * uvs[2].0,
+                b0 * uvs[0].1 + b1 * uvs[1].1 + b2 * uvs[2].1,
+            )
+        }
+        None => (b1, b2),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::LambertianMaterial;
+    use crate::Color;
+
+    fn triangle() -> Triangle {
+        Triangle::new(
+            Point::new(0.0, 0.0, 0.0),
+            Point::new(1.0, 0.0, 0.0),
+            Point::new(0.0, 1.0, 0.0),
+            Arc::new(LambertianMaterial::new(Color::new(0.5, 0.5, 0.5))),
+        )
+    }
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-12, "{a:?} is not {b:?}");
+    }
+
+    /// Returns the ray going straight down onto the plane of the triangle at (x, y).
+    fn ray_down(x: f64, y: f64) -> Ray {
+        Ray::new(&Point::new(x, y, 1.0), &Vec3::new(0.0, 0.0, -1.0))
+    }
+
+    #[test]
+    fn hits_inside() {
+        let rec = triangle()
+            .hit(&ray_down(0.25, 0.5), 0.001, INFINITY)
+            .unwrap();
+
+        assert_eq!(rec.t, 1.0);
+        assert_close(rec.p, Point::new(0.25, 0.5, 0.0));
+        assert_close(rec.normal, Vec3::new(0.0, 0.0, 1.0));
+        assert!(rec.front_face);
+        assert_eq!((rec.u, rec.v), (0.25, 0.5));
+    }
+
+    #[test]
+    fn misses_outside() {
+        let triangle = triangle();
+
+        for (x, y) in [(-0.1, 0.5), (0.5, -0.1), (0.6, 0.6), (2.0, 2.0)] {
+            assert!(triangle.hit(&ray_down(x, y), 0.001, INFINITY).is_none());
+        }
+    }
+
+    #[test]
+    fn hits_edges_and_corners() {
+        let triangle = triangle();
+
+        for (x, y) in [(0.5, 0.0), (0.0, 0.5), (0.5, 0.5), (0.0, 0.0), (1.0, 0.0)] {
+            let rec = triangle.hit(&ray_down(x, y), 0.001, INFINITY).unwrap();
+            assert_close(rec.p, Point::new(x, y, 0.0));
+        }
+    }
+
+    #[test]
+    fn misses_parallel_rays() {
+        let triangle = triangle();
+        let above = Ray::new(&Point::new(-1.0, 0.25, 1.0), &Vec3::new(1.0, 0.0, 0.0));
+        let within = Ray::new(&Point::new(-1.0, 0.25, 0.0), &Vec3::new(1.0, 0.0, 0.0));
+
+        assert!(triangle.hit(&above, 0.001, INFINITY).is_none());
+        assert!(triangle.hit(&within, 0.001, INFINITY).is_none());
+    }
+
+    #[test]
+    fn hits_back_face() {
+        let r = Ray::new(&Point::new(0.25, 0.25, -2.0), &Vec3::new(0.0, 0.0, 1.0));
+        let rec = triangle().hit(&r, 0.001, INFINITY).unwrap();
+
+        assert_eq!(rec.t, 2.0);
+        assert!(!rec.front_face);
+        // The normal faces the ray.
+        assert_close(rec.normal, Vec3::new(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn misses_outside_of_the_ray_interval() {
+        let triangle = triangle();
+
+        assert!(triangle.hit(&ray_down(0.25, 0.25), 0.001, 0.5).is_none());
+        assert!(triangle.hit(&ray_down(0.25, 0.25), 1.5, INFINITY).is_none());
+    }
+
+    #[test]
+    fn blends_corner_normals_and_uvs() {
+        let triangle = triangle()
+            .with_normals([
+                Vec3::new(0.0, 0.0, 1.0),
+                Vec3::new(1.0, 0.0, 1.0),
+                Vec3::new(0.0, 1.0, 1.0),
+            ])
+            .with_uvs([(0.0, 0.0), (1.0, 0.0), (1.0, 1.0)]);
+        let rec = triangle.hit(&ray_down(0.5, 0.5), 0.001, INFINITY).unwrap();
+
+        assert_close(rec.normal, Vec3::new(0.5, 0.5, 1.0).unit_vector());
+        assert_eq!((rec.u, rec.v), (1.0, 0.5));
+    }
+}
@@ -0,0 +1,462 @@
+use super::bounding_box::count_node_visit;
+use super::triangle::{
+    self, area, bounds, interpolate, intersect, sample_barycentric, sample_record,
+};
+use super::{solid_angle_pdf, HitRecord, Hittable, AABB};
+use crate::materials::Material;
+use crate::samplers::sample_1d;
+use crate::utils::INFINITY;
+use crate::{Color, Point, Ray, Vec3};
+use std::sync::Arc;
+
+/// The most triangles kept in a leaf of the BVH of a mesh.
+const MAX_LEAF_TRIANGLES: usize = 4;
+
+/// The deepest the BVH of a mesh gets. Every split halves the triangles, so this is never reached.
+const MAX_BVH_DEPTH: usize = 64;
+
+/// A node of the BVH of a mesh, with the triangles `start..end` below it. The first child of an
+/// inner node comes right after it, and `second` is the index of the other one.
+struct Node {
+    bbox: AABB,
+    start: usize,
+    end: usize,
+    second: Option<usize>,
+}
+
+/// # Triangle Meshes
+/// A model is made of thousands of triangles, most of which share their corners with their
+/// neighbours. A mesh stores each corner once, in the vertex buffers, with its position, and
+/// optionally its normal, texture coordinates and color, and each triangle as the indices of its
+/// three corners in them, in the index buffer.
+///
+/// Adding every triangle to the world as an object of its own would need an allocation per triangle
+/// for the BVH to hold it, and a copy of its corners. The mesh is added as a single object instead,
+/// which builds a BVH of its own over the triangles. The nodes are kept in a single array, and the
+/// leaves refer to runs of the index buffer, which is sorted so that the triangles below each node
+/// are next to each other. Every node splits its triangles in halves along the axis their centers
+/// spread out the most on.
+///
+/// As a light, a point is picked on the mesh by picking one of the triangles with a probability
+/// proportional to its area, and then a point on it, so that the points are picked uniformly over
+/// the whole surface.
+///
+/// The colors of the corners, like the ones scanners record, are blended at the points hit by the
+/// barycentric coordinates, and given to the material in the hit record, for a
+/// [`VertexColorTexture`](crate::textures::VertexColorTexture) to return.
+pub struct TriangleMesh {
+    positions: Vec<Point>,
+    normals: Option<Vec<Vec3>>,
+    uvs: Option<Vec<(f64, f64)>>,
+    colors: Option<Vec<Color>>,
+    indices: Vec<[usize; 3]>,
+    material: Arc<dyn Material>,
+    nodes: Vec<Node>,
+    // The areas of the triangles added up in the order of the index buffer, to pick them by area.
+    cumulative_areas: Vec<f64>,
+}
+
+impl Hittable for TriangleMesh {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let mut closest = None;
+        let mut closest_so_far = t_max;
+        let mut stack = [0; MAX_BVH_DEPTH];
+        let mut stack_len = 1;
+
+        while stack_len > 0 {
+            stack_len -= 1;
+            let index = stack[stack_len];
+            let node = &self.nodes[index];
+            count_node_visit();
+            if !node.bbox.hit(r, t_min, closest_so_far) {
+                continue;
+            }
+
+            match node.second {
+                Some(second) => {
+                    stack[stack_len] = second;
+                    stack[stack_len + 1] = index + 1;
+                    stack_len += 2;
+                }
+                None => {
+                    for index in node.start..node.end {
+                        let vertices = self.vertices(index);
+                        if let Some((t, b1, b2)) = intersect(r, t_min, closest_so_far, &vertices) {
+                            closest_so_far = t;
+                            closest = Some((index, t, b1, b2));
+                        }
+                    }
+                }
+            }
+        }
+
+        closest.map(|(index, t, b1, b2)| {
+            let mut rec = triangle::hit_record(
+                r,
+                t,
+                (b1, b2),
+                &self.vertices(index),
+                self.corner_normals(index).as_ref(),
+                self.corner_uvs(index).as_ref(),
+                &self.material,
+            );
+            rec.color = self.color(index, b1, b2);
+            rec
+        })
+    }
+
+    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<AABB> {
+        Some(self.nodes[0].bbox.clone())
+    }
+
+    fn is_light(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    /// The points are picked uniformly over the surface, so a direction counts towards every
+    /// triangle it goes through.
+    fn pdf_value(&self, origin: &Point, direction: &Vec3) -> f64 {
+        let r = Ray::new(origin, direction);
+        let total_area = self.total_area();
+        let mut pdf = 0.0;
+        let mut t_min = 0.001;
+        while let Some(rec) = self.hit(&r, t_min, INFINITY) {
+            pdf += solid_angle_pdf(&r, &rec, total_area);
+            t_min = rec.t + 0.001;
+        }
+
+        pdf
+    }
+
+    fn random(&self, origin: &Point) -> Vec3 {
+        let index = self.random_triangle();
+        let (b1, b2) = sample_barycentric();
+        interpolate(&self.vertices(index), b1, b2) - *origin
+    }
+
+    fn sample_surface(&self) -> Option<(HitRecord, f64)> {
+        let index = self.random_triangle();
+        let (b1, b2) = sample_barycentric();
+        let mut rec = sample_record(
+            (b1, b2),
+            &self.vertices(index),
+            self.corner_normals(index).as_ref(),
+            self.corner_uvs(index).as_ref(),
+            &self.material,
+        );
+        rec.color = self.color(index, b1, b2);
+
+        Some((rec, 1.0 / self.total_area()))
+    }
+}
+
+impl TriangleMesh {
+    /// Returns the mesh of the triangles given by the `indices` of their corners in the vertex
+    /// buffers, which hold the `positions` of the corners, and optionally their `normals` and
+    /// texture coordinates `uvs`. Fails if there are no triangles, if an index is past the end of
+    /// the positions, or if there aren't as many normals or texture coordinates as positions.
+    pub fn new(
+        positions: Vec<Point>,
+        normals: Option<Vec<Vec3>>,
+        uvs: Option<Vec<(f64, f64)>>,
+        indices: Vec<[usize; 3]>,
+        material: Arc<dyn Material>,
+    ) -> Result<TriangleMesh, String> {
+        if indices.is_empty() {
+            return Err("The triangle mesh has no triangles.".to_string());
+        }
+        if let Some(index) = indices.iter().flatten().find(|&&i| i >= positions.len()) {
+            return Err(format!(
+                "The vertex index {index} of the triangle mesh is out of range, it only has {} \
+                 vertices.",
+                positions.len()
+            ));
+        }
+        if normals.as_ref().is_some_and(|n| n.len() != positions.len()) {
+            return Err("The triangle mesh doesn't have a normal for every vertex.".to_string());
+        }
+        if uvs.as_ref().is_some_and(|uv| uv.len() != positions.len()) {
+            return Err(
+                "The triangle mesh doesn't have texture coordinates for every vertex.".to_string(),
+            );
+        }
+
+        let mut mesh = TriangleMesh {
+            positions,
+            normals,
+            uvs,
+            colors: None,
+            indices,
+            material,
+            nodes: vec![],
+            cumulative_areas: vec![],
+        };
+        mesh.build(0, mesh.indices.len());
+
+        let mut total_area = 0.0;
+        mesh.cumulative_areas = (0..mesh.indices.len())
+            .map(|index| {
+                total_area += area(&mesh.vertices(index));
+                total_area
+            })
+            .collect();
+
+        Ok(mesh)
+    }
+
+    /// Returns the mesh with the given `colors` of its vertices, or an error if there isn't one for
+    /// every vertex.
+    pub fn with_colors(self, colors: Vec<Color>) -> Result<TriangleMesh, String> {
+        if colors.len() != self.positions.len() {
+            return Err("The triangle mesh doesn't have a color for every vertex.".to_string());
+        }
+
+        Ok(TriangleMesh {
+            colors: Some(colors),
+            ..self
+        })
+    }
+
+    /// Adds the node splitting the triangles `start..end` and the nodes below it, and returns its
+    /// index.
+    fn build(&mut self, start: usize, end: usize) -> usize {
+        let bbox = (start + 1..end).fold(bounds(&self.vertices(start)), |bbox, index| {
+            AABB::surrounding_box(&bbox, &bounds(&self.vertices(index)))
+        });
+        let node = self.nodes.len();
+        self.nodes.push(Node {
+            bbox,
+            start,
+            end,
+            second: None,
+        });
+        if end - start <= MAX_LEAF_TRIANGLES {
+            return node;
+        }
+
+        let (min, max) = (start..end).map(|index| self.centroid(index)).fold(
+            (
+                Vec3::new(INFINITY, INFINITY, INFINITY),
+                -Vec3::new(INFINITY, INFINITY, INFINITY),
+            ),
+            |(mut min, mut max), c| {
+                for a in 0..3 {
+                    min[a] = min[a].min(c[a]);
+                    max[a] = max[a].max(c[a]);
+                }
+                (min, max)
+            },
+        );
+        let extent = max - min;
+        let axis = if extent.x() >= extent.y() && extent.x() >= extent.z() {
+            0
+        } else if extent.y() >= extent.z() {
+            1
+        } else {
+            2
+        };
+
+        let mid = start + (end - start) / 2;
+        let positions = &self.positions;
+        let centroid = |corners: &[usize; 3]| {
+            positions[corners[0]][axis] + positions[corners[1]][axis] + positions[corners[2]][axis]
+        };
+        self.indices[start..end]
+            .select_nth_unstable_by(mid - start, |a, b| centroid(a).total_cmp(&centroid(b)));
+
+        self.build(start, mid);
+        let second = self.build(mid, end);
+        self.nodes[node].second = Some(second);
+
+        node
+    }
+
+    fn vertices(&self, index: usize) -> [Point; 3] {
+        self.indices[index].map(|i| self.positions[i])
+    }
+
+    fn corner_normals(&self, index: usize) -> Option<[Vec3; 3]> {
+        self.normals
+            .as_ref()
+            .map(|normals| self.indices[index].map(|i| normals[i]))
+    }
+
+    fn corner_uvs(&self, index: usize) -> Option<[(f64, f64); 3]> {
+        self.uvs
+            .as_ref()
+            .map(|uvs| self.indices[index].map(|i| uvs[i]))
+    }
+
+    /// Returns the color of the vertices blended at the point with the barycentric coordinates `b1`
+    /// and `b2` on the triangle at `index`, if the mesh has colors.
+    fn color(&self, index: usize, b1: f64, b2: f64) -> Option<Color> {
+        self.colors
+            .as_ref()
+            .map(|colors| interpolate(&self.indices[index].map(|i| colors[i]), b1, b2))
+    }
+
+    fn centroid(&self, index: usize) -> Point {
+        let [p0, p1, p2] = self.vertices(index);
+        (p0 + p1 + p2) / 3.0
+    }
+
+    fn total_area(&self) -> f64 {
+        self.cumulative_areas[self.cumulative_areas.len() - 1]
+    }
+
+    /// Picks one of the triangles with a probability proportional to its area.
+    fn random_triangle(&self) -> usize {
+        let target = sample_1d() * self.total_area();
+        self.cumulative_areas
+            .partition_point(|&area| area <= target)
+            .min(self.indices.len() - 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::LambertianMaterial;
+    use crate::objects::Triangle;
+
+    /// The number of squares stacked up in the test mesh.
+    const SQUARES: usize = 16;
+
+    fn material() -> Arc<dyn Material> {
+        Arc::new(LambertianMaterial::new(Color::new(0.5, 0.5, 0.5)))
+    }
+
+    /// Returns the corners and the triangles of unit squares stacked up along the z axis, each one
+    /// moved a bit sideways, and listed out of order, so that the BVH has to sort them.
+    fn stacked_squares() -> (Vec<Point>, Vec<[usize; 3]>) {
+        let mut positions = vec![];
+        let mut indices = vec![];
+        for i in 0..SQUARES {
+            let z = ((i * 7) % SQUARES) as f64;
+            let offset = 0.1 * (i % 3) as f64;
+            let first = positions.len();
+            positions.extend([
+                Point::new(offset, offset, z),
+                Point::new(offset + 1.0, offset, z),
+                Point::new(offset + 1.0, offset + 1.0, z),
+                Point::new(offset, offset + 1.0, z),
+            ]);
+            indices.push([first, first + 1, first + 2]);
+            indices.push([first, first + 2, first + 3]);
+        }
+
+        (positions, indices)
+    }
+
+    fn mesh() -> TriangleMesh {
+        let (positions, indices) = stacked_squares();
+        TriangleMesh::new(positions, None, None, indices, material()).unwrap()
+    }
+
+    #[test]
+    fn hits_the_closest_triangle() {
+        let mesh = mesh();
+        let top = SQUARES as f64 - 1.0;
+
+        let down = Ray::new(&Point::new(0.5, 0.5, top + 2.0), &Vec3::new(0.0, 0.0, -1.0));
+        let rec = mesh.hit(&down, 0.001, INFINITY).unwrap();
+        assert_eq!(rec.p.z(), top);
+        assert_eq!(rec.t, 2.0);
+
+        let up = Ray::new(&Point::new(0.5, 0.5, -1.0), &Vec3::new(0.0, 0.0, 1.0));
+        let rec = mesh.hit(&up, 0.001, INFINITY).unwrap();
+        assert_eq!(rec.p.z(), 0.0);
+        assert!(!rec.front_face);
+
+        // Starting between the squares, the next one along the ray is hit.
+        let between = Ray::new(&Point::new(0.5, 0.5, 4.5), &Vec3::new(0.0, 0.0, -1.0));
+        assert_eq!(mesh.hit(&between, 0.001, INFINITY).unwrap().p.z(), 4.0);
+    }
+
+    #[test]
+    fn hits_like_the_triangles_one_by_one() {
+        let mesh = mesh();
+        let (positions, indices) = stacked_squares();
+        let triangles: Vec<Triangle> = indices
+            .iter()
+            .map(|&[a, b, c]| Triangle::new(positions[a], positions[b], positions[c], material()))
+            .collect();
+
+        let mut hits = 0;
+        for i in 0..20 {
+            for j in 0..20 {
+                let origin = Point::new(-1.0 + 0.15 * i as f64, -1.0, -2.0);
+                let direction = Vec3::new(0.1 * j as f64 - 0.5, 0.2 + 0.01 * j as f64, 1.0);
+                let r = Ray::new(&origin, &direction);
+
+                let closest = triangles
+                    .iter()
+                    .filter_map(|triangle| triangle.hit(&r, 0.001, INFINITY))
+                    .map(|rec| rec.t)
+                    .min_by(f64::total_cmp);
+                assert_eq!(mesh.hit(&r, 0.001, INFINITY).map(|rec| rec.t), closest);
+                hits += closest.is_some() as u32;
+            }
+        }
+        // Both the rays hitting the mesh and the ones missing it are checked.
+        assert!(hits > 0 && hits < 400);
+    }
+
+    #[test]
+    fn rejects_inconsistent_buffers() {
+        let (positions, mut indices) = stacked_squares();
+        let normals = Some(vec![Vec3::new(0.0, 0.0, 1.0)]);
+
+        assert!(TriangleMesh::new(vec![], None, None, vec![], material()).is_err());
+        assert!(TriangleMesh::new(
+            positions.clone(),
+            normals,
+            None,
+            indices.clone(),
+            material()
+        )
+        .is_err());
+        indices.push([0, 1, positions.len()]);
+        assert!(TriangleMesh::new(positions, None, None, indices, material()).is_err());
+    }
+
+    #[test]
+    fn blends_the_colors_of_the_vertices() {
+        let positions = vec![
+            Point::new(0.0, 0.0, 0.0),
+            Point::new(1.0, 0.0, 0.0),
+            Point::new(1.0, 1.0, 0.0),
+            Point::new(0.0, 1.0, 0.0),
+        ];
+        let uvs = vec![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)];
+        let colors = vec![
+            Color::new(1.0, 0.0, 0.0),
+            Color::new(0.0, 1.0, 0.0),
+            Color::new(0.0, 0.0, 1.0),
+            Color::new(1.0, 1.0, 1.0),
+        ];
+        let indices = vec![[0, 1, 2], [0, 2, 3]];
+        let mesh = TriangleMesh::new(positions, None, Some(uvs), indices, material()).unwrap();
+        let r = Ray::new(&Point::new(0.25, 0.5, 1.0), &Vec3::new(0.0, 0.0, -1.0));
+        assert!(mesh.hit(&r, 0.001, INFINITY).unwrap().color.is_none());
+
+        let mesh = mesh.with_colors(colors).unwrap();
+        let rec = mesh.hit(&r, 0.001, INFINITY).unwrap();
+        // The point is on the second triangle, with the barycentric coordinates a half for its
+        // first corner, and a quarter for each of the other two.
+        let expected = 0.5 * Color::new(1.0, 0.0, 0.0)
+            + 0.25 * Color::new(0.0, 0.0, 1.0)
+            + 0.25 * Color::new(1.0, 1.0, 1.0);
+        assert!((rec.color.unwrap() - expected).length() < 1e-12, "{rec:?}");
+        assert_eq!((rec.u, rec.v), (0.25, 0.5));
+
+        let (rec, _) = mesh.sample_surface().unwrap();
+        assert!(rec.color.is_some());
+    }
+
+    #[test]
+    fn rejects_a_color_missing_for_a_vertex() {
+        let colors = vec![Color::new(1.0, 1.0, 1.0); 4 * SQUARES - 1];
+        assert!(mesh().with_colors(colors).is_err());
+    }
+}
@@ -1,5 +1,6 @@
 use super::super::{HitRecord, Hittable, AABB};
 use crate::materials::{Isotropic, Material};
+use crate::samplers::sample_1d;
 use crate::utils::{random_in_unit_interval, INFINITY};
 use crate::{Color, Ray, Vec3};
 use embed_doc_image::embed_doc_image;
@@ -77,7 +78,7 @@ impl Hittable for ConstantMedium {
 
                 let ray_length = r.direction().length();
                 let distance_inside_boundary = (r2.t - r1.t) * ray_length;
-                let hit_distance = self.neg_inv_density * random_in_unit_interval().ln();
+                let hit_distance = self.neg_inv_density * (1.0 - sample_1d()).ln();
 
                 if hit_distance > distance_inside_boundary {
                     return None;
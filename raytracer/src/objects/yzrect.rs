@@ -1,5 +1,7 @@
-use super::{HitRecord, Hittable, AABB};
+use super::{solid_angle_pdf, HitRecord, Hittable, AABB};
 use crate::materials::Material;
+use crate::samplers::sample_2d;
+use crate::utils::INFINITY;
 use crate::{Point, Ray, Vec3};
 use std::sync::Arc;
 
@@ -32,6 +34,8 @@ impl Hittable for YZRect {
             p: r.at(t),
             normal: Vec3::default(),
             front_face: false,
+            object_id: 0,
+            color: None,
         };
 
         let outward_normal = Vec3::new(1.0, 0.0, 0.0);
@@ -47,6 +51,51 @@ impl Hittable for YZRect {
             Point::new(self.k + 0.0001, self.y1, self.z1),
         ))
     }
+
+    fn is_light(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    fn pdf_value(&self, origin: &Point, direction: &Vec3) -> f64 {
+        let r = Ray::new(origin, direction);
+        match self.hit(&r, 0.001, INFINITY) {
+            Some(rec) => {
+                let area = (self.y1 - self.y0) * (self.z1 - self.z0);
+                solid_angle_pdf(&r, &rec, area)
+            }
+            None => 0.0,
+        }
+    }
+
+    fn random(&self, origin: &Point) -> Vec3 {
+        let (u, v) = sample_2d();
+        Point::new(
+            self.k,
+            self.y0 + u * (self.y1 - self.y0),
+            self.z0 + v * (self.z1 - self.z0),
+        ) - *origin
+    }
+
+    fn sample_surface(&self) -> Option<(HitRecord, f64)> {
+        let (u, v) = sample_2d();
+        let rec = HitRecord {
+            p: Point::new(
+                self.k,
+                self.y0 + u * (self.y1 - self.y0),
+                self.z0 + v * (self.z1 - self.z0),
+            ),
+            normal: Vec3::new(1.0, 0.0, 0.0),
+            mat: self.material.clone(),
+            t: 0.0,
+            u,
+            v,
+            front_face: true,
+            object_id: 0,
+            color: None,
+        };
+
+        Some((rec, 1.0 / ((self.y1 - self.y0) * (self.z1 - self.z0))))
+    }
 }
 
 impl YZRect {
@@ -1,34 +1,25 @@
 use crate::{
+    aov::Aov,
+    cancellation::CancellationToken,
     configuration::ImageFormat as ConfImageFormat,
-    configuration::ImageSettings,
+    configuration::{AdaptiveSampling, ImageSettings},
+    denoiser::Denoiser,
+    framebuffer::{FrameBuffer, PixelSamples, Splats},
+    integrators::{Integrator, IntegratorType},
     objects::{BVHNode, Hittable},
-    utils,
-    utils::{clamp, random_in_unit_interval},
-    Color, Ray, Scene,
+    samplers::{finish_sample, sample_2d, start_sample, Sampler},
+    tiles::tiles,
+    tonemapping::DisplayTransform,
+    utils::{sample_seed, seed_random_generator},
+    Camera, Color, Scene,
 };
 use embed_doc_image::embed_doc_image;
-use image::{ImageFormat, RgbaImage};
+use image::error::{ImageError, ParameterError, ParameterErrorKind};
+use image::{ImageFormat, ImageResult, RgbaImage};
 use rayon::prelude::*;
-use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::Arc;
-
-/// To handle the multi-sampled color computation - rather than adding in a fractional contribution
-/// each time we accumulate more light to the color, just add the full color each iteration, and
-/// then perform a single divide at the end (by the number of samples) when writing out the color.
-#[inline]
-fn write_color(pixel: &mut [u8], pixel_color: &Color, samples_per_pixel: u32) {
-    // Divide the color by the number of samples and gamma correct for gamma = 2.0.
-    let scale = 1.0 / samples_per_pixel as f64;
-    let r = f64::sqrt(scale * pixel_color.x());
-    let g = f64::sqrt(scale * pixel_color.y());
-    let b = f64::sqrt(scale * pixel_color.z());
-
-    // Write the translated [0,255] value of each color component
-    pixel[0] = (256.0 * clamp(r, 0.0, 0.999)) as u8;
-    pixel[1] = (256.0 * clamp(g, 0.0, 0.999)) as u8;
-    pixel[2] = (256.0 * clamp(b, 0.0, 0.999)) as u8;
-    pixel[3] = 255;
-}
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 
 /// # Antialiasing
 /// When a real camera takes a picture, there are usually no jaggies along edges because the edge
@@ -44,121 +35,613 @@ fn write_color(pixel: &mut [u8], pixel_color: &Color, samples_per_pixel: u32) {
 /// And since they have to be behind a dyn, it has to be encapsulated in a Box or an Rc.
 /// The alternative is to make the callback as part of the function signature, and make it generic
 /// over the callback type.
+///
+/// The render can be stopped through the `cancel` token, in which case the pixels rendered so far
+/// are returned as a `RenderOutcome::Cancelled` result. An error is returned if the scene can't be
+/// rendered, for e.g. when one of its objects has no bounding box to build the BVH with.
+///
+/// With adaptive sampling enabled in the settings, the number of samples varies per pixel as
+/// described in [`AdaptiveSampling`], rather than being `samples_per_pixel` for all of them.
 #[embed_doc_image("pixelsamples", "doc_images/pixel_samples.jpg")]
-pub fn render<F>(settings: ImageSettings, scene: Scene, progress_callback: F)
+pub fn render<F>(
+    settings: ImageSettings,
+    scene: Scene,
+    cancel: &CancellationToken,
+    progress_callback: F,
+) -> Result<RenderOutcome, String>
 where
     F: Fn(f32) + Sync + Send,
 {
-    // World and Camera
-    let Scene {
-        world,
-        camera,
-        background_color,
-    } = scene;
-    let bvh_world = Arc::new(BVHNode::new(&world, 0.0, 0.0).unwrap());
-    let progress_counter = AtomicU64::new(0);
-
-    // Render
-    let iters: u32 = settings.width * settings.height;
-    let mut imout = RgbaImage::new(settings.width, settings.height);
-    // Since it's an rgba image, iterate in chunks of 4 (RGBA).
-    imout
-        .par_chunks_mut(4)
-        .enumerate()
-        .into_par_iter()
-        .for_each(|(i, chk)| {
-            let x = i % settings.width as usize;
-            // Starting y from the beginning results in an inverted image, so start from the bottom
-            // and work the way up.
-            let y = settings.height as usize - 1 - i / settings.width as usize;
-            let mut pixel_color = Color::new(0.0, 0.0, 0.0);
-            for _ in 0..settings.samples_per_pixel {
-                let u = (x as f64 + random_in_unit_interval()) / (settings.width - 1) as f64;
-                let v = (y as f64 + random_in_unit_interval()) / (settings.height - 1) as f64;
+    let samples_per_pixel = settings.samples_per_pixel;
+    let adaptive_sampling = settings.adaptive_sampling;
+    let mut renderer = Renderer::new(settings, scene)?;
+
+    match adaptive_sampling {
+        Some(adaptive) => Ok(renderer.render_adaptive(&adaptive, cancel, progress_callback)),
+        None => {
+            let samples = vec![samples_per_pixel; renderer.framebuffer.samples.len()];
+            renderer.sample_pixels(&samples, cancel, progress_callback);
+
+            Ok(RenderOutcome::new(
+                renderer.final_image(),
+                cancel.is_cancelled(),
+            ))
+        }
+    }
+}
+
+/// # Progressive Rendering
+/// Rather than taking all the samples for a pixel in one go, the renderer can take them in passes.
+/// The framebuffer keeps the sum of the samples and the number of samples per pixel, so each pass
+/// simply adds more samples on top of what was already gathered, and an image can be produced at
+/// any point in between. This allows showing a preview which refines over time, and stopping or
+/// resuming the render later on.
+pub struct Renderer {
+    settings: ImageSettings,
+    camera: Camera,
+    background_color: Color,
+    world: Arc<dyn Hittable>,
+    lights: Option<Arc<dyn Hittable>>,
+    framebuffer: FrameBuffer,
+    aovs: Vec<(Aov, FrameBuffer)>,
+    display: DisplayTransform,
+    sampler: Arc<dyn Sampler>,
+    integrator: Arc<dyn Integrator>,
+}
+
+impl Renderer {
+    /// Returns a renderer for the scene, or an error if the BVH of the scene can't be built, for
+    /// e.g. because one of its objects has no bounding box.
+    pub fn new(settings: ImageSettings, scene: Scene) -> Result<Renderer, String> {
+        let framebuffer = FrameBuffer::new(settings.width, settings.height);
+        Self::new_with_framebuffer(settings, scene, framebuffer)
+    }
+
+    /// Returns a renderer which continues accumulating samples into the given framebuffer, for e.g.
+    /// to resume a render which was stopped earlier.
+    pub fn new_with_framebuffer(
+        settings: ImageSettings,
+        scene: Scene,
+        framebuffer: FrameBuffer,
+    ) -> Result<Renderer, String> {
+        if framebuffer.width() != settings.width || framebuffer.height() != settings.height {
+            let err = format!(
+                "Framebuffer size {}x{} doesn't match the image size {}x{}",
+                framebuffer.width(),
+                framebuffer.height(),
+                settings.width,
+                settings.height
+            );
+            return Err(err);
+        }
+
+        if settings.integrator == IntegratorType::BvhHeatmap && !cfg!(feature = "bvh-heatmap") {
+            return Err(
+                "The BVH heatmap needs the raytracer to be built with the bvh-heatmap feature."
+                    .to_string(),
+            );
+        }
+
+        // World and Camera
+        let Scene {
+            world,
+            camera,
+            background_color,
+        } = scene;
+        // The axes the BVH is split along are chosen at random, so seed them too.
+        seed_random_generator(settings.seed);
+        let lights = world.lights();
+        let lights = (!lights.is_empty()).then(|| Arc::new(lights) as Arc<dyn Hittable>);
+        let world = Arc::new(BVHNode::new(&world.with_object_ids(), 0.0, 0.0)?);
+
+        let display = DisplayTransform::new(settings.tone_mapping, settings.exposure);
+        // With adaptive sampling, all the pixels take at least the minimum number of samples.
+        let samples_per_pixel = settings
+            .adaptive_sampling
+            .map_or(settings.samples_per_pixel, |adaptive| adaptive.min_samples);
+        let sampler = settings.sampler.create(samples_per_pixel, settings.seed);
+        let integrator = settings
+            .integrator
+            .create(&settings, world.as_ref(), lights.as_deref());
+        let mut aov_list = settings.aovs.clone();
+        if settings.denoiser.is_some() {
+            for guide in [Aov::Albedo, Aov::Normal, Aov::Depth] {
+                if !aov_list.contains(&guide) {
+                    aov_list.push(guide);
+                }
+            }
+        }
+        let aovs = aov_list
+            .into_iter()
+            .map(|aov| (aov, FrameBuffer::new(settings.width, settings.height)))
+            .collect();
+
+        Ok(Renderer {
+            sampler,
+            integrator,
+            aovs,
+            display,
+            settings,
+            camera,
+            background_color,
+            world,
+            lights,
+            framebuffer,
+        })
+    }
+
+    pub fn framebuffer(&self) -> &FrameBuffer {
+        &self.framebuffer
+    }
+
+    /// Returns the number of samples per pixel gathered so far.
+    pub fn samples_taken(&self) -> u32 {
+        self.framebuffer.samples.iter().copied().min().unwrap_or(0)
+    }
+
+    /// Returns the image for the samples gathered so far. It isn't denoised, which keeps it cheap
+    /// enough to preview the render after every pass.
+    pub fn image(&self) -> RenderedImage {
+        RenderedImage::new(self.framebuffer.clone(), self.aovs.clone(), self.display)
+    }
+
+    /// Returns the image for the samples gathered so far, denoised if a denoiser is configured.
+    pub fn final_image(&self) -> RenderedImage {
+        let image = self.image();
+        match &self.settings.denoiser {
+            Some(denoiser) => image.denoise(denoiser),
+            None => image,
+        }
+    }
+
+    /// Takes `samples` more samples for every pixel and adds them to the framebuffer. The image is
+    /// rendered tile by tile, and the progress callback is invoked as each tile completes. If the
+    /// render is cancelled, the tiles which were not started yet are left untouched.
+    pub fn render_pass<F>(
+        &mut self,
+        samples: u32,
+        cancel: &CancellationToken,
+        progress_callback: F,
+    ) -> RenderOutcome
+    where
+        F: Fn(f32) + Sync + Send,
+    {
+        let samples = vec![samples; self.framebuffer.samples.len()];
+        self.sample_pixels(&samples, cancel, progress_callback);
+
+        RenderOutcome::new(self.image(), cancel.is_cancelled())
+    }
+
+    /// Renders the image with adaptive sampling, as described in [`AdaptiveSampling`]. The progress
+    /// callback reports the progress through the passes which may be needed to reach the maximum
+    /// number of samples, so it can jump ahead when all the pixels converge early. The image is
+    /// denoised at the end if a denoiser is configured.
+    pub fn render_adaptive<F>(
+        &mut self,
+        adaptive: &AdaptiveSampling,
+        cancel: &CancellationToken,
+        progress_callback: F,
+    ) -> RenderOutcome
+    where
+        F: Fn(f32) + Sync + Send,
+    {
+        let (width, height) = (self.settings.width, self.settings.height);
+        // The first pass takes the minimum, then every pass doubles the number of samples.
+        let passes = 1
+            + (adaptive.max_samples as f64 / adaptive.min_samples as f64)
+                .log2()
+                .ceil() as u32;
+
+        for pass in 0..passes {
+            let samples: Vec<u32> = (0..height)
+                .flat_map(|y| (0..width).map(move |x| (x, y)))
+                .map(|(x, y)| {
+                    let taken = self.framebuffer.sample_count(x, y);
+                    if taken < adaptive.min_samples {
+                        adaptive.min_samples - taken
+                    } else if taken < adaptive.max_samples
+                        && relative_error(&self.framebuffer, x, y) > adaptive.noise_threshold
+                    {
+                        taken.min(adaptive.max_samples - taken)
+                    } else {
+                        0
+                    }
+                })
+                .collect();
+            if samples.iter().all(|&s| s == 0) {
+                break;
+            }
+
+            self.sample_pixels(&samples, cancel, |i: f32| {
+                progress_callback((pass as f32 + i / 100.0) / passes as f32 * 100.0)
+            });
+            if cancel.is_cancelled() {
+                break;
+            }
+        }
+
+        RenderOutcome::new(self.final_image(), cancel.is_cancelled())
+    }
+
+    /// Takes `samples[i]` more samples for the i-th pixel (in image order) and adds them to the
+    /// framebuffer, tile by tile. The progress callback is invoked as each tile completes.
+    fn sample_pixels<F>(
+        &mut self,
+        samples: &[u32],
+        cancel: &CancellationToken,
+        progress_callback: F,
+    ) where
+        F: Fn(f32) + Sync + Send,
+    {
+        let Renderer {
+            settings,
+            camera,
+            background_color,
+            world,
+            lights,
+            framebuffer,
+            aovs,
+            sampler,
+            integrator,
+            ..
+        } = self;
+        let tiles = tiles(
+            settings.width,
+            settings.height,
+            settings.tile_size,
+            settings.tile_order,
+        );
+        let next_tile = AtomicUsize::new(0);
+        let progress_counter = AtomicU64::new(0);
+        let iters = samples.iter().map(|&s| s as u64).sum::<u64>().max(1);
+        // The samples of this pass continue from the ones already gathered for each pixel.
+        let first_samples = framebuffer.samples.clone();
+        let aov_list: Vec<Aov> = aovs.iter().map(|(aov, _)| *aov).collect();
+        // Averaging object IDs along the edges of objects would give IDs of objects which are not
+        // there, so all the samples of a pixel count the ID which its first sample hit.
+        let object_ids = aovs
+            .iter()
+            .find(|(aov, _)| *aov == Aov::ObjectId)
+            .map(|(_, buffer)| buffer.pixels());
+        let buffers = Mutex::new((framebuffer, aovs));
+        // The splats of the tiles, kept until all of them are rendered, to be added in the order
+        // of the tiles rather than in the order they happen to complete in.
+        let tile_splats: Mutex<Vec<Option<Vec<Splats>>>> =
+            Mutex::new((0..tiles.len()).map(|_| None).collect());
+
+        // The radiance goes to the first of the `splats`, and the output variables to the others.
+        let sample_pixel = |x: u32, row: u32, splats: &mut [Splats]| {
+            let pixel = (row * settings.width + x) as usize;
+            let samples = samples[pixel];
+            // Rows are counted from the top of the image, whereas the camera's v coordinate goes up
+            // from the bottom, so flip the row to avoid an inverted image.
+            let y = settings.height - 1 - row;
+            let mut pixel_samples = PixelSamples::default();
+            let mut aov_samples = vec![PixelSamples::default(); aov_list.len()];
+            let mut object_id = object_ids
+                .as_ref()
+                .filter(|_| first_samples[pixel] > 0)
+                .map(|ids| ids[pixel]);
+            for sample in first_samples[pixel]..first_samples[pixel] + samples {
+                seed_random_generator(sample_seed(settings.seed, pixel as u64, sample as u64));
+                start_sample(sampler, x, row, sample);
+                let (du, dv) = sample_2d();
+                let u = (x as f64 + du) / (settings.width - 1) as f64;
+                let v = (y as f64 + dv) / (settings.height - 1) as f64;
                 let r = camera.get_ray(u, v);
-                pixel_color +=
-                    ray_color(&r, &background_color, bvh_world.clone(), settings.max_depth);
+                let mut first_hit = None;
+                let color = integrator.ray_color(
+                    &r,
+                    world.as_ref(),
+                    lights.as_deref(),
+                    background_color,
+                    &mut first_hit,
+                );
+                pixel_samples.add(color);
+                // The position of the sample in the image, where rows go down from the top.
+                let (sx, sy) = (x as f64 + du, row as f64 + 1.0 - dv);
+                splats[0].add(sx, sy, color, &settings.filter);
+
+                for ((value, aov), splats) in aov_samples
+                    .iter_mut()
+                    .zip(aov_list.iter())
+                    .zip(splats[1..].iter_mut())
+                {
+                    let aov_value = aov.value(&r, first_hit.as_ref(), background_color);
+                    match aov {
+                        Aov::ObjectId => value.add(*object_id.get_or_insert(aov_value)),
+                        _ => {
+                            value.add(aov_value);
+                            splats.add(sx, sy, aov_value, &settings.filter);
+                        }
+                    }
+                }
             }
+            finish_sample();
+
+            (pixel_samples, aov_samples)
+        };
+
+        // Render
+        // Each worker picks up the next tile in order until all the tiles are rendered, so that the
+        // tiles are started in the order given by the scheduler.
+        (0..rayon::current_num_threads())
+            .into_par_iter()
+            .for_each(|_| loop {
+                let index = next_tile.fetch_add(1, Ordering::SeqCst);
+                if index >= tiles.len() || cancel.is_cancelled() {
+                    break;
+                }
+
+                let tile = &tiles[index];
+                let mut splats: Vec<Splats> = (0..=aov_list.len())
+                    .map(|_| {
+                        Splats::new(
+                            tile,
+                            settings.filter.radius,
+                            settings.width,
+                            settings.height,
+                        )
+                    })
+                    .collect();
+                let (pixels, aov_values): (Vec<PixelSamples>, Vec<Vec<PixelSamples>>) = tile
+                    .pixels()
+                    .map(|(x, y)| sample_pixel(x, y, &mut splats))
+                    .unzip();
+
+                let mut buffers = buffers.lock().unwrap();
+                let (framebuffer, aovs) = &mut *buffers;
+                framebuffer.add_tile_statistics(tile, &pixels);
+                for (i, (aov, aov_buffer)) in aovs.iter_mut().enumerate() {
+                    let values: Vec<PixelSamples> =
+                        aov_values.iter().map(|values| values[i]).collect();
+                    // Object IDs can't be blended, so they stay in the pixel they were seen in.
+                    match aov {
+                        Aov::ObjectId => aov_buffer.add_tile_samples(tile, &values),
+                        _ => aov_buffer.add_tile_statistics(tile, &values),
+                    }
+                }
+                drop(buffers);
+                tile_splats.lock().unwrap()[index] = Some(splats);
+
+                let taken = pixels.iter().map(|p| p.count as u64).sum::<u64>();
+                let done = progress_counter.fetch_add(taken, Ordering::SeqCst) + taken;
+                progress_callback(done as f32 / iters as f32 * 100.0);
+            });
 
-            let prev_value = progress_counter.fetch_add(1, Ordering::SeqCst);
-            // Call the callback only on the boundaries of 10 pixels to avoid insignificant updates.
-            if prev_value % 10 == 0 || iters < 10 {
-                progress_callback((prev_value + 1) as f32 / iters as f32 * 100.0);
+        let (framebuffer, aovs) = buffers.into_inner().unwrap();
+        for splats in tile_splats.into_inner().unwrap().iter().flatten() {
+            framebuffer.add_splats(&splats[0]);
+            for ((aov, aov_buffer), splats) in aovs.iter_mut().zip(&splats[1..]) {
+                if *aov != Aov::ObjectId {
+                    aov_buffer.add_splats(splats);
+                }
             }
+        }
+    }
 
-            write_color(chk, &pixel_color, settings.samples_per_pixel);
-        });
+    /// Renders the image in passes, doubling the number of samples per pixel gathered with every
+    /// pass (1, 2, 4, ...) until `samples_per_pixel` samples have been gathered. After every pass
+    /// `on_pass` receives the image so far, and can return `false` to stop rendering; calling this
+    /// function again resumes from where it stopped. The progress callback reports the progress
+    /// towards the total number of samples.
+    ///
+    /// Each pass brings every pixel up to the same number of samples, so that the pixels which are
+    /// ahead of the others, for e.g. after a pass was cancelled halfway through, aren't sampled more
+    /// than the rest.
+    pub fn render_progressive<P, F>(
+        &mut self,
+        cancel: &CancellationToken,
+        mut on_pass: P,
+        progress_callback: F,
+    ) -> RenderOutcome
+    where
+        P: FnMut(&RenderedImage) -> bool,
+        F: Fn(f32) + Sync + Send,
+    {
+        let total = self.settings.samples_per_pixel;
 
-    imout
-        .save_with_format(
-            std::path::Path::new(&settings.path),
-            get_format(settings.format),
-        )
-        .expect("Unable to save image in specified format");
+        while self.samples_taken() < total {
+            let taken = self.samples_taken();
+            let target = (2 * taken).clamp(1, total);
+            let samples: Vec<u32> = self
+                .framebuffer
+                .samples
+                .iter()
+                .map(|&count| target.saturating_sub(count))
+                .collect();
+            self.sample_pixels(&samples, cancel, |i: f32| {
+                progress_callback(
+                    (taken as f32 + i / 100.0 * (target - taken) as f32) / total as f32 * 100.0,
+                )
+            });
+
+            let outcome = RenderOutcome::new(self.image(), cancel.is_cancelled());
+            if outcome.is_cancelled() || !on_pass(outcome.image()) {
+                return outcome;
+            }
+        }
+
+        RenderOutcome::new(self.final_image(), cancel.is_cancelled())
+    }
 }
 
-/// At the core, the ray tracer sends rays through pixels and computes the color seen in the direction
-/// of those rays. The involved steps are (1) calculate the ray from the eye to the pixel, (2) determine
-/// which objects the ray intersects, and (3) compute a color for that intersection point.
-///
-/// In addition to setting up the pixel dimensions for the rendered image, we also need to setup a
-/// virtual viewport through which to pass our scene rays. For the standard square pixel spacing, the
-/// viewport's aspect ratio should be the same as our rendered image. We'll just pick a viewport two
-/// units in height. We'll also set the distance between the projection plane and the projection point
-/// to be one unit. This is referred to as the "focal length"
+/// The result of rendering a scene. It holds both the linear radiance accumulated in the framebuffer,
+/// which can be used for post-processing or comparing renders, and the tone mapped 8-bit image
+/// derived from it for display. Writing the image to disk is left to the caller.
 ///
-/// ![Camera Geometry][camgeom]
-///
-/// The "eye" (or camera center if you think of a camera) is at (0,0,0). The y-axis is pointing upwards,
-/// and the x-axis goes towards the right. In order to respect the convention of a right handed
-/// coordinate system, into the screen is negative z-axis. The screen will be traversed from the upper
-/// left hand corner, and two offset vectors will be used, along the screen sides to move the ray
-/// endpoint across the screen.
-///
-/// A common trick used for visualizing normals (because it's easy and somewhat intuitive to assume
-/// **n** is a unit length vector - so each component is between -1 and 1) is to map each component
-/// to the interval from 0 to 1, and then map x/y/z to r/g/b.
+/// Once the image is denoised, the 8-bit image and the saved files are made from the denoised
+/// radiance, while the framebuffer keeps the radiance as it was rendered.
+pub struct RenderedImage {
+    framebuffer: FrameBuffer,
+    denoised: Option<FrameBuffer>,
+    image: RgbaImage,
+    aovs: Vec<(Aov, FrameBuffer)>,
+    display: DisplayTransform,
+}
+
+impl RenderedImage {
+    fn new(
+        framebuffer: FrameBuffer,
+        aovs: Vec<(Aov, FrameBuffer)>,
+        display: DisplayTransform,
+    ) -> RenderedImage {
+        let image = framebuffer.to_rgba_image(&display);
+
+        RenderedImage {
+            framebuffer,
+            denoised: None,
+            image,
+            aovs,
+            display,
+        }
+    }
+
+    /// Returns the image with its radiance denoised, guided by its albedo, normal and depth. The
+    /// image is returned as it is if those weren't rendered.
+    pub fn denoise(self, denoiser: &Denoiser) -> RenderedImage {
+        let guides = (
+            self.aov(Aov::Albedo),
+            self.aov(Aov::Normal),
+            self.aov(Aov::Depth),
+        );
+        let denoised = match guides {
+            (Some(albedo), Some(normal), Some(depth)) => denoiser
+                .denoise(&self.framebuffer, albedo, normal, depth)
+                .ok(),
+            _ => None,
+        };
+
+        match denoised {
+            Some(denoised) => RenderedImage {
+                image: denoised.to_rgba_image(&self.display),
+                denoised: Some(denoised),
+                ..self
+            },
+            None => self,
+        }
+    }
+
+    /// Returns the buffer of the given arbitrary output variable, if it was rendered.
+    pub fn aov(&self, aov: Aov) -> Option<&FrameBuffer> {
+        self.aovs
+            .iter()
+            .find(|(a, _)| *a == aov)
+            .map(|(_, buffer)| buffer)
+    }
+
+    /// Returns the arbitrary output variables which were rendered.
+    pub fn aovs(&self) -> impl Iterator<Item = Aov> + '_ {
+        self.aovs.iter().map(|(aov, _)| *aov)
+    }
+
+    pub fn framebuffer(&self) -> &FrameBuffer {
+        &self.framebuffer
+    }
+
+    /// Returns the denoised radiance, if the image was denoised.
+    pub fn denoised(&self) -> Option<&FrameBuffer> {
+        self.denoised.as_ref()
+    }
+
+    pub fn image(&self) -> &RgbaImage {
+        &self.image
+    }
+
+    /// Writes the image to `path` in the given format. The HDR formats (EXR and Radiance HDR) get
+    /// the linear radiance, while the others get the 8-bit image.
+    pub fn save(&self, path: &Path, format: ConfImageFormat) -> ImageResult<()> {
+        match format {
+            ConfImageFormat::Exr | ConfImageFormat::Hdr => self
+                .denoised
+                .as_ref()
+                .unwrap_or(&self.framebuffer)
+                .to_rgb32f_image()
+                .save_with_format(path, get_format(format)),
+            _ => self.image.save_with_format(path, get_format(format)),
+        }
+    }
+
+    /// Writes the buffer of an arbitrary output variable to `path` in the given format. The HDR
+    /// formats get the raw values, while the others get an image normalized for viewing.
+    pub fn save_aov(&self, aov: Aov, path: &Path, format: ConfImageFormat) -> ImageResult<()> {
+        let buffer = self.aov(aov).ok_or_else(|| {
+            ImageError::Parameter(ParameterError::from_kind(ParameterErrorKind::Generic(
+                format!("The {} output variable was not rendered", aov.name()),
+            )))
+        })?;
+
+        match format {
+            ConfImageFormat::Exr | ConfImageFormat::Hdr => buffer
+                .to_rgb32f_image()
+                .save_with_format(path, get_format(format)),
+            _ => aov
+                .visualize(buffer)
+                .save_with_format(path, get_format(format)),
+        }
+    }
+}
+
+/// The outcome of a render: either all the requested samples were gathered, or the render was
+/// cancelled, in which case the image holds whatever was rendered up to that point.
+pub enum RenderOutcome {
+    Completed(RenderedImage),
+    Cancelled(RenderedImage),
+}
+
+impl RenderOutcome {
+    fn new(image: RenderedImage, cancelled: bool) -> RenderOutcome {
+        if cancelled {
+            RenderOutcome::Cancelled(image)
+        } else {
+            RenderOutcome::Completed(image)
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        matches!(self, RenderOutcome::Cancelled(_))
+    }
+
+    pub fn image(&self) -> &RenderedImage {
+        match self {
+            RenderOutcome::Completed(image) | RenderOutcome::Cancelled(image) => image,
+        }
+    }
+
+    pub fn into_image(self) -> RenderedImage {
+        match self {
+            RenderOutcome::Completed(image) | RenderOutcome::Cancelled(image) => image,
+        }
+    }
+}
+
+/// Below this luminance, the error of a pixel is taken relative to it instead, so that the noise in
+/// the dark parts of the image, where it is hard to see, doesn't keep them sampling forever.
+const MIN_ERROR_LUMINANCE: f64 = 0.05;
+
+/// Returns the standard error of the mean luminance of the pixel, relative to the mean luminance.
 ///
-/// ## Adding Background Color to the Ray Color Function
-/// We want to be able to set a background color (probably black in presence of lights), so the only
-/// light in the scene is coming from the emitters.
-#[embed_doc_image("camgeom", "doc_images/camera_geometry.jpg")]
-fn ray_color(r: &Ray, bg_color: &Color, world: Arc<dyn Hittable>, depth: u32) -> Color {
-    // If we've exceeded the ray bounce limit, no more light is gathered.
-    if depth == 0 {
-        return Color::new(0.0, 0.0, 0.0);
-    }
-
-    // # Fixing the shadow Acne
-    // Some of the reflected rays hit the object they are reflecting off of not at exactly at
-    // **t = 0**, but instead at **t = -0.0000001** or **t = 0.0000001** or whatever floating
-    // point approximation the sphere intersector gives us. So we need to ignore hits very near zero:
-    // So pass the t_min as 0.001.
-    match world.hit(r, 0.001, utils::INFINITY) {
-        Some(hit_rec) => {
-            // todo!("Use a strategy pattern to choose between different diffusers");
-            //let target = hit_rec.p + hit_rec.normal + Vec3::random_vector_in_unit_sphere();
-            // let target =
-            //     hit_rec.p + hit_rec.normal + Vec3::random_unit_vector_lambertian_distribution();
-            // let target = hit_rec.p + Vec3::random_unit_vector_in_hemisphere(&hit_rec.normal);
-            // return 0.5
-            //     * ray_color(
-            //         &Ray::new(&hit_rec.p, &(target - hit_rec.p)),
-            //         world,
-            //         depth - 1,
-            //     );
-
-            let emitted = hit_rec.mat.emitted(hit_rec.u, hit_rec.v, &hit_rec.p);
-            match hit_rec.mat.scatter(r, &hit_rec) {
-                Some((scattered, attenuation)) => {
-                    emitted + attenuation * ray_color(&scattered, bg_color, world, depth - 1)
-                }
-                None => emitted,
-            }
+/// With only a few samples, a pixel whose paths all missed the lights looks perfectly converged, so
+/// the variance and the mean are estimated from the samples of the pixel and its 8 neighbours.
+fn relative_error(framebuffer: &FrameBuffer, x: u32, y: u32) -> f64 {
+    let (mut sum, mut sum_squares, mut count) = (0.0, 0.0, 0.0);
+    for ny in y.saturating_sub(1)..(y + 2).min(framebuffer.height()) {
+        for nx in x.saturating_sub(1)..(x + 2).min(framebuffer.width()) {
+            let i = (ny * framebuffer.width() + nx) as usize;
+            sum += framebuffer.sum_luminance[i];
+            sum_squares += framebuffer.sum_squares[i];
+            count += framebuffer.samples[i] as f64;
         }
-        // If the ray hits nothing, return the background color
-        None => *bg_color,
     }
+    if count < 2.0 {
+        return f64::INFINITY;
+    }
+
+    let mean = sum / count;
+    let variance = ((sum_squares / count - mean * mean) * count / (count - 1.0)).max(0.0);
+    let samples = framebuffer.sample_count(x, y).max(1) as f64;
+
+    (variance / samples).sqrt() / mean.max(MIN_ERROR_LUMINANCE)
 }
 
 fn get_format(format: ConfImageFormat) -> ImageFormat {
@@ -167,5 +650,141 @@ fn get_format(format: ConfImageFormat) -> ImageFormat {
         ConfImageFormat::Png => ImageFormat::Png,
         ConfImageFormat::Tiff => ImageFormat::Tiff,
         ConfImageFormat::Ppm => ImageFormat::Pnm,
+        ConfImageFormat::Exr => ImageFormat::OpenExr,
+        ConfImageFormat::Hdr => ImageFormat::Hdr,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::configuration::parse_configuration;
+    use crate::framebuffer::luminance;
+    use crate::materials::LambertianMaterial;
+    use crate::objects::{Sphere, World};
+    use crate::{Point, Vec3};
+
+    fn settings(samples_per_pixel: u32) -> ImageSettings {
+        let yaml = format!(
+            "aspect_ratio: 1.5\nheight: 6\nsamples_per_pixel: {samples_per_pixel}\nmax_depth: 3\n\
+             format: png\npath: \"\""
+        );
+        parse_configuration(&yaml).unwrap()
+    }
+
+    /// A diffuse sphere lit by the sky.
+    fn scene() -> Scene {
+        let mut world = World::new();
+        world.add(Arc::new(Sphere::new(
+            Point::new(0.0, 0.0, 0.0),
+            1.0,
+            Arc::new(LambertianMaterial::new(Color::new(0.5, 0.5, 0.5))),
+        )));
+        let camera = Camera::new(
+            Point::new(0.0, 0.0, 4.0),
+            Point::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            40.0,
+            1.5,
+            0.0,
+            4.0,
+            0.0,
+            1.0,
+        );
+
+        Scene::new(world, camera, Color::new(0.7, 0.8, 1.0))
+    }
+
+    #[test]
+    fn resumes_every_pixel_up_to_the_samples_per_pixel() {
+        let settings = settings(4);
+        // A framebuffer of a render stopped halfway through a pass, where the pixels of the top
+        // rows got one more sample than the others.
+        let mut framebuffer = FrameBuffer::new(settings.width, settings.height);
+        for (i, count) in framebuffer.samples.iter_mut().enumerate() {
+            *count = 1 + (i < 2 * settings.width as usize) as u32;
+        }
+        let mut renderer = Renderer::new_with_framebuffer(settings, scene(), framebuffer).unwrap();
+
+        renderer.render_progressive(&CancellationToken::new(), |_| true, |_| {});
+
+        assert_samples(renderer.framebuffer(), 4);
+    }
+
+    #[test]
+    fn resumes_a_cancelled_pass_up_to_the_samples_per_pixel() {
+        // Small tiles, so that the pass is cancelled after a few of them.
+        let mut settings = settings(4);
+        settings.tile_size = 2;
+        let mut renderer = Renderer::new(settings, scene()).unwrap();
+        let cancel = CancellationToken::new();
+        let outcome = renderer.render_progressive(
+            &cancel,
+            |_| true,
+            |progress: f32| {
+                if progress > 10.0 {
+                    cancel.cancel();
+                }
+            },
+        );
+        // Some of the pixels got their first sample before the pass was cancelled.
+        assert!(outcome.is_cancelled());
+        assert_eq!(renderer.samples_taken(), 0);
+        assert!(renderer
+            .framebuffer()
+            .samples
+            .iter()
+            .any(|&count| count > 0));
+
+        renderer.render_progressive(&CancellationToken::new(), |_| true, |_| {});
+
+        assert_samples(renderer.framebuffer(), 4);
+    }
+
+    #[test]
+    fn denoises_only_the_final_image() {
+        let mut settings = settings(4);
+        settings.denoiser = Some(Denoiser::new(2));
+        let mut renderer = Renderer::new(settings, scene()).unwrap();
+        let mut passes = 0;
+        let outcome = renderer.render_progressive(
+            &CancellationToken::new(),
+            |image| {
+                passes += 1;
+                image.denoised().is_none()
+            },
+            |_| {},
+        );
+
+        assert_eq!(passes, 3);
+        assert!(!outcome.is_cancelled());
+        assert!(outcome.image().denoised().is_some());
+    }
+
+    #[test]
+    fn relative_error_is_zero_on_a_flat_image() {
+        let mut framebuffer = FrameBuffer::new(4, 3);
+        let color = Color::new(0.2, 0.4, 0.6);
+        for i in 0..framebuffer.samples.len() {
+            framebuffer.sum[i] = color * 8.0;
+            framebuffer.sum_luminance[i] = luminance(&color) * 8.0;
+            framebuffer.sum_squares[i] = luminance(&color).powi(2) * 8.0;
+            framebuffer.samples[i] = 8;
+        }
+
+        for y in 0..framebuffer.height() {
+            for x in 0..framebuffer.width() {
+                let error = relative_error(&framebuffer, x, y);
+                assert!(error < 1e-6, "pixel ({x}, {y}) has an error of {error}");
+            }
+        }
+    }
+
+    fn assert_samples(framebuffer: &FrameBuffer, samples: u32) {
+        for y in 0..framebuffer.height() {
+            for x in 0..framebuffer.width() {
+                assert_eq!(framebuffer.sample_count(x, y), samples, "pixel ({x}, {y})");
+            }
+        }
     }
 }
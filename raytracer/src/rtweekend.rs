@@ -1,5 +1,6 @@
-use once_cell::sync::Lazy;
 use rand::prelude::*;
+use rand::rngs::SmallRng;
+use std::cell::RefCell;
 
 // As per the book's convention, this module will host all the constants needed.
 
@@ -11,12 +12,40 @@ pub fn degrees_to_radians(degrees: f64) -> f64 {
     degrees * PI / 180.0
 }
 
+thread_local! {
+    // Every thread has its own generator, so that no synchronization is needed between the render
+    // threads. The generator starts out with a fixed seed, which makes the results reproducible.
+    static RNG: RefCell<SmallRng> = RefCell::new(SmallRng::seed_from_u64(0));
+}
+
+/// Reseeds the random number generator of the current thread. All the random numbers generated on
+/// this thread afterwards are determined by the seed.
+pub fn seed_random_generator(seed: u64) {
+    RNG.with(|rng| *rng.borrow_mut() = SmallRng::seed_from_u64(seed));
+}
+
+/// Combines a global seed with the index of a pixel and of a sample within that pixel, to give
+/// every sample of the image its own independent seed. Seeding the generator this way for each
+/// sample makes the image independent of the number of threads and the order the pixels are
+/// rendered in.
+pub fn sample_seed(seed: u64, pixel: u64, sample: u64) -> u64 {
+    mix(mix(seed ^ mix(pixel)) ^ sample)
+}
+
+/// The finalizer of the SplitMix64 generator, which scrambles the bits of the input so that seeds
+/// close to each other give unrelated outputs.
+#[inline]
+pub(crate) fn mix(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
 /// Returns a random real in [0,1).
 #[inline]
 pub fn random_in_unit_interval() -> f64 {
-    static mut RNG_THREAD: Lazy<ThreadRng> = Lazy::new(ThreadRng::default);
-
-    unsafe { RNG_THREAD.gen() }
+    RNG.with(|rng| rng.borrow_mut().random())
 }
 
 /// Returns a random real in the range [min, max).
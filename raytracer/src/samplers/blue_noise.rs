@@ -0,0 +1,148 @@
+use crate::samplers::sobol::scrambled_sobol_2d;
+use crate::samplers::{hash, Sampler};
+use rand::prelude::*;
+use rand::rngs::SmallRng;
+use std::sync::OnceLock;
+
+/// The size of the tile of blue noise, which is repeated over the image.
+const TILE_SIZE: usize = 64;
+
+/// # Blue Noise Sampler
+/// With the other samplers, the error left in neighbouring pixels is unrelated, which shows up as
+/// white noise: grain of all sizes, including clumps of bright or dark pixels. The eye is much less
+/// sensitive to *blue* noise, where neighbouring pixels err in opposite directions, and the grain
+/// is as fine as possible. Blue noise also goes away quickly when the image is blurred, for e.g. by
+/// the denoiser.
+///
+/// All the pixels share the same scrambled Sobol points, which are then shifted by an offset read
+/// from a tile of blue noise, wrapping around at 1 (Heitz and Belcour 2019, "Distributing Monte
+/// Carlo Errors as a Blue Noise in Screen Space"). Neighbouring pixels get very different offsets,
+/// so their errors end up blue as well. Each pair of dimensions reads the tile at a different
+/// random position.
+#[derive(Debug, Clone, Copy)]
+pub struct BlueNoiseSampler {
+    seed: u64,
+}
+
+impl BlueNoiseSampler {
+    pub fn new(seed: u64) -> BlueNoiseSampler {
+        BlueNoiseSampler { seed }
+    }
+}
+
+impl Sampler for BlueNoiseSampler {
+    fn sample_2d(&self, x: u32, y: u32, index: u32, dimension: u32) -> (f64, f64) {
+        let seed = hash(&[self.seed, dimension as u64]);
+        let (u, v) = scrambled_sobol_2d(index, seed);
+
+        let tile = blue_noise_tile();
+        let offset = |h: u64| {
+            let tx = (x as usize + (h as usize % TILE_SIZE)) % TILE_SIZE;
+            let ty = (y as usize + ((h >> 32) as usize % TILE_SIZE)) % TILE_SIZE;
+            tile[ty * TILE_SIZE + tx]
+        };
+
+        (
+            (u + offset(hash(&[seed, 0]))).fract(),
+            (v + offset(hash(&[seed, 1]))).fract(),
+        )
+    }
+}
+
+/// Returns the tile of blue noise, which is generated the first time it is needed.
+fn blue_noise_tile() -> &'static [f64] {
+    static TILE: OnceLock<Vec<f64>> = OnceLock::new();
+    TILE.get_or_init(void_and_cluster)
+}
+
+/// Generates a tile of blue noise with the void and cluster method (Ulichney 1993). The *energy* of
+/// a pixel is the sum of a Gaussian of its distance to each of the pixels which are set, so a high
+/// energy marks a tight cluster of set pixels, and a low one a large void between them. Starting
+/// from a few random pixels which are evened out by moving them from the tightest cluster to the
+/// largest void, the pixels are ranked by removing the set pixels from the tightest cluster one at
+/// a time, and then setting the pixels at the largest void one at a time. The ranks, scaled to
+/// [0,1), give the noise.
+fn void_and_cluster() -> Vec<f64> {
+    const SIZE: usize = TILE_SIZE * TILE_SIZE;
+    const SIGMA: f64 = 1.5;
+
+    // The tile wraps around, so the energy of a pixel only depends on its offset from another one.
+    let falloff: Vec<f64> = (0..SIZE)
+        .map(|i| {
+            let wrap = |d: usize| d.min(TILE_SIZE - d) as f64;
+            let (dx, dy) = (wrap(i % TILE_SIZE), wrap(i / TILE_SIZE));
+            (-(dx * dx + dy * dy) / (2.0 * SIGMA * SIGMA)).exp()
+        })
+        .collect();
+
+    let update = |energy: &mut [f64], pixel: usize, sign: f64| {
+        let (px, py) = (pixel % TILE_SIZE, pixel / TILE_SIZE);
+        for (i, e) in energy.iter_mut().enumerate() {
+            let dx = (i % TILE_SIZE + TILE_SIZE - px) % TILE_SIZE;
+            let dy = (i / TILE_SIZE + TILE_SIZE - py) % TILE_SIZE;
+            *e += sign * falloff[dy * TILE_SIZE + dx];
+        }
+    };
+    let tightest_cluster = |pattern: &[bool], energy: &[f64]| {
+        (0..SIZE)
+            .filter(|&i| pattern[i])
+            .max_by(|&a, &b| energy[a].total_cmp(&energy[b]))
+            .unwrap()
+    };
+    let largest_void = |pattern: &[bool], energy: &[f64]| {
+        (0..SIZE)
+            .filter(|&i| !pattern[i])
+            .min_by(|&a, &b| energy[a].total_cmp(&energy[b]))
+            .unwrap()
+    };
+
+    // The initial pattern, with a tenth of the pixels set.
+    let mut rng = SmallRng::seed_from_u64(0);
+    let mut pattern = vec![false; SIZE];
+    let mut energy = vec![0.0; SIZE];
+    let initial = SIZE / 10;
+    let mut set = 0;
+    while set < initial {
+        let pixel = rng.random_range(0..SIZE);
+        if !pattern[pixel] {
+            pattern[pixel] = true;
+            update(&mut energy, pixel, 1.0);
+            set += 1;
+        }
+    }
+    // Moving the pixels around usually settles quickly, but stop at some point in case it doesn't.
+    for _ in 0..SIZE {
+        let cluster = tightest_cluster(&pattern, &energy);
+        pattern[cluster] = false;
+        update(&mut energy, cluster, -1.0);
+        let void = largest_void(&pattern, &energy);
+        pattern[void] = true;
+        update(&mut energy, void, 1.0);
+        if void == cluster {
+            break;
+        }
+    }
+
+    let mut rank = vec![0; SIZE];
+
+    // Rank the initial pixels, from the last one down.
+    let (mut ranking, mut ranking_energy) = (pattern.clone(), energy.clone());
+    for r in (0..initial).rev() {
+        let cluster = tightest_cluster(&ranking, &ranking_energy);
+        ranking[cluster] = false;
+        update(&mut ranking_energy, cluster, -1.0);
+        rank[cluster] = r;
+    }
+
+    // Rank the rest of the pixels, filling the voids.
+    for r in initial..SIZE {
+        let void = largest_void(&pattern, &energy);
+        pattern[void] = true;
+        update(&mut energy, void, 1.0);
+        rank[void] = r;
+    }
+
+    rank.iter()
+        .map(|&r| (r as f64 + 0.5) / SIZE as f64)
+        .collect()
+}
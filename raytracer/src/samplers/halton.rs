@@ -0,0 +1,69 @@
+use crate::samplers::{hash, permutation_element, Sampler};
+use crate::utils::random_in_unit_interval;
+
+/// The bases of the dimensions of the Halton sequence. Beyond these, the points of the sequence
+/// correlate too much between the dimensions to be of use, so random numbers are used instead.
+const PRIMES: [u32; 32] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131,
+];
+
+/// # Halton Sampler
+/// The Halton sequence takes the *i*-th coordinate of a point in dimension *d* as the radical inverse
+/// of *i* in the base of the *d*-th prime: the digits of *i* in that base are mirrored around the
+/// decimal point, so 1, 2, 3, 4, ... in base 2 give 0.1, 0.01, 0.11, 0.001, ... in binary, that is
+/// 1/2, 1/4, 3/4, 1/8, .... Every new point falls into the largest gap left by the previous ones,
+/// and as the bases are coprime, the points fill every pair of dimensions evenly too.
+///
+/// The plain sequence has two problems: all the pixels would get the same points, and in the larger
+/// bases the first points line up, as the first *b* points in base *b* are just 0, 1/b, 2/b, ....
+/// Both are fixed by Owen scrambling the digits: each digit is replaced through a random
+/// permutation of the digits of the base, picked by the pixel, the dimension and the digits before
+/// it. This keeps the points as evenly spread as before, but breaks up the lines.
+#[derive(Debug, Clone, Copy)]
+pub struct HaltonSampler {
+    seed: u64,
+}
+
+impl HaltonSampler {
+    pub fn new(seed: u64) -> HaltonSampler {
+        HaltonSampler { seed }
+    }
+}
+
+impl Sampler for HaltonSampler {
+    fn sample_2d(&self, x: u32, y: u32, index: u32, dimension: u32) -> (f64, f64) {
+        let d = 2 * dimension as usize;
+        if d + 1 >= PRIMES.len() {
+            return (random_in_unit_interval(), random_in_unit_interval());
+        }
+
+        let seed = hash(&[self.seed, x as u64, y as u64, dimension as u64]);
+
+        (
+            scrambled_radical_inverse(PRIMES[d], index, seed),
+            scrambled_radical_inverse(PRIMES[d + 1], index, hash(&[seed])),
+        )
+    }
+}
+
+/// Mirrors the digits of `index` in the given base around the decimal point, passing each digit
+/// through a permutation which depends on the seed and the digits before it.
+fn scrambled_radical_inverse(base: u32, mut index: u32, seed: u64) -> f64 {
+    let inverse_base = 1.0 / base as f64;
+    let mut reversed = 0.0;
+    let mut scale = inverse_base;
+    let mut prefix = seed;
+    // Keep going past the last digit of the index, as the leading zeros get scrambled too, until the
+    // digits are finer than the 32 bits of the other samplers.
+    while scale * (1u64 << 32) as f64 > 1.0 {
+        let digit = index % base;
+        let permuted = permutation_element(digit, base, hash(&[prefix]) as u32);
+        reversed += permuted as f64 * scale;
+        prefix = hash(&[prefix, digit as u64]);
+        index /= base;
+        scale *= inverse_base;
+    }
+
+    reversed
+}
@@ -0,0 +1,19 @@
+use crate::samplers::Sampler;
+use crate::utils::random_in_unit_interval;
+
+/// Draws every dimension at random. The random number generator is seeded for every sample by the
+/// renderer, so the samples are still reproducible.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct IndependentSampler;
+
+impl IndependentSampler {
+    pub fn new() -> IndependentSampler {
+        IndependentSampler
+    }
+}
+
+impl Sampler for IndependentSampler {
+    fn sample_2d(&self, _x: u32, _y: u32, _index: u32, _dimension: u32) -> (f64, f64) {
+        (random_in_unit_interval(), random_in_unit_interval())
+    }
+}
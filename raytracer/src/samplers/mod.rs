@@ -0,0 +1,13 @@
+mod blue_noise;
+mod halton;
+mod independent;
+mod sampler;
+mod sobol;
+mod stratified;
+
+pub use blue_noise::BlueNoiseSampler;
+pub use halton::HaltonSampler;
+pub use independent::IndependentSampler;
+pub use sampler::*;
+pub use sobol::SobolSampler;
+pub use stratified::StratifiedSampler;
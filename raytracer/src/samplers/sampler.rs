@@ -0,0 +1,225 @@
+use crate::rtweekend::{mix, random_in_unit_interval};
+use crate::samplers::{
+    BlueNoiseSampler, HaltonSampler, IndependentSampler, SobolSampler, StratifiedSampler,
+};
+use serde::Deserialize;
+use std::cell::RefCell;
+use std::sync::Arc;
+
+/// # Samplers
+/// Every sample of a pixel is a point in a high dimensional space: two dimensions pick the position
+/// of the sample inside the pixel, two more the point on the lens, one the time the shutter is open,
+/// and then every bounce of the path uses a few more to pick the scattered direction. Picking every
+/// coordinate independently at random leaves clumps and holes between the samples, and the error
+/// only goes down with the square root of the number of samples. Spreading the samples out evenly
+/// over each pair of dimensions gets rid of the clumps, and makes the image converge much faster.
+///
+/// A sampler returns the coordinates for a given pair of `dimension`s of the `index`-th sample of the
+/// pixel (`x`, `y`). The coordinates only depend on these arguments, so samples can be taken in any
+/// order on any thread, and the image comes out the same.
+pub trait Sampler: Send + Sync {
+    fn sample_2d(&self, x: u32, y: u32, index: u32, dimension: u32) -> (f64, f64);
+}
+
+/// The samplers which can be chosen in the configuration.
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SamplerType {
+    /// Uniform random numbers for every dimension.
+    #[default]
+    Independent,
+    /// Jittered samples on a grid of strata, shuffled between the dimensions.
+    Stratified,
+    /// The Halton sequence, with the digits Owen scrambled for each pixel.
+    Halton,
+    /// The Sobol sequence, with hash based Owen scrambling.
+    Sobol,
+    /// The Sobol sequence, with the error distributed as blue noise over the image.
+    BlueNoise,
+}
+
+impl SamplerType {
+    /// Returns the sampler for an image with the given number of samples per pixel.
+    pub fn create(&self, samples_per_pixel: u32, seed: u64) -> Arc<dyn Sampler> {
+        match self {
+            SamplerType::Independent => Arc::new(IndependentSampler::new()),
+            SamplerType::Stratified => Arc::new(StratifiedSampler::new(samples_per_pixel, seed)),
+            SamplerType::Halton => Arc::new(HaltonSampler::new(seed)),
+            SamplerType::Sobol => Arc::new(SobolSampler::new(seed)),
+            SamplerType::BlueNoise => Arc::new(BlueNoiseSampler::new(seed)),
+        }
+    }
+}
+
+struct CurrentSample {
+    sampler: Arc<dyn Sampler>,
+    x: u32,
+    y: u32,
+    index: u32,
+    dimension: u32,
+}
+
+thread_local! {
+    // The sample which is being traced on this thread, if any. The camera and the materials draw
+    // their random numbers from it through `sample_1d` and `sample_2d`.
+    static CURRENT_SAMPLE: RefCell<Option<CurrentSample>> = const { RefCell::new(None) };
+}
+
+/// Starts tracing the `index`-th sample of the pixel (`x`, `y`) on this thread. The following calls
+/// to `sample_1d` and `sample_2d` return the dimensions of this sample in order.
+pub(crate) fn start_sample(sampler: &Arc<dyn Sampler>, x: u32, y: u32, index: u32) {
+    CURRENT_SAMPLE.with(|current| {
+        *current.borrow_mut() = Some(CurrentSample {
+            sampler: sampler.clone(),
+            x,
+            y,
+            index,
+            dimension: 0,
+        })
+    });
+}
+
+/// Stops drawing the numbers from the sampler on this thread.
+pub(crate) fn finish_sample() {
+    CURRENT_SAMPLE.with(|current| *current.borrow_mut() = None);
+}
+
+/// Returns the next pair of dimensions of the sample being traced on this thread, or two random
+/// numbers if no sample is being traced (for e.g. while building the scene).
+pub fn sample_2d() -> (f64, f64) {
+    CURRENT_SAMPLE.with(|current| match current.borrow_mut().as_mut() {
+        Some(sample) => {
+            let dimension = sample.dimension;
+            sample.dimension += 1;
+            sample
+                .sampler
+                .sample_2d(sample.x, sample.y, sample.index, dimension)
+        }
+        None => (random_in_unit_interval(), random_in_unit_interval()),
+    })
+}
+
+/// Returns the next dimension of the sample being traced on this thread. This uses up a whole pair
+/// of dimensions, so that the following pairs stay aligned between the samples.
+pub fn sample_1d() -> f64 {
+    sample_2d().0
+}
+
+/// Hashes the arguments together into 64 random bits.
+#[inline]
+pub(crate) fn hash(values: &[u64]) -> u64 {
+    values.iter().fold(0, |h, &v| mix(h ^ v))
+}
+
+/// Maps 64 random bits to a real in [0,1).
+#[inline]
+pub(crate) fn hash_to_unit(hash: u64) -> f64 {
+    (hash >> 11) as f64 * (1.0 / (1u64 << 53) as f64)
+}
+
+/// Returns the element at position `i` of a random permutation of `0..n`, picked by `seed`, without
+/// building the permutation (Kensler 2013, "Correlated Multi-Jittered Sampling").
+pub(crate) fn permutation_element(mut i: u32, n: u32, seed: u32) -> u32 {
+    let mut w = n - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+    loop {
+        i ^= seed;
+        i = i.wrapping_mul(0xe170893d);
+        i ^= seed >> 16;
+        i ^= (i & w) >> 4;
+        i ^= seed >> 8;
+        i = i.wrapping_mul(0x0929eb3f);
+        i ^= seed >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | seed >> 27);
+        i = i.wrapping_mul(0x6935fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dcb303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e501cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < n {
+            break;
+        }
+    }
+
+    (i.wrapping_add(seed)) % n
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns how many of the first `n` samples of the pixel fall into each cell of a grid of
+    /// `columns` by `rows` cells, over the given pair of dimensions.
+    fn cell_counts(
+        sampler: &dyn Sampler,
+        n: u32,
+        (columns, rows): (u32, u32),
+        dimension: u32,
+    ) -> Vec<u32> {
+        let mut counts = vec![0; (columns * rows) as usize];
+        for index in 0..n {
+            let (u, v) = sampler.sample_2d(3, 5, index, dimension);
+            let (column, row) = ((u * columns as f64) as u32, (v * rows as f64) as u32);
+            counts[(row * columns + column) as usize] += 1;
+        }
+        counts
+    }
+
+    #[test]
+    fn samples_are_in_the_unit_square() {
+        let samplers = [
+            SamplerType::Independent,
+            SamplerType::Stratified,
+            SamplerType::Halton,
+            SamplerType::Sobol,
+            SamplerType::BlueNoise,
+        ];
+        for sampler_type in samplers {
+            let sampler = sampler_type.create(16, 7);
+            for (x, y, index, dimension) in (0..64).map(|i| (i % 5, i / 5, i % 16, i % 20)) {
+                let (u, v) = sampler.sample_2d(x, y, index, dimension);
+                assert!(
+                    (0.0..1.0).contains(&u) && (0.0..1.0).contains(&v),
+                    "{sampler_type:?} gave ({u}, {v})"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn stratified_and_sobol_samples_fill_every_stratum_once() {
+        for sampler_type in [SamplerType::Stratified, SamplerType::Sobol] {
+            let sampler = sampler_type.create(16, 7);
+            for dimension in 0..4 {
+                let counts = cell_counts(sampler.as_ref(), 16, (4, 4), dimension);
+                assert!(
+                    counts.iter().all(|&count| count == 1),
+                    "{sampler_type:?} in dimension {dimension}: {counts:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn halton_samples_fill_every_stratum_once() {
+        let sampler = SamplerType::Halton.create(16, 7);
+        // The strata of each pair of dimensions follow the bases of the pair, 2 and 3 for the
+        // first one, then 5 and 7.
+        for (dimension, (columns, rows)) in [(0, (4, 9)), (1, (5, 7))] {
+            let counts = cell_counts(sampler.as_ref(), columns * rows, (columns, rows), dimension);
+            assert!(
+                counts.iter().all(|&count| count == 1),
+                "dimension {dimension}: {counts:?}"
+            );
+        }
+    }
+}
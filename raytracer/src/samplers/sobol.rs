@@ -0,0 +1,81 @@
+use crate::samplers::{hash, Sampler};
+
+/// # Sobol Sampler
+/// The first two dimensions of the Sobol sequence form a (0,2)-sequence in base 2: every block of
+/// 2<sup>*k*</sup> consecutive points puts exactly one point into every cell of *any* partition
+/// of the unit square into 2<sup>*k*</sup> equal rectangles, of whatever shape. This makes them
+/// about as well spread out as points can get.
+///
+/// Each pixel and pair of dimensions gets its own copy of these two dimensions, randomized with
+/// Owen scrambling: the bits are flipped at random in a way which keeps the points spread out. The
+/// order of the points is shuffled in the same way, so that the pairs don't correlate with each
+/// other. This follows Burley 2020, "Practical Hash-based Owen Scrambling".
+#[derive(Debug, Clone, Copy)]
+pub struct SobolSampler {
+    seed: u64,
+}
+
+impl SobolSampler {
+    pub fn new(seed: u64) -> SobolSampler {
+        SobolSampler { seed }
+    }
+}
+
+impl Sampler for SobolSampler {
+    fn sample_2d(&self, x: u32, y: u32, index: u32, dimension: u32) -> (f64, f64) {
+        let seed = hash(&[self.seed, x as u64, y as u64, dimension as u64]);
+        scrambled_sobol_2d(index, seed)
+    }
+}
+
+/// Returns the `index`-th point of the first two dimensions of the Sobol sequence, with the order of
+/// the points and the points themselves Owen scrambled by the seed.
+pub(crate) fn scrambled_sobol_2d(index: u32, seed: u64) -> (f64, f64) {
+    let index = nested_uniform_scramble(index, seed as u32);
+    let sx = nested_uniform_scramble(sobol(index, 0), hash(&[seed, 0]) as u32);
+    let sy = nested_uniform_scramble(sobol(index, 1), hash(&[seed, 1]) as u32);
+
+    (to_unit(sx), to_unit(sy))
+}
+
+/// Returns the `index`-th point of the given dimension (0 or 1) of the Sobol sequence, as the bits
+/// of a fixed point number in [0,1).
+fn sobol(index: u32, dimension: u32) -> u32 {
+    // The direction numbers of the first dimension are the bits from the top down, which gives the
+    // van der Corput sequence, and for the second one each is the previous one xor-ed with itself
+    // shifted right by one.
+    let mut direction: u32 = 1 << 31;
+    let mut result = 0;
+    for bit in 0..32 {
+        if index & (1 << bit) != 0 {
+            result ^= direction;
+        }
+        direction = match dimension {
+            0 => direction >> 1,
+            _ => direction ^ (direction >> 1),
+        };
+    }
+
+    result
+}
+
+/// Owen scrambles the bits of `x`, from the highest one down. Flipping a bit based on all the bits
+/// above it keeps the points in the same cells of the partitions, which keeps them spread out.
+fn nested_uniform_scramble(x: u32, seed: u32) -> u32 {
+    laine_karras_permutation(x.reverse_bits(), seed).reverse_bits()
+}
+
+/// A hash where each bit of the output only depends on the same and the lower bits of the input.
+fn laine_karras_permutation(mut x: u32, seed: u32) -> u32 {
+    x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50b47c);
+    x ^= x.wrapping_mul(0xb82f1e52);
+    x ^= x.wrapping_mul(0xc7afe638);
+    x ^= x.wrapping_mul(0x8d22f6e6);
+    x
+}
+
+#[inline]
+fn to_unit(x: u32) -> f64 {
+    x as f64 / (1u64 << 32) as f64
+}
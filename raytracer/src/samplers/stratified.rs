@@ -0,0 +1,53 @@
+use crate::samplers::{hash, hash_to_unit, permutation_element, Sampler};
+
+/// # Stratified Sampling
+/// Splits each pair of dimensions into a grid of cells (strata), as close to square as possible
+/// with one cell for every sample of the pixel, and places each sample at a random position in a
+/// different cell. The cells are handed out to the samples in a shuffled order, which is different
+/// for every pixel and every pair of dimensions, so that the pairs don't correlate with each other.
+///
+/// Samples beyond `samples_per_pixel` (for e.g. with adaptive sampling) go through the grid again,
+/// with a different shuffle.
+#[derive(Debug, Clone, Copy)]
+pub struct StratifiedSampler {
+    columns: u32,
+    rows: u32,
+    seed: u64,
+}
+
+impl StratifiedSampler {
+    pub fn new(samples_per_pixel: u32, seed: u64) -> StratifiedSampler {
+        let samples_per_pixel = samples_per_pixel.max(1);
+        let columns = (samples_per_pixel as f64).sqrt().ceil() as u32;
+        let rows = samples_per_pixel.div_ceil(columns);
+        StratifiedSampler {
+            columns,
+            rows,
+            seed,
+        }
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn sample_2d(&self, x: u32, y: u32, index: u32, dimension: u32) -> (f64, f64) {
+        let strata = self.columns * self.rows;
+        let round = index / strata;
+        let key = hash(&[
+            self.seed,
+            x as u64,
+            y as u64,
+            dimension as u64,
+            round as u64,
+        ]);
+
+        let stratum = permutation_element(index % strata, strata, key as u32);
+        let jitter = hash(&[key, index as u64]);
+        let jx = hash_to_unit(jitter);
+        let jy = hash_to_unit(hash(&[jitter]));
+
+        (
+            ((stratum % self.columns) as f64 + jx) / self.columns as f64,
+            ((stratum / self.columns) as f64 + jy) / self.rows as f64,
+        )
+    }
+}
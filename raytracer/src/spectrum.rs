@@ -0,0 +1,139 @@
+use crate::{Color, Vec3};
+use once_cell::sync::Lazy;
+use std::cell::Cell;
+
+/// The range of wavelengths, in nanometers, the paths are traced at when rendering spectrally.
+pub const MIN_WAVELENGTH: f64 = 380.0;
+pub const MAX_WAVELENGTH: f64 = 720.0;
+
+/// The spectra of Smits 1999, "An RGB to Spectrum Conversion for Reflectances", sampled in ten
+/// equally wide bins over the range of wavelengths.
+const BINS: usize = 10;
+const WHITE: [f64; BINS] = [
+    1.0000, 1.0000, 0.9999, 0.9993, 0.9992, 0.9998, 1.0000, 1.0000, 1.0000, 1.0000,
+];
+const CYAN: [f64; BINS] = [
+    0.9710, 0.9426, 1.0007, 1.0007, 1.0007, 1.0007, 0.1564, 0.0000, 0.0000, 0.0000,
+];
+const MAGENTA: [f64; BINS] = [
+    1.0000, 1.0000, 0.9685, 0.2229, 0.0000, 0.0458, 0.8369, 1.0000, 1.0000, 0.9959,
+];
+const YELLOW: [f64; BINS] = [
+    0.0001, 0.0000, 0.1088, 0.6651, 1.0000, 1.0000, 0.9996, 0.9586, 0.9685, 0.9840,
+];
+const RED: [f64; BINS] = [
+    0.1012, 0.0515, 0.0000, 0.0000, 0.0000, 0.0000, 0.8325, 1.0149, 1.0149, 1.0149,
+];
+const GREEN: [f64; BINS] = [
+    0.0000, 0.0000, 0.0273, 0.7937, 1.0000, 0.9418, 0.1719, 0.0000, 0.0000, 0.0025,
+];
+const BLUE: [f64; BINS] = [
+    1.0000, 1.0000, 0.8916, 0.3323, 0.0000, 0.0000, 0.0003, 0.0369, 0.0483, 0.0496,
+];
+
+thread_local! {
+    // The wavelength of the path being traced on this thread, when rendering spectrally.
+    static WAVELENGTH: Cell<Option<f64>> = const { Cell::new(None) };
+}
+
+/// The color of a spectrum of ones, which the colors at the film are divided by so that white
+/// stays white.
+static WHITE_POINT: Lazy<Color> = Lazy::new(|| {
+    const STEPS: usize = 1000;
+    let step = (MAX_WAVELENGTH - MIN_WAVELENGTH) / STEPS as f64;
+    let mut sum = Color::new(0.0, 0.0, 0.0);
+    for i in 0..STEPS {
+        sum += xyz_to_rgb(&wavelength_to_xyz(MIN_WAVELENGTH + (i as f64 + 0.5) * step));
+    }
+
+    sum / STEPS as f64
+});
+
+/// Returns the value at the `wavelength`, in nanometers, of a smooth spectrum which looks like
+/// `color`: a mix of a white spectrum with the spectra of the two closest of cyan, magenta, yellow,
+/// red, green and blue.
+pub fn rgb_to_spectrum(color: &Color, wavelength: f64) -> f64 {
+    let bin = ((wavelength - MIN_WAVELENGTH) / (MAX_WAVELENGTH - MIN_WAVELENGTH) * BINS as f64)
+        .clamp(0.0, (BINS - 1) as f64) as usize;
+    let (r, g, b) = (color.x(), color.y(), color.z());
+
+    if r <= g && r <= b {
+        let (mid, high) = if g <= b { (g, b) } else { (b, g) };
+        let second = if g <= b { BLUE[bin] } else { GREEN[bin] };
+        r * WHITE[bin] + (mid - r) * CYAN[bin] + (high - mid) * second
+    } else if g <= r && g <= b {
+        let (mid, high) = if r <= b { (r, b) } else { (b, r) };
+        let second = if r <= b { BLUE[bin] } else { RED[bin] };
+        g * WHITE[bin] + (mid - g) * MAGENTA[bin] + (high - mid) * second
+    } else {
+        let (mid, high) = if r <= g { (r, g) } else { (g, r) };
+        let second = if r <= g { GREEN[bin] } else { RED[bin] };
+        b * WHITE[bin] + (mid - b) * YELLOW[bin] + (high - mid) * second
+    }
+}
+
+/// Returns the CIE 1931 XYZ color of light of unit power at the `wavelength`, with the multi-lobe
+/// fit of the color matching functions by Wyman, Sloan and Shirley 2013, "Simple Analytic
+/// Approximations to the CIE XYZ Color Matching Functions".
+pub fn wavelength_to_xyz(wavelength: f64) -> Vec3 {
+    let lobe = |mean: f64, below: f64, above: f64| {
+        let t = (wavelength - mean) / if wavelength < mean { below } else { above };
+        (-0.5 * t * t).exp()
+    };
+
+    Vec3::new(
+        1.056 * lobe(599.8, 37.9, 31.0) + 0.362 * lobe(442.0, 16.0, 26.7)
+            - 0.065 * lobe(501.1, 20.4, 26.2),
+        0.821 * lobe(568.8, 46.9, 40.5) + 0.286 * lobe(530.9, 16.3, 31.1),
+        1.217 * lobe(437.0, 11.8, 36.0) + 0.681 * lobe(459.0, 26.0, 13.8),
+    )
+}
+
+/// Converts a CIE XYZ color to linear sRGB.
+pub fn xyz_to_rgb(xyz: &Vec3) -> Color {
+    let (x, y, z) = (xyz.x(), xyz.y(), xyz.z());
+
+    Color::new(
+        3.2404542 * x - 1.5371385 * y - 0.4985314 * z,
+        -0.9692660 * x + 1.8760108 * y + 0.0415560 * z,
+        0.0556434 * x - 0.2040259 * y + 1.0572252 * z,
+    )
+}
+
+/// Returns the color the light of the given `radiance` at the `wavelength` adds up to, when the
+/// wavelengths are picked uniformly over the range. Averaged over the wavelengths, a spectrum of
+/// ones gives white.
+pub fn wavelength_to_rgb(radiance: f64, wavelength: f64) -> Color {
+    let rgb = xyz_to_rgb(&wavelength_to_xyz(wavelength)) * radiance;
+
+    Color::new(
+        rgb.x() / WHITE_POINT.x(),
+        rgb.y() / WHITE_POINT.y(),
+        rgb.z() / WHITE_POINT.z(),
+    )
+}
+
+/// Returns the wavelength of the path being traced on this thread, if rendering spectrally.
+pub fn current_wavelength() -> Option<f64> {
+    WAVELENGTH.with(|wavelength| wavelength.get())
+}
+
+/// Sets the wavelength the path traced on this thread is at, or none to go back to tracing the
+/// RGB channels together.
+pub(crate) fn set_current_wavelength(wavelength: Option<f64>) {
+    WAVELENGTH.with(|current| current.set(wavelength));
+}
+
+/// Returns the color as seen along the path being traced on this thread: when rendering
+/// spectrally, that is the value of its spectrum at the wavelength of the path, in all three
+/// channels, and otherwise the color itself. The materials pass the colors they reflect and emit
+/// through this.
+pub(crate) fn at_current_wavelength(color: Color) -> Color {
+    match current_wavelength() {
+        Some(wavelength) => {
+            let value = rgb_to_spectrum(&color, wavelength);
+            Color::new(value, value, value)
+        }
+        None => color,
+    }
+}
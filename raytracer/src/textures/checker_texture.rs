@@ -1,4 +1,5 @@
 use super::Texture;
+use crate::objects::HitRecord;
 use crate::textures::SolidColor;
 use crate::{Color, Point};
 use std::sync::Arc;
@@ -13,13 +14,11 @@ pub struct CheckerTexture {
 
 impl Texture for CheckerTexture {
     fn value(&self, u: f64, v: f64, p: &Point) -> Color {
-        let sines = (10.0 * p.x()).sin() * (10.0 * p.y()).sin() * (10.0 * p.z()).sin();
+        self.square(p).value(u, v, p)
+    }
 
-        if sines < 0.0 {
-            self.odd.value(u, v, p)
-        } else {
-            self.even.value(u, v, p)
-        }
+    fn value_at(&self, rec: &HitRecord) -> Color {
+        self.square(&rec.p).value_at(rec)
     }
 }
 
@@ -27,6 +26,17 @@ impl CheckerTexture {
     pub fn new(odd: Arc<dyn Texture>, even: Arc<dyn Texture>) -> CheckerTexture {
         CheckerTexture { odd, even }
     }
+
+    /// Returns the texture of the square of the pattern the point `p` is in.
+    fn square(&self, p: &Point) -> &Arc<dyn Texture> {
+        let sines = (10.0 * p.x()).sin() * (10.0 * p.y()).sin() * (10.0 * p.z()).sin();
+
+        if sines < 0.0 {
+            &self.odd
+        } else {
+            &self.even
+        }
+    }
 }
 
 impl Default for CheckerTexture {
@@ -4,6 +4,7 @@ use crate::{Color, Point};
 use image;
 use image::{DynamicImage, GenericImageView};
 use std::path::Path;
+use std::sync::Arc;
 
 /// # Image Texture Mapping
 /// From the point **P**, we compute the surface coordinates *(u,v)*. We then use these to index into
@@ -23,10 +24,16 @@ use std::path::Path;
 /// ```
 ///
 /// This is just a fractional position.
+///
+/// The bytes of most images are sRGB encoded, as they are made to be shown on a screen, while the
+/// ray tracer works with the linear amount of light. The textures of scene files which say so are
+/// decoded to linear colors, and multiplied by a color factor.
 #[derive(Clone)]
 pub struct ImageTexture {
-    img: DynamicImage,
+    img: Arc<DynamicImage>,
     // bytes_per_scanline: u32,
+    srgb: bool,
+    factor: Color,
 }
 
 impl Texture for ImageTexture {
@@ -62,13 +69,17 @@ impl Texture for ImageTexture {
 
         let color_scale = 1.0 / 255.0;
         let pixel = self.img.get_pixel(i, j);
+        let channel = |byte: u8| {
+            let value = color_scale * byte as f64;
+            if self.srgb {
+                srgb_eotf(value)
+            } else {
+                value
+            }
+        };
 
         //Currently we are not caring about alpha channel.
-        Color::new(
-            color_scale * pixel[0] as f64,
-            color_scale * pixel[1] as f64,
-            color_scale * pixel[2] as f64,
-        )
+        self.factor * Color::new(channel(pixel[0]), channel(pixel[1]), channel(pixel[2]))
     }
 }
 
@@ -76,6 +87,71 @@ impl ImageTexture {
     pub fn new(file: &Path) -> ImageTexture {
         let img = image::open(file).expect("File not found");
         //TODO: Write a log statement, which checks if the image is empty.
-        ImageTexture { img }
+        ImageTexture::from_image(img)
+    }
+
+    /// Returns the texture of the image in the given file, or an error if it can't be read.
+    pub fn open(file: &Path) -> Result<ImageTexture, String> {
+        image::open(file)
+            .map(ImageTexture::from_image)
+            .map_err(|err| format!("Couldn't read the image {}: {err}", file.display()))
+    }
+
+    /// Returns the texture of an image which was already read, for e.g. from a scene file.
+    pub(crate) fn from_image(img: DynamicImage) -> ImageTexture {
+        ImageTexture {
+            img: Arc::new(img),
+            srgb: false,
+            factor: Color::new(1.0, 1.0, 1.0),
+        }
+    }
+
+    /// Returns the texture decoding the bytes of the image from sRGB to linear colors.
+    pub(crate) fn with_srgb(self) -> ImageTexture {
+        ImageTexture { srgb: true, ..self }
+    }
+
+    /// Returns the texture with its colors multiplied by the `factor`.
+    pub(crate) fn with_factor(self, factor: Color) -> ImageTexture {
+        ImageTexture { factor, ..self }
+    }
+}
+
+/// The sRGB electro-optical transfer function, which decodes an sRGB value in [0,1] to the linear
+/// value it encodes.
+fn srgb_eotf(encoded: f64) -> f64 {
+    if encoded <= 0.04045 {
+        encoded / 12.92
+    } else {
+        ((encoded + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    #[test]
+    fn decodes_srgb_and_multiplies_by_the_factor() {
+        let img = DynamicImage::ImageRgb8(RgbImage::from_pixel(1, 1, Rgb([128, 10, 255])));
+        let texture = ImageTexture::from_image(img);
+        let p = Point::new(0.0, 0.0, 0.0);
+
+        let raw = texture.value(0.5, 0.5, &p);
+        assert_eq!(
+            (raw.x(), raw.y(), raw.z()),
+            (128.0 / 255.0, 10.0 / 255.0, 1.0)
+        );
+
+        let texture = texture.with_srgb();
+        let linear = texture.value(0.5, 0.5, &p);
+        assert!((linear.x() - 0.215861).abs() < 1e-6, "{linear:?}");
+        assert!((linear.y() - 0.003035).abs() < 1e-6, "{linear:?}");
+        assert!((linear.z() - 1.0).abs() < 1e-12, "{linear:?}");
+
+        let factor = Color::new(0.5, 0.25, 2.0);
+        let scaled = texture.with_factor(factor).value(0.5, 0.5, &p);
+        assert!((scaled - factor * linear).length() < 1e-12, "{scaled:?}");
     }
 }
@@ -3,9 +3,11 @@ mod image_texture;
 mod perlin;
 mod solid_color;
 mod texture;
+mod vertex_color_texture;
 
 pub use checker_texture::CheckerTexture;
 pub use image_texture::ImageTexture;
 pub use perlin::{PerlinNoiseOptions, PerlinNoiseTexture};
 pub use solid_color::SolidColor;
 pub use texture::Texture;
+pub use vertex_color_texture::VertexColorTexture;
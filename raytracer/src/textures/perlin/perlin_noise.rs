@@ -18,7 +18,7 @@ use embed_doc_image::embed_doc_image;
 /// noise is that it be simple and fast, so it's usually done as a hack.
 #[embed_doc_image("whitenoise", "doc_images/white_noise.jpg")]
 #[embed_doc_image("whitenoiseblurred", "doc_images/white_noise_blurred.jpg")]
-pub(in crate::textures::perlin) trait Perlin {
+pub(in crate::textures::perlin) trait Perlin: Send + Sync {
     fn noise(&self, p: &Point) -> f64 {
         if self.use_turbulence() {
             self.turbulence(p)
@@ -1,5 +1,13 @@
+use crate::objects::HitRecord;
 use crate::{Color, Point};
 
-pub trait Texture {
+pub trait Texture: Send + Sync {
     fn value(&self, u: f64, v: f64, p: &Point) -> Color;
+
+    /// Returns the color of the texture at the point the hit record `rec` is for. Most textures
+    /// only need its texture coordinates and position, but some, like the colors of the vertices of
+    /// a mesh, need more of it.
+    fn value_at(&self, rec: &HitRecord) -> Color {
+        self.value(rec.u, rec.v, &rec.p)
+    }
 }
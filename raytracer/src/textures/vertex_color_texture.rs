@@ -0,0 +1,28 @@
+use crate::objects::HitRecord;
+use crate::textures::Texture;
+use crate::{Color, Point};
+
+/// # Vertex Colors
+/// Scanners record the color of the surface at every point they measure, so scanned meshes come
+/// with a color per vertex rather than an image. The color between the corners of a triangle is
+/// blended from the colors of the corners, by the barycentric coordinates of the point.
+///
+/// The blending is done by the [`TriangleMesh`](crate::objects::TriangleMesh) the colors were
+/// given to, when it is hit, as only the mesh knows which triangle the point is on, and where on
+/// it. This texture returns the color the mesh put in the hit record, so that the texture
+/// coordinates of the mesh stay free for other textures.
+pub struct VertexColorTexture;
+
+impl Texture for VertexColorTexture {
+    /// Without a hit record, there are no colors to look up, so this returns solid cyan as a
+    /// debugging aid, like the image texture.
+    fn value(&self, _u: f64, _v: f64, _p: &Point) -> Color {
+        Color::new(0.0, 1.0, 1.0)
+    }
+
+    /// Returns the color of the vertices blended at the point, or solid cyan if the object hit has
+    /// no colors.
+    fn value_at(&self, rec: &HitRecord) -> Color {
+        rec.color.unwrap_or(Color::new(0.0, 1.0, 1.0))
+    }
+}
@@ -0,0 +1,150 @@
+use crate::configuration::TileOrder;
+
+/// # Tiles
+/// Instead of handing out individual pixels to the render threads, the image is split into square
+/// tiles which are rendered as a whole by a single thread. Neighbouring pixels send rays into the
+/// same part of the scene, so rendering them together keeps the nodes of the BVH they traverse in
+/// the cache, and the image fills in as blocks which is easier on the eye for previews.
+///
+/// The tile covers the pixels in the columns `x0..x1` and rows `y0..y1`, where row 0 is the top of
+/// the image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tile {
+    pub x0: u32,
+    pub y0: u32,
+    pub x1: u32,
+    pub y1: u32,
+}
+
+impl Tile {
+    /// Returns the (column, row) coordinates of the pixels in the tile, row by row.
+    pub fn pixels(&self) -> impl Iterator<Item = (u32, u32)> + '_ {
+        (self.y0..self.y1).flat_map(move |y| (self.x0..self.x1).map(move |x| (x, y)))
+    }
+}
+
+/// Splits an image of the given size into tiles of `tile_size` x `tile_size` pixels (the tiles on
+/// the right and bottom edges may be smaller), and returns them in the order they should be
+/// rendered.
+pub fn tiles(width: u32, height: u32, tile_size: u32, order: TileOrder) -> Vec<Tile> {
+    let tile_size = tile_size.max(1);
+    let columns = width.div_ceil(tile_size);
+    let rows = height.div_ceil(tile_size);
+
+    let tile = |column: u32, row: u32| Tile {
+        x0: column * tile_size,
+        y0: row * tile_size,
+        x1: ((column + 1) * tile_size).min(width),
+        y1: ((row + 1) * tile_size).min(height),
+    };
+
+    let grid = (0..rows).flat_map(|row| (0..columns).map(move |column| (column, row)));
+
+    match order {
+        TileOrder::Scanline => grid.map(|(column, row)| tile(column, row)).collect(),
+        TileOrder::CenterOut => {
+            let (cx, cy) = (width as f64 / 2.0, height as f64 / 2.0);
+            let mut tiles: Vec<Tile> = grid.map(|(column, row)| tile(column, row)).collect();
+            let distance = |t: &Tile| {
+                let dx = (t.x0 + t.x1) as f64 / 2.0 - cx;
+                let dy = (t.y0 + t.y1) as f64 / 2.0 - cy;
+                dx * dx + dy * dy
+            };
+            tiles.sort_by(|a, b| distance(a).total_cmp(&distance(b)));
+
+            tiles
+        }
+        TileOrder::Spiral => spiral(columns, rows)
+            .map(|(column, row)| tile(column, row))
+            .collect(),
+    }
+}
+
+/// Walks a square spiral starting from the center of a `columns` x `rows` grid, taking 1, 1, 2, 2,
+/// 3, 3, ... steps in the directions right, down, left and up, and returns the cells which fall
+/// inside the grid.
+fn spiral(columns: u32, rows: u32) -> impl Iterator<Item = (u32, u32)> {
+    const DIRECTIONS: [(i64, i64); 4] = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+    let total = (columns * rows) as usize;
+    let (mut x, mut y) = ((columns as i64 - 1) / 2, (rows as i64 - 1) / 2);
+    let mut cells = Vec::with_capacity(total);
+    let mut steps = 1;
+    let mut direction = 0;
+
+    let visit = |x: i64, y: i64, cells: &mut Vec<(u32, u32)>| {
+        if x >= 0 && y >= 0 && x < columns as i64 && y < rows as i64 {
+            cells.push((x as u32, y as u32));
+        }
+    };
+
+    visit(x, y, &mut cells);
+    while cells.len() < total {
+        // Every step length is used twice, before growing by one.
+        for _ in 0..2 {
+            let (dx, dy) = DIRECTIONS[direction];
+            for _ in 0..steps {
+                x += dx;
+                y += dy;
+                visit(x, y, &mut cells);
+            }
+            direction = (direction + 1) % 4;
+        }
+        steps += 1;
+    }
+
+    cells.into_iter()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ORDERS: [TileOrder; 3] = [TileOrder::Scanline, TileOrder::Spiral, TileOrder::CenterOut];
+
+    /// Returns how many times each pixel of the image is covered by the tiles, in image order.
+    fn coverage(width: u32, height: u32, tiles: &[Tile]) -> Vec<u32> {
+        let mut covered = vec![0; (width * height) as usize];
+        for tile in tiles {
+            assert!(tile.x0 < tile.x1 && tile.x1 <= width, "{tile:?}");
+            assert!(tile.y0 < tile.y1 && tile.y1 <= height, "{tile:?}");
+            for (x, y) in tile.pixels() {
+                covered[(y * width + x) as usize] += 1;
+            }
+        }
+
+        covered
+    }
+
+    #[test]
+    fn covers_every_pixel_once() {
+        // Sizes which are multiples of the tile size, and ones which leave smaller tiles along the
+        // right and bottom edges, or are smaller than a single tile.
+        for (width, height, tile_size) in [(64, 32, 16), (37, 23, 8), (5, 3, 16), (17, 1, 4)] {
+            for order in ORDERS {
+                let tiles = tiles(width, height, tile_size, order);
+                let covered = coverage(width, height, &tiles);
+                assert!(
+                    covered.iter().all(|&count| count == 1),
+                    "{width}x{height} in tiles of {tile_size} in {order:?} order"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn starts_from_the_center_for_spiral_and_center_out() {
+        for order in [TileOrder::Spiral, TileOrder::CenterOut] {
+            let first = tiles(48, 48, 16, order)[0];
+            assert_eq!(
+                first,
+                Tile {
+                    x0: 16,
+                    y0: 16,
+                    x1: 32,
+                    y1: 32
+                },
+                "{order:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,131 @@
+use crate::utils::clamp;
+use crate::Color;
+use serde::Deserialize;
+
+/// # Tone Mapping
+/// The radiance gathered by the renderer is unbounded: a light with an emission of 15 and everything
+/// it illuminates directly can be way brighter than what a display can show. Tone mapping compresses
+/// this range into **[0,1]** before the color is encoded for display.
+///
+/// * `Clamp` keeps the colors as they are and clips everything above 1, which blows out the bright
+///   parts of the image.
+/// * `Reinhard` maps each channel *c* to *c / (1 + c)*, which never reaches 1, so bright areas keep
+///   their detail at the cost of some contrast.
+/// * `Aces` uses Krzysztof Narkowicz's fit of the ACES filmic curve, which gives a gentle roll-off of
+///   the highlights and a bit more contrast in the mid-tones.
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ToneMapping {
+    #[default]
+    Clamp,
+    Reinhard,
+    Aces,
+}
+
+impl ToneMapping {
+    /// Maps a linear radiance value to a linear display value in [0,1].
+    pub fn map(&self, color: Color) -> Color {
+        let map_channel = |c: f64| match self {
+            ToneMapping::Clamp => c,
+            ToneMapping::Reinhard => c / (1.0 + c),
+            ToneMapping::Aces => (c * (2.51 * c + 0.03)) / (c * (2.43 * c + 0.59) + 0.14),
+        };
+
+        Color::new(
+            clamp(map_channel(color.x()), 0.0, 1.0),
+            clamp(map_channel(color.y()), 0.0, 1.0),
+            clamp(map_channel(color.z()), 0.0, 1.0),
+        )
+    }
+}
+
+/// The output stage which turns the linear radiance of a pixel into an 8-bit sRGB color: the
+/// radiance is scaled by the exposure, given in stops (each stop doubles the brightness), tone
+/// mapped into [0,1] and finally encoded with the sRGB transfer function.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DisplayTransform {
+    pub tone_mapping: ToneMapping,
+    pub exposure: f64,
+}
+
+impl DisplayTransform {
+    pub fn new(tone_mapping: ToneMapping, exposure: f64) -> DisplayTransform {
+        DisplayTransform {
+            tone_mapping,
+            exposure,
+        }
+    }
+
+    /// Returns the 8-bit sRGB encoded color for the given linear radiance.
+    pub fn apply(&self, color: &Color) -> [u8; 3] {
+        let exposed = *color * 2.0_f64.powf(self.exposure);
+        let mapped = self.tone_mapping.map(exposed);
+
+        // Write the translated [0,255] value of each color component
+        let encode = |c: f64| (256.0 * clamp(srgb_oetf(c), 0.0, 0.999)) as u8;
+        [encode(mapped.x()), encode(mapped.y()), encode(mapped.z())]
+    }
+}
+
+/// # sRGB Transfer Function
+/// Displays expect colors encoded with the sRGB transfer function rather than linear values. It is
+/// close to a gamma of 2.2, but has a short linear segment near black to avoid an infinite slope at
+/// zero:
+///
+/// ```math
+///     V = 12.92L                      if L ≤ 0.0031308
+///     V = 1.055L^{1/2.4} - 0.055      otherwise
+/// ```
+#[inline]
+fn srgb_oetf(linear: f64) -> f64 {
+    if linear <= 0.0031308 {
+        12.92 * linear
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The inverse of the sRGB transfer function, which displays decode the colors with.
+    fn srgb_eotf(encoded: f64) -> f64 {
+        if encoded <= 0.04045 {
+            encoded / 12.92
+        } else {
+            ((encoded + 0.055) / 1.055).powf(2.4)
+        }
+    }
+
+    #[test]
+    fn srgb_round_trips() {
+        for code in 0..=255_u8 {
+            let encoded = (code as f64 + 0.5) / 256.0;
+            let linear = srgb_eotf(encoded);
+            assert!((srgb_oetf(linear) - encoded).abs() < 1e-12, "{encoded}");
+
+            let color = Color::new(linear, linear, linear);
+            assert_eq!(DisplayTransform::default().apply(&color), [code; 3]);
+        }
+    }
+
+    #[test]
+    fn tone_mapping_is_monotonic() {
+        for tone_mapping in [ToneMapping::Reinhard, ToneMapping::Aces] {
+            let mut previous = 0.0;
+            for i in 1..=10000 {
+                let c = i as f64 * 0.01;
+                let mapped = tone_mapping.map(Color::new(c, c, c));
+                assert!(
+                    mapped.x() > 0.0 && mapped.x() <= 1.0,
+                    "{tone_mapping:?}({c})"
+                );
+                assert!(mapped.x() >= previous, "{tone_mapping:?}({c})");
+                assert_eq!(mapped.x(), mapped.y());
+                assert_eq!(mapped.x(), mapped.z());
+                previous = mapped.x();
+            }
+        }
+    }
+}
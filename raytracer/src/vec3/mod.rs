@@ -1,5 +1,7 @@
+mod onb;
 mod vec3_impl;
 
+pub(crate) use onb::Onb;
 pub use vec3_impl::*;
 
 pub type Point = Vec3;
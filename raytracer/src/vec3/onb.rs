@@ -0,0 +1,33 @@
+use crate::Vec3;
+
+/// An orthonormal basis: three unit vectors at right angles to each other. Directions which are easy
+/// to pick around the *z* axis, like the ones in a cone, can be turned around any other axis by
+/// building a basis with its *w* vector along that axis, and going from the local coordinates to
+/// the basis.
+pub(crate) struct Onb {
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+}
+
+impl Onb {
+    /// Returns a basis whose *w* vector points along `n`.
+    pub(crate) fn new(n: &Vec3) -> Onb {
+        let w = n.unit_vector();
+        // Any vector which isn't parallel to w gives the other two vectors of the basis.
+        let a = if w.x().abs() > 0.9 {
+            Vec3::new(0.0, 1.0, 0.0)
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let v = w.cross(&a).unit_vector();
+        let u = w.cross(&v);
+
+        Onb { u, v, w }
+    }
+
+    /// Returns the vector with the coordinates `a` in this basis.
+    pub(crate) fn local(&self, a: &Vec3) -> Vec3 {
+        a.x() * self.u + a.y() * self.v + a.z() * self.w
+    }
+}
@@ -1,4 +1,5 @@
-use crate::rtweekend::{random, random_in_unit_interval};
+use crate::rtweekend::{random, PI};
+use crate::samplers::{sample_1d, sample_2d};
 use embed_doc_image::embed_doc_image;
 use std::ops::{Add, AddAssign, Div, DivAssign, Index, IndexMut, Mul, MulAssign, Neg, Sub};
 
@@ -58,14 +59,15 @@ impl Vec3 {
         f64::abs(self.e[0]) < S && f64::abs(self.e[1]) < S && f64::abs(self.e[2]) < S
     }
 
+    /// Returns a random point on the surface of the unit sphere. The height *z* is uniform in
+    /// [-1,1], which by Archimedes' hat-box theorem spreads the points uniformly over the sphere.
     pub fn random_unit_vector() -> Vec3 {
-        Vec3 {
-            e: [
-                random_in_unit_interval(),
-                random_in_unit_interval(),
-                random_in_unit_interval(),
-            ],
-        }
+        let (u, v) = sample_2d();
+        let z = 1.0 - 2.0 * u;
+        let r = f64::sqrt(f64::max(0.0, 1.0 - z * z));
+        let phi = 2.0 * PI * v;
+
+        Vec3::new(r * phi.cos(), r * phi.sin(), z)
     }
 
     pub fn random_vector(min: f64, max: f64) -> Vec3 {
@@ -92,19 +94,18 @@ impl Vec3 {
     ///
     /// ![Generating a random diffuse bounce ray][randomdiffuseray]
     ///
-    /// We need a way to pick a random point in a unit radius sphere. We'll use a rejection algorithm:
-    /// First, pick a random point in the unit cube where x, y, and z all range from -1 to +1. Reject
-    /// this point and try again if the point is outside the sphere.
+    /// We need a way to pick a random point in a unit radius sphere. A rejection algorithm, which picks
+    /// random points in the unit cube until one falls inside the sphere, needs a varying amount of
+    /// random numbers, which doesn't play well with the samplers. Instead, pick a random direction,
+    /// and a random distance along it. As the volume of a ball grows with the cube of its radius,
+    /// the distance is the cube root of a uniform random number, to spread the points evenly.
     #[embed_doc_image("raybounces", "doc_images/light_ray_bounces.jpg")]
     #[embed_doc_image("randomdiffuseray", "doc_images/generating_a_random_diffuse_ray.jpg")]
     pub fn random_vector_in_unit_sphere() -> Vec3 {
-        loop {
-            let p = Self::random_vector(-1.0, 1.0);
-            if p.length_squared() >= 1.0 {
-                continue;
-            }
-            return p;
-        }
+        let direction = Self::random_unit_vector();
+        let radius = sample_1d().cbrt();
+
+        radius * direction
     }
 
     /// # True Lambertian Reflection
@@ -148,14 +149,23 @@ impl Vec3 {
         r_out_perpendicular + r_out_parallel
     }
 
+    /// Returns a random point in the unit disk in the xy plane. The unit square is mapped onto the
+    /// disk with Shirley and Chiu's concentric mapping, which turns squares around the center into
+    /// circles, so points which are spread out evenly over the square stay that way on the disk.
     pub fn random_vector_in_unit_disk() -> Vec3 {
-        loop {
-            let p = Vec3::new(random(-1.0, 1.0), random(-1.0, 1.0), 0.0);
-            if p.length_squared() >= 1.0 {
-                continue;
-            }
-            return p;
+        let (u, v) = sample_2d();
+        let (a, b) = (2.0 * u - 1.0, 2.0 * v - 1.0);
+        if a == 0.0 && b == 0.0 {
+            return Vec3::default();
         }
+
+        let (r, theta) = if a.abs() > b.abs() {
+            (a, PI / 4.0 * (b / a))
+        } else {
+            (b, PI / 2.0 - PI / 4.0 * (a / b))
+        };
+
+        Vec3::new(r * theta.cos(), r * theta.sin(), 0.0)
     }
 }
 
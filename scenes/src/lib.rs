@@ -2,7 +2,11 @@ extern crate raytracer;
 mod scenes;
 
 use indicatif::{ProgressBar, ProgressStyle};
-use raytracer::{load_configuration, render, ImageSettings, Scene};
+use raytracer::utils::seed_random_generator;
+use raytracer::{
+    load_configuration, render, CancellationToken, ImageSettings, RenderOutcome, RenderedImage, Renderer,
+    Scene,
+};
 use std::path::Path;
 
 pub use scenes::*;
@@ -100,48 +104,88 @@ pub fn render_scene(filename: String, function: Scenes) {
     );
 
     let scene =scene(function, &settings);
+    let path = settings.path.clone();
+    let format = settings.format;
+    let aovs = settings.aovs.clone();
 
-    render(settings, scene, |i: f32| {
+    let outcome = render(settings, scene, &CancellationToken::new(), |i: f32| {
         pb.set_position(i as u64);
         pb.set_message(format!("{i:.2}%"));
-    });
+    })
+    .expect("Unable to render the scene");
 
     pb.finish_with_message("Done!");
+
+    let image = outcome.image();
+    let path = Path::new(&path);
+    image
+        .save(path, format)
+        .expect("Unable to save image in specified format");
+
+    // Only the passes asked for are saved, and not the ones rendered just to guide the denoiser.
+    for aov in aovs {
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let mut name = format!("{stem}_{}", aov.name());
+        if let Some(extension) = path.extension() {
+            name = format!("{name}.{}", extension.to_string_lossy());
+        }
+        image
+            .save_aov(aov, &path.with_file_name(name), format)
+            .expect("Unable to save output variable in specified format");
+    }
 }
 
-pub fn render_scene_buffer<F>(function: Scenes, progress_callback: F)
+pub fn render_scene_buffer<F>(function: Scenes, cancel: &CancellationToken, progress_callback: F) -> Result<RenderOutcome, String>
     where F: Fn(f32) + Sync + Send
 {
     let settings = load_configuration().expect("Couldnot read settings");
 
     let scene = scene(function, &settings);
 
-    render(settings, scene, progress_callback);
+    render(settings, scene, cancel, progress_callback)
+}
+
+/// Renders the scene progressively, handing the image to `on_pass` after every pass so that it can
+/// be displayed while it refines. Returning `false` from `on_pass` stops the render.
+pub fn render_scene_progressive<P, F>(function: Scenes, cancel: &CancellationToken, on_pass: P, progress_callback: F) -> Result<RenderOutcome, String>
+    where P: FnMut(&RenderedImage) -> bool,
+          F: Fn(f32) + Sync + Send
+{
+    let settings = load_configuration().expect("Couldnot read settings");
+
+    let scene = scene(function, &settings);
+
+    let mut renderer = Renderer::new(settings, scene)?;
+    Ok(renderer.render_progressive(cancel, on_pass, progress_callback))
 }
 
 fn scene(scn: Scenes, settings: &ImageSettings) -> Scene {
+    // The scenes place objects and generate noise textures at random, so seed the generator to
+    // build the same scene every time.
+    seed_random_generator(settings.seed);
+
     match scn {
-        Scenes::EarthScene => earth_scene(&settings),
-        Scenes::CornellBoxWithSmoke => cornell_smoke(&settings),
-        Scenes::CornellBoxWithTwoBoxes => cornell_box_with_two_boxes(&settings),
-        Scenes::PerlinSmoothedTexturedSpheres => perlin_smoothed_textured_spheres(&settings),
-        Scenes::EmptyCornellBox => empty_cornell_box(&settings),
-        Scenes::RectangleLightScene => rectangle_light_scene(&settings),
-        Scenes::RTWeekendOneFinalScene => rtweekend_one_final_scene(&settings),
+        Scenes::EarthScene => earth_scene(settings),
+        Scenes::CornellBoxWithSmoke => cornell_smoke(settings),
+        Scenes::CornellBoxWithTwoBoxes => cornell_box_with_two_boxes(settings),
+        Scenes::PerlinSmoothedTexturedSpheres => perlin_smoothed_textured_spheres(settings),
+        Scenes::EmptyCornellBox => empty_cornell_box(settings),
+        Scenes::RectangleLightScene => rectangle_light_scene(settings),
+        Scenes::RTWeekendOneFinalScene => rtweekend_one_final_scene(settings),
         Scenes::RTWeekendOneFinalSceneWithMovingSpheres => {
-            rtweekend_one_final_scene_with_moving_spheres(&settings)
+            rtweekend_one_final_scene_with_moving_spheres(settings)
         }
         Scenes::RTWeekendOneFinalSceneWithMovingSpheresCheckeredTexture => {
-            rtweekend_one_final_scene_with_moving_spheres_checkered_texture(&settings)
+            rtweekend_one_final_scene_with_moving_spheres_checkered_texture(settings)
         }
-        Scenes::RTNextWeekFinalScene => rtnextweek_final_scene(&settings),
-        Scenes::MarbleSpheres => marble_spheres(&settings),
+        Scenes::RTNextWeekFinalScene => rtnextweek_final_scene(settings),
+        Scenes::MarbleSpheres => marble_spheres(settings),
         Scenes::SceneWithHollowGlassSphere
         | Scenes::DielectricShinySphere
-        | Scenes::SceneWithAlternateViewPoint => earth_scene(&settings),
-        Scenes::PerlinTexturedSpheres => perlin_textured_spheres(&settings),
+        | Scenes::SceneWithAlternateViewPoint => earth_scene(settings),
+        Scenes::PerlinTexturedSpheres => perlin_textured_spheres(settings),
         Scenes::WideAngleCameraScene => scene_for_wide_angle_camera(),
         Scenes::SceneWithDepthofFieldCamera => scene_with_depth_of_field_camera(),
-        Scenes::TwoCheckeredSpheres => two_checkered_spheres(&settings),
+        Scenes::TwoCheckeredSpheres => two_checkered_spheres(settings),
     }
 }
\ No newline at end of file
@@ -1,6 +1,6 @@
 use raytracer::materials::{Dielectric, LambertianMaterial, Metal};
 use raytracer::objects::{
-    Hittable, MovingSphere, RotateY, Sphere, Translate, World, XYRect, XZRect, YZRect,
+    Hittable, MovingSphere, Sphere, Transform, World, XYRect, XZRect, YZRect,
 };
 use raytracer::utils::{random, random_in_unit_interval, PI};
 use raytracer::{objects, Camera, Color, ImageSettings, Point, Scene, Vec3};
@@ -808,8 +808,8 @@ pub fn cornell_box_with_two_boxes(settings: &ImageSettings) -> Scene {
         Point::new(165.0, 330.0, 165.0),
         white.clone(),
     ));
-    box1 = Arc::new(RotateY::new(box1, 15.0));
-    let arcbox1 = Arc::new(Translate::new(box1, Vec3::new(265.0, 0.0, 295.0)));
+    box1 = Arc::new(Transform::rotate_y(box1, 15.0));
+    let arcbox1 = Arc::new(Transform::translate(box1, Vec3::new(265.0, 0.0, 295.0)));
     world.add(arcbox1);
 
     let mut box2: Arc<dyn Hittable> = Arc::new(objects::Box::new(
@@ -817,8 +817,8 @@ pub fn cornell_box_with_two_boxes(settings: &ImageSettings) -> Scene {
         Point::new(165.0, 165.0, 165.0),
         white,
     ));
-    box2 = Arc::new(RotateY::new(box2, -18.0));
-    let arcbox2 = Arc::new(Translate::new(box2, Vec3::new(130.0, 0.0, 65.0)));
+    box2 = Arc::new(Transform::rotate_y(box2, -18.0));
+    let arcbox2 = Arc::new(Transform::translate(box2, Vec3::new(130.0, 0.0, 65.0)));
     world.add(arcbox2);
 
     let lookfrom = Point::new(278.0, 278.0, -800.0);
@@ -855,16 +855,16 @@ pub fn cornell_smoke(settings: &ImageSettings) -> Scene {
         Point::new(165.0, 330.0, 165.0),
         white.clone(),
     ));
-    box1 = Arc::new(RotateY::new(box1, 15.0));
-    box1 = Arc::new(Translate::new(box1, Vec3::new(265.0, 0.0, 295.0)));
+    box1 = Arc::new(Transform::rotate_y(box1, 15.0));
+    box1 = Arc::new(Transform::translate(box1, Vec3::new(265.0, 0.0, 295.0)));
 
     let mut box2: Arc<dyn Hittable> = Arc::new(objects::Box::new(
         Point::new(0.0, 0.0, 0.0),
         Point::new(165.0, 165.0, 165.0),
         white,
     ));
-    box2 = Arc::new(RotateY::new(box2, -18.0));
-    box2 = Arc::new(Translate::new(box2, Vec3::new(130.0, 0.0, 65.0)));
+    box2 = Arc::new(Transform::rotate_y(box2, -18.0));
+    box2 = Arc::new(Transform::translate(box2, Vec3::new(130.0, 0.0, 65.0)));
 
     world.add(Arc::new(ConstantMedium::new_with_color(
         box1,
@@ -1010,8 +1010,8 @@ pub fn rtnextweek_final_scene(settings: &ImageSettings) -> Scene {
         )));
     }
 
-    world.add(Arc::new(Translate::new(
-        Arc::new(RotateY::new(Arc::new(boxes2), 15.0)),
+    world.add(Arc::new(Transform::translate(
+        Arc::new(Transform::rotate_y(Arc::new(boxes2), 15.0)),
         Vec3::new(-100.0, 270.0, 395.0),
     )));
 